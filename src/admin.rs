@@ -0,0 +1,500 @@
+use std::sync::Arc;
+
+use async_openai::{config::OpenAIConfig, error::OpenAIError, Client};
+use axum::{http::StatusCode, Extension, Json};
+use axum_thiserror::ErrorStatus;
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use futures_util::{stream, StreamExt, TryStreamExt};
+use mongodb::{
+    bson::{self, doc, oid::ObjectId, Bson, Document},
+    error::{ErrorKind, WriteFailure},
+    options::FindOptions,
+    Collection,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config::AppConfig;
+use crate::crypto::{CryptoError, EntryCipher};
+use crate::routes::{date_filter, JournalEntry};
+use crate::similar::embed_text;
+use crate::throttle::{take_turn, OpenAiThrottle, ThrottleError};
+use crate::usage::UsageCounter;
+
+/// Nombre d'appels simultanés à l'API d'embeddings pendant le backfill, pour rester sous
+/// les limites de débit d'OpenAI (le client réessaie de lui-même les réponses 429).
+pub const BACKFILL_CONCURRENCY: usize = 4;
+
+#[derive(Deserialize, Debug)]
+pub struct DuplicateDocument {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DuplicateGroup {
+    pub documents: Vec<DuplicateDocument>,
+}
+
+impl DuplicateGroup {
+    /// Identifiants des documents à supprimer : tous sauf le plus récemment modifié.
+    /// À `updated_at` égal (ou absent), l'`ObjectId` le plus récent est conservé.
+    pub fn ids_to_remove(&self) -> Vec<ObjectId> {
+        let Some(kept) = self
+            .documents
+            .iter()
+            .max_by_key(|document| (document.updated_at, document.id))
+        else {
+            return vec![];
+        };
+
+        self.documents
+            .iter()
+            .filter(|document| document.id != kept.id)
+            .map(|document| document.id)
+            .collect()
+    }
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum DedupeError {
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Bson(bson::de::Error),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DedupeResult {
+    pub removed: u64,
+}
+
+pub async fn dedupe_entries(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+) -> Result<Json<DedupeResult>, DedupeError> {
+    let groups: Vec<DuplicateGroup> = mongo_entries
+        .aggregate(
+            [
+                doc! {
+                    "$group": {
+                        "_id": "$date",
+                        "documents": { "$push": { "_id": "$_id", "updated_at": "$updated_at" } },
+                        "count": { "$sum": 1 }
+                    }
+                },
+                doc! { "$match": { "count": { "$gt": 1 } } },
+            ],
+            None,
+        )
+        .await
+        .map_err(DedupeError::Mongo)?
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(DedupeError::Mongo)?
+        .into_iter()
+        .map(bson::from_document)
+        .collect::<Result<_, _>>()
+        .map_err(DedupeError::Bson)?;
+
+    let ids: Vec<ObjectId> = groups
+        .iter()
+        .flat_map(DuplicateGroup::ids_to_remove)
+        .collect();
+    if ids.is_empty() {
+        return Ok(Json(DedupeResult { removed: 0 }));
+    }
+
+    let result = mongo_entries
+        .delete_many(doc! { "_id": { "$in": ids } }, None)
+        .await
+        .map_err(DedupeError::Mongo)?;
+
+    Ok(Json(DedupeResult {
+        removed: result.deleted_count,
+    }))
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum BackfillError {
+    #[error(transparent)]
+    #[status(StatusCode::TOO_MANY_REQUESTS)]
+    Throttle(ThrottleError),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    OpenAI(OpenAIError),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Crypto(CryptoError),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct BackfillResult {
+    pub processed: u64,
+    pub failed: u64,
+}
+
+/// Calcule l'embedding d'une entrée qui n'en a pas, à partir de son texte ou à défaut de son résumé.
+/// Les tokens consommés sont ajoutés à `usage`.
+pub async fn with_embedding(
+    openai: &Client<OpenAIConfig>,
+    throttle: Option<&OpenAiThrottle>,
+    usage: &UsageCounter,
+    model: &str,
+    cipher: &EntryCipher,
+    entry: JournalEntry,
+) -> Result<JournalEntry, BackfillError> {
+    let mut entry = entry.decrypted(cipher).map_err(BackfillError::Crypto)?;
+    let text = entry
+        .raw_text
+        .clone()
+        .unwrap_or_else(|| entry.short_summary.clone());
+    take_turn(throttle).await.map_err(BackfillError::Throttle)?;
+    let embedding = embed_text(openai, model, &text)
+        .await
+        .map_err(BackfillError::OpenAI)?;
+    usage.record(embedding.as_ref().map(|(_, tokens)| *tokens));
+    entry.embedding = embedding.map(|(embedding, _)| embedding);
+    Ok(entry)
+}
+
+/// Ajoute un embedding aux entrées qui n'en ont pas. Chaque entrée est enregistrée
+/// dès que son embedding est calculé : relancer l'opération reprend là où elle s'est arrêtée.
+pub async fn backfill_embeddings(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(openai): Extension<Arc<Client<OpenAIConfig>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(cipher): Extension<Arc<EntryCipher>>,
+    Extension(usage): Extension<Arc<UsageCounter>>,
+    throttle: Option<Extension<Arc<OpenAiThrottle>>>,
+) -> Result<Json<BackfillResult>, BackfillError> {
+    let throttle = throttle.as_deref().map(Arc::as_ref);
+    let entries: Vec<JournalEntry> = mongo_entries
+        .find(doc! { "embedding": { "$exists": false } }, None)
+        .await
+        .map_err(BackfillError::Mongo)?
+        .try_collect()
+        .await
+        .map_err(BackfillError::Mongo)?;
+
+    let results: Vec<Result<bool, BackfillError>> = stream::iter(entries)
+        .map(|entry| {
+            let (mongo_entries, openai, cipher, usage) = (&mongo_entries, &openai, &cipher, &usage);
+            let model = &config.embedding_model;
+            async move {
+                let entry = with_embedding(openai, throttle, usage, model, cipher, entry).await?;
+                let Some(embedding) = entry.embedding else {
+                    return Ok(false);
+                };
+                mongo_entries
+                    .update_one(
+                        date_filter(entry.date),
+                        doc! { "$set": { "embedding": embedding } },
+                        None,
+                    )
+                    .await
+                    .map_err(BackfillError::Mongo)?;
+                Ok(true)
+            }
+        })
+        .buffer_unordered(BACKFILL_CONCURRENCY)
+        .collect()
+        .await;
+
+    let mut result = BackfillResult {
+        processed: 0,
+        failed: 0,
+    };
+    for outcome in results {
+        match outcome {
+            Ok(true) => result.processed += 1,
+            Ok(false) => {}
+            Err(error) => {
+                tracing::warn!("Could not backfill an embedding: {error}");
+                result.failed += 1;
+            }
+        }
+    }
+    Ok(Json(result))
+}
+
+/// Code d'erreur Mongo d'une clé unique déjà prise.
+const DUPLICATE_KEY: i32 = 11000;
+
+/// Jour d'une entrée quel que soit le format de son champ `date` : la chaîne
+/// `YYYY-MM-DD` attendue, une chaîne RFC 3339 ou une date BSON. Les anciens
+/// upserts écrivaient minuit à Paris, d'où la conversion dans le fuseau configuré.
+pub fn stored_date(date: &Bson, timezone: Tz) -> Option<NaiveDate> {
+    match date {
+        Bson::String(date) => NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .ok()
+            .or_else(|| {
+                DateTime::parse_from_rfc3339(date)
+                    .ok()
+                    .map(|date| date.with_timezone(&timezone).date_naive())
+            }),
+        Bson::DateTime(date) => Some(date.to_chrono().with_timezone(&timezone).date_naive()),
+        _ => None,
+    }
+}
+
+/// Documents dont le champ `date` n'est pas encore une chaîne `YYYY-MM-DD`.
+pub fn unnormalized_dates_filter() -> Document {
+    doc! {
+        "$or": [
+            { "date": { "$not": { "$type": "string" } } },
+            { "date": { "$not": { "$regex": r"^\d{4}-\d{2}-\d{2}$" } } },
+        ]
+    }
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum NormalizeDatesError {
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct NormalizeDatesResult {
+    pub converted: u64,
+    /// Documents dont la date n'a pas pu être lue, laissés tels quels.
+    pub unreadable: Vec<ObjectId>,
+    /// Documents dont le jour existe déjà sous forme de chaîne, à dédoublonner
+    /// avec `/admin/dedupe` après conversion manuelle.
+    pub conflicts: Vec<ObjectId>,
+}
+
+/// Réécrit sous la forme sérialisée par `JournalEntry` les dates stockées dans
+/// un autre format, pour que `date_filter` et les tris retrouvent toutes les entrées.
+pub async fn normalize_dates(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<Json<NormalizeDatesResult>, NormalizeDatesError> {
+    let documents = mongo_entries.clone_with_type::<Document>();
+    let stored: Vec<Document> = documents
+        .find(
+            unnormalized_dates_filter(),
+            FindOptions::builder()
+                .projection(doc! { "_id": 1, "date": 1 })
+                .build(),
+        )
+        .await
+        .map_err(NormalizeDatesError::Mongo)?
+        .try_collect()
+        .await
+        .map_err(NormalizeDatesError::Mongo)?;
+
+    let mut result = NormalizeDatesResult::default();
+    for document in stored {
+        let Ok(id) = document.get_object_id("_id") else {
+            continue;
+        };
+        let Some(date) = document
+            .get("date")
+            .and_then(|date| stored_date(date, config.timezone))
+        else {
+            result.unreadable.push(id);
+            continue;
+        };
+
+        match documents
+            .update_one(doc! { "_id": id }, doc! { "$set": date_filter(date) }, None)
+            .await
+        {
+            Ok(_) => result.converted += 1,
+            Err(error) => match *error.kind {
+                ErrorKind::Write(WriteFailure::WriteError(ref write))
+                    if write.code == DUPLICATE_KEY =>
+                {
+                    result.conflicts.push(id)
+                }
+                _ => return Err(NormalizeDatesError::Mongo(error)),
+            },
+        }
+    }
+
+    Ok(Json(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{routing::post, Extension, Json, Router};
+    use chrono::NaiveDate;
+    use chrono_tz::Europe::Paris;
+    use futures_util::TryStreamExt;
+    use mongodb::bson::{self, doc, oid::ObjectId, Bson, Document};
+    use serde_json::json;
+
+    use crate::{
+        config::AppConfig, crypto::EntryCipher, routes::JournalEntry, similar::EMBEDDING_MODEL,
+        testing::mock_openai, usage::UsageCounter,
+    };
+
+    use super::{
+        normalize_dates, stored_date, unnormalized_dates_filter, with_embedding, DuplicateGroup,
+    };
+
+    #[test]
+    fn keep_most_recent_duplicate() {
+        let (old, recent, legacy) = (ObjectId::new(), ObjectId::new(), ObjectId::new());
+        let group: DuplicateGroup = bson::from_document(doc! {
+            "_id": "2024-01-24",
+            "documents": [
+                { "_id": old, "updated_at": "2024-01-24T20:00:00Z" },
+                { "_id": recent, "updated_at": "2024-01-25T08:00:00Z" },
+                { "_id": legacy },
+            ],
+            "count": 3
+        })
+        .unwrap();
+
+        let mut removed = group.ids_to_remove();
+        removed.sort();
+        let mut expected = vec![old, legacy];
+        expected.sort();
+        assert_eq!(removed, expected);
+    }
+
+    #[test]
+    fn keep_newest_object_id_without_updated_at() {
+        let (first, second) = (ObjectId::new(), ObjectId::new());
+        let group: DuplicateGroup = bson::from_document(doc! {
+            "documents": [{ "_id": second }, { "_id": first }]
+        })
+        .unwrap();
+
+        assert_eq!(group.ids_to_remove(), vec![first]);
+    }
+
+    #[tokio::test]
+    async fn backfill_missing_embedding() {
+        let openai = mock_openai(Router::new().route(
+            "/embeddings",
+            post(|| async {
+                Json(json!({
+                    "object": "list",
+                    "data": [{ "index": 0, "object": "embedding", "embedding": [0.1, 0.2, 0.3] }],
+                    "model": "text-embedding-3-small",
+                    "usage": { "prompt_tokens": 4, "total_tokens": 4 }
+                }))
+            }),
+        ))
+        .await;
+        let entry = JournalEntry {
+            date: NaiveDate::from_ymd_opt(2024, 1, 24).unwrap(),
+            short_summary: "Belle course".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(entry.embedding, None);
+
+        let usage = UsageCounter::default();
+        let entry = with_embedding(
+            &openai,
+            None,
+            &usage,
+            EMBEDDING_MODEL,
+            &EntryCipher::default(),
+            entry,
+        )
+        .await
+        .unwrap();
+        assert_eq!(entry.embedding, Some(vec![0.1, 0.2, 0.3]));
+        assert_eq!(usage.stats().prompt_tokens, 4);
+    }
+
+    #[test]
+    fn read_dates_in_both_formats() {
+        let (string, datetime): (JournalEntry, Document) = (
+            JournalEntry {
+                date: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+                ..Default::default()
+            },
+            // Minuit à Paris, soit la veille à 23 h en UTC
+            doc! { "date": bson::DateTime::parse_rfc3339_str("2024-02-29T23:00:00Z").unwrap() },
+        );
+        let string = bson::to_document(&string).unwrap();
+        let expected = NaiveDate::from_ymd_opt(2024, 3, 1);
+
+        for document in [&string, &datetime] {
+            assert_eq!(stored_date(document.get("date").unwrap(), Paris), expected);
+        }
+        assert_eq!(
+            stored_date(
+                &Bson::String("2024-03-01T00:00:00+01:00".to_string()),
+                Paris
+            ),
+            expected
+        );
+        assert_eq!(stored_date(&Bson::String("hier".to_string()), Paris), None);
+        assert_eq!(stored_date(&Bson::Int32(20240301), Paris), None);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server in MONGO"]
+    async fn normalize_stored_dates() {
+        dotenvy::dotenv().ok();
+        let options =
+            crate::mongo_client_options(&std::env::var("MONGO").unwrap(), crate::DEFAULT_APP_NAME)
+                .await
+                .unwrap();
+        let entries = mongodb::Client::with_options(options)
+            .unwrap()
+            .database("journai_test")
+            .collection::<JournalEntry>("normalize_dates");
+        entries.drop(None).await.unwrap();
+        entries
+            .insert_one(
+                JournalEntry {
+                    date: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+                    ..Default::default()
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        let mut legacy = bson::to_document(&JournalEntry::default()).unwrap();
+        legacy.insert(
+            "date",
+            bson::DateTime::parse_rfc3339_str("2024-03-01T23:00:00Z").unwrap(),
+        );
+        entries
+            .clone_with_type::<Document>()
+            .insert_one(legacy, None)
+            .await
+            .unwrap();
+
+        let Json(result) = normalize_dates(
+            Extension(Arc::new(entries.clone())),
+            Extension(Arc::new(AppConfig::default())),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.converted, 1);
+        assert!(result.unreadable.is_empty() && result.conflicts.is_empty());
+
+        let dates: Vec<NaiveDate> = entries
+            .find(doc! { "date": { "$type": "string" } }, None)
+            .await
+            .unwrap()
+            .map_ok(|entry| entry.date)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(dates.len(), 2);
+        assert!(dates.contains(&NaiveDate::from_ymd_opt(2024, 3, 2).unwrap()));
+        assert!(entries
+            .find_one(unnormalized_dates_filter(), None)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,186 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use subtle::ConstantTimeEq;
+
+use crate::webhook::WEBHOOK_ROUTE;
+
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
+/// Clé exigée dans `X-API-Key`. Sans clé configurée, l'API reste ouverte.
+#[derive(Debug, Default, Clone)]
+pub struct ApiKeyConfig {
+    pub api_key: Option<String>,
+    /// Exige aussi la clé pour les lectures.
+    pub protect_reads: bool,
+}
+
+impl ApiKeyConfig {
+    /// Configuration lue depuis `API_KEY` et `API_KEY_PROTECT_READS`.
+    pub fn from_env() -> Self {
+        let api_key = std::env::var("API_KEY").ok().filter(|key| !key.is_empty());
+        if api_key.is_none() {
+            tracing::warn!("API_KEY is not set, write routes are not protected");
+        }
+        ApiKeyConfig {
+            api_key,
+            protect_reads: std::env::var("API_KEY_PROTECT_READS")
+                .is_ok_and(|value| value == "true"),
+        }
+    }
+
+    fn requires_key(&self, method: &Method, path: &str) -> bool {
+        self.protect_reads
+            || is_sensitive(path)
+            || !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+    }
+
+    /// Compare la clé en temps constant pour ne rien révéler de son contenu.
+    fn accepts(&self, key: Option<&[u8]>) -> bool {
+        match (&self.api_key, key) {
+            (None, _) => true,
+            (Some(expected), Some(key)) => bool::from(expected.as_bytes().ct_eq(key)),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+/// Sauvegardes et administration exposent toute la base : la clé y est exigée
+/// même en lecture.
+fn is_sensitive(path: &str) -> bool {
+    let path = path.strip_prefix("/v1").unwrap_or(path);
+    path == "/backup" || path == "/restore-backup" || path.starts_with("/admin/")
+}
+
+pub async fn require_api_key(
+    State(config): State<Arc<ApiKeyConfig>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let key = request
+        .headers()
+        .get(API_KEY_HEADER)
+        .map(|value| value.as_bytes());
+    // Le webhook vérifie lui-même son secret
+    let exempt = request.uri().path().ends_with(WEBHOOK_ROUTE);
+    if config.requires_key(request.method(), request.uri().path())
+        && !exempt
+        && !config.accepts(key)
+    {
+        return (StatusCode::UNAUTHORIZED, "missing or invalid API key").into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+        middleware,
+        routing::{get, post},
+        Router,
+    };
+    use tower::ServiceExt;
+
+    use super::{require_api_key, ApiKeyConfig};
+
+    fn app(protect_reads: bool) -> Router {
+        Router::new()
+            .route("/", get(|| async {}).post(|| async {}))
+            .route("/v1/webhook/entry", post(|| async {}))
+            .route("/v1/backup", get(|| async {}))
+            .route("/v1/admin/config", get(|| async {}))
+            .layer(middleware::from_fn_with_state(
+                Arc::new(ApiKeyConfig {
+                    api_key: Some("secret".to_string()),
+                    protect_reads,
+                }),
+                require_api_key,
+            ))
+    }
+
+    async fn status(app: Router, method: &str, key: Option<&str>) -> StatusCode {
+        status_of(app, method, "/", key).await
+    }
+
+    async fn status_of(app: Router, method: &str, uri: &str, key: Option<&str>) -> StatusCode {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(key) = key {
+            request = request.header("X-API-Key", key);
+        }
+        app.oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn protect_writes() {
+        assert_eq!(
+            status(app(false), "POST", Some("secret")).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(app(false), "POST", Some("secreT")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(app(false), "POST", None).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(status(app(false), "GET", None).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn protect_reads_when_configured() {
+        assert_eq!(
+            status(app(true), "GET", None).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(app(true), "GET", Some("secret")).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn always_protect_backup_and_admin() {
+        for uri in ["/v1/backup", "/v1/admin/config"] {
+            assert_eq!(
+                status_of(app(false), "GET", uri, None).await,
+                StatusCode::UNAUTHORIZED
+            );
+            assert_eq!(
+                status_of(app(false), "GET", uri, Some("secret")).await,
+                StatusCode::OK
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn leave_webhook_to_its_own_secret() {
+        assert_eq!(
+            status_of(app(true), "POST", "/v1/webhook/entry", None).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn open_without_configured_key() {
+        let app = Router::new()
+            .route("/", get(|| async {}).post(|| async {}))
+            .layer(middleware::from_fn_with_state(
+                Arc::new(ApiKeyConfig::default()),
+                require_api_key,
+            ));
+        assert_eq!(status(app, "POST", None).await, StatusCode::OK);
+    }
+}
@@ -0,0 +1,252 @@
+use std::sync::Arc;
+
+use axum::{
+    body::{Body, Bytes},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    BoxError, Extension, Json,
+};
+use axum_thiserror::ErrorStatus;
+use chrono::{DateTime, Utc};
+use futures_util::{stream, Stream, StreamExt};
+use mongodb::{bson::doc, options::ReplaceOptions, Collection};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::routes::{date_filter, JournalEntry};
+
+/// Version du format de sauvegarde, à incrémenter à chaque changement incompatible.
+pub const BACKUP_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Backup {
+    pub schema_version: u32,
+    pub dumped_at: DateTime<Utc>,
+    pub entries: Vec<JournalEntry>,
+}
+
+#[derive(Serialize)]
+struct BackupHeader {
+    schema_version: u32,
+    dumped_at: DateTime<Utc>,
+}
+
+/// Produit le document de sauvegarde morceau par morceau, sans jamais garder
+/// toutes les entrées en mémoire.
+pub fn backup_stream<S, E>(
+    dumped_at: DateTime<Utc>,
+    entries: S,
+) -> impl Stream<Item = Result<Bytes, BoxError>>
+where
+    S: Stream<Item = Result<JournalEntry, E>>,
+    E: Into<BoxError>,
+{
+    let header = serde_json::to_string(&BackupHeader {
+        schema_version: BACKUP_SCHEMA_VERSION,
+        dumped_at,
+    })
+    .map(|mut header| {
+        // On rouvre l'objet pour y ajouter le tableau des entrées
+        header.pop();
+        Bytes::from(header + ",\"entries\":[")
+    })
+    .map_err(BoxError::from);
+
+    let entries = entries.enumerate().map(|(index, entry)| {
+        let entry = serde_json::to_vec(&entry.map_err(Into::into)?)?;
+        let mut chunk = Vec::with_capacity(entry.len() + 1);
+        if index > 0 {
+            chunk.push(b',');
+        }
+        chunk.extend(entry);
+        Ok(Bytes::from(chunk))
+    });
+
+    stream::once(async { header })
+        .chain(entries)
+        .chain(stream::once(async { Ok(Bytes::from_static(b"]}")) }))
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum BackupError {
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+pub async fn get_backup(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+) -> Result<Response, BackupError> {
+    let dumped_at = Utc::now();
+    let cursor = mongo_entries
+        .find(None, None)
+        .await
+        .map_err(BackupError::Mongo)?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"journai-backup-{}.json\"",
+                    dumped_at.format("%Y%m%dT%H%M%SZ")
+                ),
+            ),
+        ],
+        Body::from_stream(backup_stream(dumped_at, cursor)),
+    )
+        .into_response())
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum RestoreBackupError {
+    #[error("unsupported backup schema version {0}")]
+    #[status(StatusCode::BAD_REQUEST)]
+    UnsupportedVersion(u32),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RestoreBackupResult {
+    pub restored: u32,
+}
+
+pub async fn restore_backup(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Json(backup): Json<Backup>,
+) -> Result<Json<RestoreBackupResult>, RestoreBackupError> {
+    if backup.schema_version > BACKUP_SCHEMA_VERSION {
+        return Err(RestoreBackupError::UnsupportedVersion(
+            backup.schema_version,
+        ));
+    }
+
+    let mut restored = 0;
+    for mut entry in backup.entries {
+        // L'identifiant d'une autre base ne correspondrait pas à celui de l'entrée déjà présente
+        entry.id.clear();
+        mongo_entries
+            .replace_one(
+                date_filter(entry.date),
+                entry,
+                ReplaceOptions::builder().upsert(true).build(),
+            )
+            .await
+            .map_err(RestoreBackupError::Mongo)?;
+        restored += 1;
+    }
+
+    Ok(Json(RestoreBackupResult { restored }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{Extension, Json};
+    use chrono::{NaiveDate, Utc};
+    use futures_util::{stream, TryStreamExt};
+    use http_body_util::BodyExt;
+    use mongodb::{bson::doc, options::FindOptions};
+
+    use crate::routes::JournalEntry;
+
+    use super::{backup_stream, get_backup, restore_backup, Backup, BACKUP_SCHEMA_VERSION};
+
+    fn sample_entries() -> Vec<JournalEntry> {
+        vec![
+            JournalEntry {
+                date: NaiveDate::from_ymd_opt(2024, 1, 24).unwrap(),
+                rate: 7.5,
+                short_summary: "Bonne journée".to_string(),
+                tags: vec!["sport".to_string()],
+                ..Default::default()
+            },
+            JournalEntry {
+                date: NaiveDate::from_ymd_opt(2024, 1, 25).unwrap(),
+                rate: 3.0,
+                short_summary: "Journée difficile".to_string(),
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[tokio::test]
+    async fn serialize_backup_stream() {
+        let entries = sample_entries();
+        let dumped_at = Utc::now();
+        let chunks: Vec<_> = backup_stream(
+            dumped_at,
+            stream::iter(entries.clone().into_iter().map(Ok::<_, std::io::Error>)),
+        )
+        .try_collect()
+        .await
+        .unwrap();
+
+        let backup = serde_json::from_slice::<Backup>(&chunks.concat()).unwrap();
+        assert_eq!(backup.schema_version, BACKUP_SCHEMA_VERSION);
+        assert_eq!(backup.dumped_at, dumped_at);
+        assert_eq!(backup.entries, entries);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server in MONGO"]
+    async fn backup_round_trip() {
+        dotenvy::dotenv().ok();
+        let options =
+            crate::mongo_client_options(&std::env::var("MONGO").unwrap(), crate::DEFAULT_APP_NAME)
+                .await
+                .unwrap();
+        let database = mongodb::Client::with_options(options)
+            .unwrap()
+            .database("journai_test");
+        let source = database.collection::<JournalEntry>("backup_source");
+        let target = database.collection::<JournalEntry>("backup_target");
+        source.drop(None).await.unwrap();
+        target.drop(None).await.unwrap();
+        source.insert_many(sample_entries(), None).await.unwrap();
+
+        let response = get_backup(Extension(Arc::new(source))).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let backup = serde_json::from_slice::<Backup>(&body).unwrap();
+
+        let Json(result) = restore_backup(Extension(Arc::new(target.clone())), Json(backup))
+            .await
+            .unwrap();
+        assert_eq!(result.restored, 2);
+
+        let restored: Vec<_> = target
+            .find(
+                None,
+                FindOptions::builder().sort(doc! { "date": 1 }).build(),
+            )
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        let restored: Vec<_> = restored
+            .into_iter()
+            .map(|entry| JournalEntry {
+                id: String::new(),
+                ..entry
+            })
+            .collect();
+        assert_eq!(restored, sample_entries());
+    }
+
+    #[tokio::test]
+    async fn backup_without_entries() {
+        let chunks: Vec<_> =
+            backup_stream(Utc::now(), stream::empty::<Result<_, std::io::Error>>())
+                .try_collect()
+                .await
+                .unwrap();
+
+        let backup = serde_json::from_slice::<Backup>(&chunks.concat()).unwrap();
+        assert!(backup.entries.is_empty());
+    }
+}
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use axum::{
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Extension,
+};
+use axum_thiserror::ErrorStatus;
+use futures_util::TryStreamExt;
+use icalendar::{Calendar, Component, EventLike};
+use mongodb::{bson::doc, options::FindOptions, Collection};
+use thiserror::Error;
+
+use crate::routes::JournalEntry;
+
+/// Calendrier avec un événement sur la journée pour chaque jour d'écriture,
+/// le résumé en description.
+pub fn entries_calendar(entries: &[JournalEntry]) -> Calendar {
+    let mut calendar = Calendar::new();
+    calendar.name("Journai");
+    for entry in entries {
+        calendar.push(
+            icalendar::Event::new()
+                .uid(&format!("{}@journai", entry.date))
+                .summary(&format!("Journal ({}/10)", entry.rate))
+                .description(&entry.short_summary)
+                .all_day(entry.date)
+                .done(),
+        );
+    }
+    calendar.done()
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum CalendarError {
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+pub async fn get_calendar(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+) -> Result<impl IntoResponse, CalendarError> {
+    let entries: Vec<JournalEntry> = mongo_entries
+        .find(
+            None,
+            FindOptions::builder().sort(doc! { "date": 1 }).build(),
+        )
+        .await
+        .map_err(CalendarError::Mongo)?
+        .try_collect()
+        .await
+        .map_err(CalendarError::Mongo)?;
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        entries_calendar(&entries).to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::routes::JournalEntry;
+
+    use super::entries_calendar;
+
+    #[test]
+    fn one_event_per_entry() {
+        let entries: Vec<JournalEntry> = [(24, "Belle course"), (26, "Journée au bureau")]
+            .into_iter()
+            .map(|(day, summary)| JournalEntry {
+                date: NaiveDate::from_ymd_opt(2024, 1, day).unwrap(),
+                rate: 7.0,
+                short_summary: summary.to_string(),
+                ..Default::default()
+            })
+            .collect();
+
+        let ics = entries_calendar(&entries).to_string();
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 2);
+        assert!(ics.contains("DTSTART;VALUE=DATE:20240124"));
+        assert!(ics.contains("DTSTART;VALUE=DATE:20240126"));
+        assert!(!ics.contains("DTSTART;VALUE=DATE:20240125"));
+        assert!(ics.contains("DESCRIPTION:Belle course"));
+    }
+}
@@ -0,0 +1,329 @@
+use async_openai::{
+    config::OpenAIConfig,
+    error::OpenAIError,
+    types::{
+        ChatCompletionRequestMessage, ChatCompletionRequestSystemMessageArgs,
+        ChatCompletionRequestUserMessageArgs, CreateChatCompletionRequestArgs,
+    },
+    Client,
+};
+use futures_util::future::try_join_all;
+use thiserror::Error;
+
+use crate::throttle::{take_turn, OpenAiThrottle, ThrottleError};
+use crate::usage::TokenUsage;
+
+/// Au-delà de ce nombre de tokens estimés, le texte est résumé par morceaux avant l'analyse.
+pub const CHUNKING_THRESHOLD_TOKENS: usize = 3000;
+/// Taille visée d'un morceau, en tokens estimés.
+pub const CHUNK_TOKENS: usize = 1500;
+const CHARS_PER_TOKEN: usize = 4;
+
+pub const CHUNK_SUMMARY_PROMPT: &str = include_str!("./chunk_summary_message.txt");
+
+/// Estimation grossière du nombre de tokens, environ quatre caractères par token.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Découpe le texte en morceaux d'au plus `max_tokens` tokens estimés, aux fins de
+/// paragraphe quand c'est possible, sinon entre deux mots.
+pub fn split_into_chunks(text: &str, max_tokens: usize) -> Vec<String> {
+    let max_chars = max_tokens * CHARS_PER_TOKEN;
+    let mut chunks = Vec::new();
+    let mut chunk = String::new();
+    let mut push = |chunk: &mut String, part: &str, separator: &str| {
+        if !chunk.is_empty() && chunk.chars().count() + part.chars().count() + 2 > max_chars {
+            chunks.push(std::mem::take(chunk));
+        }
+        if !chunk.is_empty() {
+            chunk.push_str(separator);
+        }
+        chunk.push_str(part);
+    };
+
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        if paragraph.chars().count() <= max_chars {
+            push(&mut chunk, paragraph, "\n\n");
+        } else {
+            for word in paragraph.split_whitespace() {
+                push(&mut chunk, word, " ");
+            }
+        }
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+async fn summarize_chunk(
+    openai: &Client<OpenAIConfig>,
+    model: &str,
+    chunk: &str,
+) -> Result<(String, TokenUsage), OpenAIError> {
+    let response = openai
+        .chat()
+        .create(
+            CreateChatCompletionRequestArgs::default()
+                .model(model)
+                .messages(vec![
+                    ChatCompletionRequestMessage::System(
+                        ChatCompletionRequestSystemMessageArgs::default()
+                            .content(CHUNK_SUMMARY_PROMPT)
+                            .build()?,
+                    ),
+                    ChatCompletionRequestMessage::User(
+                        ChatCompletionRequestUserMessageArgs::default()
+                            .content(chunk)
+                            .build()?,
+                    ),
+                ])
+                .n(1)
+                .build()?,
+        )
+        .await?;
+
+    let summary = response
+        .choices
+        .first()
+        .and_then(|choice| choice.message.content.clone())
+        .unwrap_or_default();
+    let tokens = response
+        .usage
+        .as_ref()
+        .map(TokenUsage::from)
+        .unwrap_or_default();
+    Ok((summary.trim().to_string(), tokens))
+}
+
+#[derive(Error, Debug)]
+pub enum CondenseError {
+    #[error(transparent)]
+    OpenAI(OpenAIError),
+    #[error(transparent)]
+    Throttle(ThrottleError),
+}
+
+/// Résume le texte morceau par morceau (map) jusqu'à ce que les résumés mis bout à
+/// bout tiennent sous le seuil ; l'analyse habituelle fait ensuite office de reduce.
+/// Chaque morceau attend son propre tour dans la file OpenAI.
+pub async fn condense_text(
+    openai: &Client<OpenAIConfig>,
+    throttle: Option<&OpenAiThrottle>,
+    model: &str,
+    text: &str,
+) -> Result<(String, TokenUsage), CondenseError> {
+    let mut text = text.to_string();
+    let mut tokens = TokenUsage::default();
+    while estimate_tokens(&text) > CHUNKING_THRESHOLD_TOKENS {
+        let summaries = try_join_all(split_into_chunks(&text, CHUNK_TOKENS).iter().map(
+            |chunk| async move {
+                take_turn(throttle).await.map_err(CondenseError::Throttle)?;
+                summarize_chunk(openai, model, chunk)
+                    .await
+                    .map_err(CondenseError::OpenAI)
+            },
+        ))
+        .await?;
+
+        let condensed = summaries
+            .iter()
+            .map(|(summary, _)| summary.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        tokens = summaries
+            .iter()
+            .fold(tokens, |total, (_, usage)| total + *usage);
+        // Des résumés aussi longs que le texte ne feraient que boucler
+        if estimate_tokens(&condensed) >= estimate_tokens(&text) {
+            break;
+        }
+        text = condensed;
+    }
+    Ok((text, tokens))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use axum::{routing::post, Json, Router};
+    use chrono::NaiveDate;
+    use serde_json::Value;
+
+    use crate::{
+        config::{AppConfig, CHAT_MODEL},
+        routes::{prepare_journal_entry, CreateJournalEntry},
+        testing::{chat_completion_response, mock_openai},
+        throttle::OpenAiThrottle,
+    };
+
+    use super::{
+        condense_text, estimate_tokens, split_into_chunks, CondenseError,
+        CHUNKING_THRESHOLD_TOKENS, CHUNK_SUMMARY_PROMPT, CHUNK_TOKENS,
+    };
+
+    fn long_text() -> String {
+        (0..400)
+            .map(|paragraph| {
+                format!("Paragraphe {paragraph} : j'ai révisé, couru puis dîné avec Léa.")
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    /// Faux OpenAI résumant chaque morceau en une phrase, puis analysant le résumé consolidé.
+    async fn mock_chunked_openai(
+        chunk_calls: Arc<AtomicUsize>,
+    ) -> async_openai::Client<async_openai::config::OpenAIConfig> {
+        mock_chunked_openai_with_usage(chunk_calls, true).await
+    }
+
+    /// Comme `mock_chunked_openai`, l'analyse finale ne renvoyant son usage que si `final_usage`.
+    async fn mock_chunked_openai_with_usage(
+        chunk_calls: Arc<AtomicUsize>,
+        final_usage: bool,
+    ) -> async_openai::Client<async_openai::config::OpenAIConfig> {
+        mock_openai(Router::new().route(
+            "/chat/completions",
+            post(move |Json(request): Json<Value>| async move {
+                if request["messages"][0]["content"] == CHUNK_SUMMARY_PROMPT {
+                    chunk_calls.fetch_add(1, Ordering::Relaxed);
+                    Json(chat_completion_response("Révisions, course et dîner avec Léa."))
+                } else {
+                    let mut response = chat_completion_response(
+                        r#"{"date":"2024-03-04","rate":7.5,"short_summary":"Journée chargée","tags":["études","sport"]}"#,
+                    );
+                    if !final_usage {
+                        response.as_object_mut().unwrap().remove("usage");
+                    }
+                    Json(response)
+                }
+            }),
+        ))
+        .await
+    }
+
+    #[test]
+    fn split_long_text_at_paragraphs() {
+        let text = long_text();
+        assert!(estimate_tokens(&text) > CHUNKING_THRESHOLD_TOKENS);
+
+        let chunks = split_into_chunks(&text, CHUNK_TOKENS);
+        assert!(chunks.len() > 1);
+        assert!(chunks
+            .iter()
+            .all(|chunk| estimate_tokens(chunk) <= CHUNK_TOKENS));
+        assert!(chunks[0].starts_with("Paragraphe 0 :"));
+        assert!(chunks[0].ends_with("Léa."));
+
+        let words = "mot ".repeat(CHUNK_TOKENS * 3);
+        assert!(split_into_chunks(&words, CHUNK_TOKENS)
+            .iter()
+            .all(|chunk| estimate_tokens(chunk) <= CHUNK_TOKENS));
+    }
+
+    #[tokio::test]
+    async fn keep_short_text_whole() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let openai = mock_chunked_openai(calls.clone()).await;
+        let (text, _) = condense_text(&openai, None, CHAT_MODEL, "Belle course.")
+            .await
+            .unwrap();
+        assert_eq!(text, "Belle course.");
+        assert_eq!(calls.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn each_chunk_takes_a_turn() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let openai = mock_chunked_openai(calls.clone()).await;
+        let text = long_text();
+        let chunks = split_into_chunks(&text, CHUNK_TOKENS).len();
+        let throttle = |capacity| {
+            OpenAiThrottle::with_refill(
+                capacity,
+                Duration::from_secs(60),
+                Duration::from_millis(20),
+            )
+        };
+
+        let exact = throttle(chunks);
+        condense_text(&openai, Some(&exact), CHAT_MODEL, &text)
+            .await
+            .unwrap();
+        assert!(exact.acquire().await.is_err());
+
+        // Un jeton de moins que de morceaux : la file refuse le dernier
+        let short = throttle(chunks - 1);
+        assert!(matches!(
+            condense_text(&openai, Some(&short), CHAT_MODEL, &text).await,
+            Err(CondenseError::Throttle(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn analyze_long_entry_from_chunk_summaries() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let openai = mock_chunked_openai(calls.clone()).await;
+        let text = long_text();
+        let chunks = split_into_chunks(&text, CHUNK_TOKENS).len();
+
+        let entry = prepare_journal_entry(
+            &openai,
+            None,
+            &AppConfig::default(),
+            CreateJournalEntry {
+                name: "Alice".to_string(),
+                summary: text.clone(),
+                date: NaiveDate::from_ymd_opt(2024, 3, 4).unwrap(),
+                ..Default::default()
+            },
+            false,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(calls.load(Ordering::Relaxed), chunks);
+        assert_eq!(entry.short_summary, "Journée chargée");
+        assert_eq!(entry.rate, 7.5);
+        // Le texte saisi est conservé en entier
+        assert_eq!(entry.raw_text, Some(text));
+        let tokens = entry.tokens.unwrap();
+        assert_eq!(tokens.prompt_tokens, 120 * (chunks as u32 + 1));
+    }
+
+    #[tokio::test]
+    async fn keep_chunk_tokens_without_final_usage() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let openai = mock_chunked_openai_with_usage(calls.clone(), false).await;
+        let text = long_text();
+        let chunks = split_into_chunks(&text, CHUNK_TOKENS).len() as u32;
+
+        let entry = prepare_journal_entry(
+            &openai,
+            None,
+            &AppConfig::default(),
+            CreateJournalEntry {
+                name: "Alice".to_string(),
+                summary: text,
+                date: NaiveDate::from_ymd_opt(2024, 3, 4).unwrap(),
+                ..Default::default()
+            },
+            false,
+        )
+        .await
+        .unwrap();
+
+        let tokens = entry.tokens.unwrap();
+        assert_eq!(tokens.prompt_tokens, 120 * chunks);
+        assert_eq!(tokens.completion_tokens, 30 * chunks);
+    }
+}
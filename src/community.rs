@@ -0,0 +1,179 @@
+use std::sync::Arc;
+
+use axum::{extract::Query, http::StatusCode, Extension, Json};
+use axum_thiserror::ErrorStatus;
+use chrono::{NaiveDate, Utc};
+use chrono_tz::Tz;
+use futures_util::TryStreamExt;
+use mongodb::{
+    bson::{self, doc, Document},
+    Collection,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    config::AppConfig,
+    recap::parse_month,
+    routes::JournalEntry,
+    stats::{count_unrated, entry_stages, period_filter, rated_filter, RatedStats},
+};
+
+/// En dessous de ce nombre d'utilisateurs, la moyenne communautaire permettrait
+/// de retrouver la note d'un autre utilisateur : elle n'est pas renvoyée.
+pub const MIN_COMMUNITY_SIZE: usize = 5;
+
+/// Moyenne mensuelle d'un utilisateur. L'API ne gérant encore qu'un seul journal,
+/// les entrées sans `user_id` sont celles de l'utilisateur courant.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UserAverage {
+    #[serde(rename = "_id")]
+    pub user_id: Option<String>,
+    pub average: f32,
+}
+
+/// Comparaison anonymisée : seuls des agrégats sont renvoyés.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CommunityComparison {
+    pub month: String,
+    pub my_average: Option<f32>,
+    pub community_average: Option<f32>,
+    /// Part des utilisateurs dont la moyenne est inférieure ou égale à la mienne, en pourcentage.
+    pub percentile: Option<f32>,
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum CommunityError {
+    #[error("invalid month \"{0}\", expected a month like 2024-03")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidMonth(String),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Bson(bson::de::Error),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+/// Moyenne de chaque utilisateur sur le mois, sur ses entrées notées.
+pub fn user_averages_pipeline(timezone: Tz, first: NaiveDate, last: NaiveDate) -> Vec<Document> {
+    let mut pipeline = entry_stages(timezone, period_filter(first, last));
+    pipeline.extend([
+        doc! { "$match": rated_filter() },
+        doc! { "$group": { "_id": "$user_id", "average": { "$avg": "$rate" } } },
+    ]);
+    pipeline
+}
+
+pub fn compare_to_community(month: &str, averages: &[UserAverage]) -> CommunityComparison {
+    let my_average = averages
+        .iter()
+        .find(|average| average.user_id.is_none())
+        .map(|average| average.average);
+
+    let (community_average, percentile) = if averages.len() < MIN_COMMUNITY_SIZE {
+        (None, None)
+    } else {
+        let count = averages.len() as f32;
+        let community_average = averages.iter().map(|average| average.average).sum::<f32>() / count;
+        let percentile = my_average.map(|mine| {
+            averages
+                .iter()
+                .filter(|average| average.average <= mine)
+                .count() as f32
+                * 100.0
+                / count
+        });
+        (Some(community_average), percentile)
+    };
+
+    CommunityComparison {
+        month: month.to_string(),
+        my_average,
+        community_average,
+        percentile,
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct CommunityQuery {
+    /// Mois comparé, le mois en cours par défaut.
+    pub month: Option<String>,
+}
+
+pub async fn get_community_stats(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Query(query): Query<CommunityQuery>,
+) -> Result<Json<RatedStats<CommunityComparison>>, CommunityError> {
+    let month = query.month.unwrap_or_else(|| {
+        Utc::now()
+            .with_timezone(&config.timezone)
+            .format("%Y-%m")
+            .to_string()
+    });
+    let (first, last) =
+        parse_month(&month).ok_or_else(|| CommunityError::InvalidMonth(month.clone()))?;
+
+    let averages: Vec<UserAverage> = mongo_entries
+        .aggregate(user_averages_pipeline(config.timezone, first, last), None)
+        .await
+        .map_err(CommunityError::Mongo)?
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(CommunityError::Mongo)?
+        .into_iter()
+        .map(bson::from_document)
+        .collect::<Result<_, _>>()
+        .map_err(CommunityError::Bson)?;
+
+    let excluded_count = count_unrated(
+        &mongo_entries,
+        entry_stages(config.timezone, period_filter(first, last)),
+    )
+    .await
+    .map_err(CommunityError::Mongo)?;
+
+    Ok(Json(RatedStats {
+        stats: compare_to_community(&month, &averages),
+        excluded_count,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare_to_community, UserAverage};
+
+    fn average(user_id: Option<&str>, average: f32) -> UserAverage {
+        UserAverage {
+            user_id: user_id.map(str::to_string),
+            average,
+        }
+    }
+
+    #[test]
+    fn compare_with_community() {
+        let averages = vec![
+            average(None, 7.0),
+            average(Some("a"), 5.0),
+            average(Some("b"), 6.0),
+            average(Some("c"), 8.0),
+            average(Some("d"), 9.0),
+        ];
+
+        let comparison = compare_to_community("2024-03", &averages);
+        assert_eq!(comparison.my_average, Some(7.0));
+        assert_eq!(comparison.community_average, Some(7.0));
+        assert_eq!(comparison.percentile, Some(60.0));
+    }
+
+    #[test]
+    fn hide_community_of_too_few_users() {
+        let averages = vec![average(None, 7.0), average(Some("a"), 5.0)];
+
+        let comparison = compare_to_community("2024-03", &averages);
+        assert_eq!(comparison.my_average, Some(7.0));
+        assert_eq!(comparison.community_average, None);
+        assert_eq!(comparison.percentile, None);
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    io::{Read, Write},
+    sync::Arc,
+};
+
+use axum::{extract::Query, http::StatusCode, Extension, Json};
+use axum_thiserror::ErrorStatus;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{Days, NaiveDate, Utc};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use futures_util::TryStreamExt;
+use mongodb::{bson::doc, Collection};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config::AppConfig;
+use crate::crypto::{CryptoError, EntryCipher};
+use crate::routes::{date_filter, JournalEntry};
+
+/// Âge, en jours, à partir duquel le texte brut d'une entrée est compressé.
+pub const DEFAULT_COMPRESS_AFTER_DAYS: u64 = 365;
+
+/// Texte compressé en gzip puis encodé en base64, pour rester une chaîne.
+pub fn compress_text(text: &str) -> String {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder
+        .write_all(text.as_bytes())
+        .and_then(|()| encoder.finish())
+        .map(|compressed| STANDARD.encode(compressed))
+        .expect("gzip compression of an in-memory text cannot fail")
+}
+
+pub fn decompress_text(stored: &str) -> Result<String, CryptoError> {
+    let compressed = STANDARD
+        .decode(stored)
+        .map_err(|_| CryptoError::Decompression)?;
+    let mut text = String::new();
+    GzDecoder::new(compressed.as_slice())
+        .read_to_string(&mut text)
+        .map_err(|_| CryptoError::Decompression)?;
+    Ok(text)
+}
+
+/// Une entrée du `date` est assez ancienne pour être compressée.
+pub fn is_old(date: NaiveDate, today: NaiveDate, compress_after_days: u64) -> bool {
+    today
+        .checked_sub_days(Days::new(compress_after_days))
+        .is_some_and(|limit| date < limit)
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum CompressOldError {
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Crypto(CryptoError),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct CompressOldQuery {
+    /// Âge minimal des entrées compressées, `COMPRESS_AFTER_DAYS` par défaut.
+    pub older_than_days: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CompressOldResult {
+    pub compressed: u64,
+}
+
+/// Compresse après coup le texte brut des anciennes entrées qui ne le sont pas encore.
+pub async fn compress_old_entries(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(cipher): Extension<Arc<EntryCipher>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Query(query): Query<CompressOldQuery>,
+) -> Result<Json<CompressOldResult>, CompressOldError> {
+    let today = Utc::now().with_timezone(&config.timezone).date_naive();
+    let older_than_days = query.older_than_days.unwrap_or(config.compress_after_days);
+    let Some(limit) = today.checked_sub_days(Days::new(older_than_days)) else {
+        return Ok(Json(CompressOldResult { compressed: 0 }));
+    };
+
+    let entries: Vec<JournalEntry> = mongo_entries
+        .find(
+            doc! {
+                "date": { "$lt": limit.to_string() },
+                "raw_text": { "$type": "string" },
+                "text_compressed": { "$ne": true },
+            },
+            None,
+        )
+        .await
+        .map_err(CompressOldError::Mongo)?
+        .try_collect()
+        .await
+        .map_err(CompressOldError::Mongo)?;
+
+    let mut compressed = 0;
+    for entry in entries {
+        let stored = entry
+            .decrypted(&cipher)
+            .map_err(CompressOldError::Crypto)?
+            .compressed()
+            .encrypted(&cipher);
+        mongo_entries
+            .update_one(
+                date_filter(stored.date),
+                doc! { "$set": { "raw_text": stored.raw_text, "text_compressed": true } },
+                None,
+            )
+            .await
+            .map_err(CompressOldError::Mongo)?;
+        compressed += 1;
+    }
+
+    Ok(Json(CompressOldResult { compressed }))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use mongodb::bson;
+
+    use crate::{crypto::EntryCipher, routes::JournalEntry};
+
+    use super::{compress_text, decompress_text, is_old};
+
+    #[test]
+    fn compression_round_trip() {
+        let text = "Longue journée de randonnée. ".repeat(40);
+        let stored = compress_text(&text);
+        assert!(stored.len() < text.len());
+        assert_eq!(decompress_text(&stored).unwrap(), text);
+        assert!(decompress_text("pas du gzip").is_err());
+    }
+
+    #[test]
+    fn read_compressed_entries() {
+        let cipher = EntryCipher::new(&[7; 32]).unwrap();
+        let entry = JournalEntry {
+            date: NaiveDate::from_ymd_opt(2022, 6, 1).unwrap(),
+            raw_text: Some("Premier jour de vacances à Lisbonne.".to_string()),
+            ..Default::default()
+        };
+
+        let stored = bson::to_document(&entry.clone().compressed().encrypted(&cipher)).unwrap();
+        assert!(stored.get_bool("text_compressed").unwrap());
+        let read: JournalEntry = bson::from_document(stored).unwrap();
+        assert_eq!(read.decrypted(&cipher).unwrap(), entry);
+
+        // Les entrées jamais compressées se lisent comme avant
+        let stored = bson::to_document(&entry.encrypted(&cipher)).unwrap();
+        assert!(!stored.contains_key("text_compressed"));
+        let read: JournalEntry = bson::from_document(stored).unwrap();
+        assert_eq!(read.decrypted(&cipher).unwrap(), entry);
+    }
+
+    #[test]
+    fn compress_only_old_entries() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 14).unwrap();
+        assert!(is_old(
+            NaiveDate::from_ymd_opt(2023, 3, 1).unwrap(),
+            today,
+            365
+        ));
+        assert!(!is_old(
+            NaiveDate::from_ymd_opt(2023, 3, 20).unwrap(),
+            today,
+            365
+        ));
+    }
+}
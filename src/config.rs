@@ -0,0 +1,209 @@
+use std::{str::FromStr, time::Duration};
+
+use axum::http::HeaderValue;
+use chrono_tz::{Europe::Paris, Tz};
+use thiserror::Error;
+use tower_http::cors::CorsLayer;
+
+use crate::compression::DEFAULT_COMPRESS_AFTER_DAYS;
+use crate::cors::{credentials_cors_layer, public_cors_layer};
+use crate::routes::{DEFAULT_LIST_LIMIT, DEFAULT_MAX_SUMMARY_LENGTH};
+use crate::similar::{DEFAULT_SIMILARITY_THRESHOLD, EMBEDDING_MODEL};
+use crate::throttle::{DEFAULT_OPENAI_QUEUE_TIMEOUT, DEFAULT_OPENAI_REQUESTS_PER_MINUTE};
+use crate::usage::TokenPricing;
+
+pub const CHAT_MODEL: &str = "gpt-3.5-turbo";
+/// Température par défaut d'OpenAI.
+pub const DEFAULT_TEMPERATURE: f32 = 1.0;
+pub const DEFAULT_TIMEZONE: Tz = Paris;
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("invalid TIMEZONE \"{0}\"")]
+    InvalidTimezone(String),
+}
+
+/// Configuration de l'application, lue au démarrage. Une partie peut être
+/// modifiée à chaud, voir `live_config`.
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    pub chat_model: String,
+    pub embedding_model: String,
+    /// Température de l'analyse des entrées, entre 0 et 2.
+    pub temperature: f32,
+    /// Durée maximale d'une requête, appels à OpenAI compris.
+    pub request_timeout: Duration,
+    /// Fuseau utilisé quand la requête n'en précise pas.
+    pub timezone: Tz,
+    pub default_list_limit: u64,
+    /// Longueur maximale du texte d'une entrée, en caractères.
+    pub max_summary_length: usize,
+    pub similarity_threshold: f32,
+    /// Avec `true`, seules les `allowed_origins` sont acceptées (la spec CORS interdit alors `*`).
+    pub allow_credentials: bool,
+    pub allowed_origins: Vec<String>,
+    /// Démarre en mode maintenance, les écritures étant refusées.
+    pub read_only: bool,
+    /// Clé exigée par les endpoints `/internal`, fermés sans clé configurée.
+    pub internal_api_key: Option<String>,
+    /// Secret attendu par `/webhook/entry`, fermé sans secret configuré.
+    pub webhook_secret: Option<String>,
+    pub pricing: TokenPricing,
+    /// Active les endpoints `/debug`, à réserver au développement.
+    pub debug_endpoints: bool,
+    /// `development` active les routes `/dev`, introuvables partout ailleurs.
+    pub environment: String,
+    /// Âge, en jours, à partir duquel le texte brut des entrées est compressé.
+    pub compress_after_days: u64,
+    /// Débit maximal d'appels OpenAI, les requêtes en excès attendant leur tour.
+    pub openai_requests_per_minute: u32,
+    pub openai_queue_timeout: Duration,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            chat_model: CHAT_MODEL.to_string(),
+            embedding_model: EMBEDDING_MODEL.to_string(),
+            temperature: DEFAULT_TEMPERATURE,
+            request_timeout: Duration::from_secs(60),
+            timezone: DEFAULT_TIMEZONE,
+            default_list_limit: DEFAULT_LIST_LIMIT,
+            max_summary_length: DEFAULT_MAX_SUMMARY_LENGTH,
+            similarity_threshold: DEFAULT_SIMILARITY_THRESHOLD,
+            allow_credentials: false,
+            allowed_origins: vec![],
+            read_only: false,
+            internal_api_key: None,
+            webhook_secret: None,
+            pricing: TokenPricing::default(),
+            debug_endpoints: false,
+            environment: "production".to_string(),
+            compress_after_days: DEFAULT_COMPRESS_AFTER_DAYS,
+            openai_requests_per_minute: DEFAULT_OPENAI_REQUESTS_PER_MINUTE,
+            openai_queue_timeout: DEFAULT_OPENAI_QUEUE_TIMEOUT,
+        }
+    }
+}
+
+/// Valeur de la variable `name`, ou `default` si elle est absente ou invalide.
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    match std::env::var(name) {
+        Ok(value) => value.parse().unwrap_or_else(|_| {
+            tracing::warn!("Ignoring invalid {name}={value}");
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+impl AppConfig {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let default = AppConfig::default();
+        let timezone = match std::env::var("TIMEZONE") {
+            Ok(timezone) => timezone
+                .parse()
+                .map_err(|_| ConfigError::InvalidTimezone(timezone))?,
+            Err(_) => default.timezone,
+        };
+
+        Ok(AppConfig {
+            chat_model: env_or("OPENAI_MODEL", default.chat_model),
+            embedding_model: env_or("OPENAI_EMBEDDING_MODEL", default.embedding_model),
+            temperature: env_or("OPENAI_TEMPERATURE", default.temperature),
+            request_timeout: Duration::from_secs(env_or(
+                "REQUEST_TIMEOUT_SECS",
+                default.request_timeout.as_secs(),
+            )),
+            timezone,
+            default_list_limit: env_or("DEFAULT_LIST_LIMIT", default.default_list_limit),
+            max_summary_length: env_or("MAX_SUMMARY_LENGTH", default.max_summary_length),
+            similarity_threshold: env_or("SIMILARITY_THRESHOLD", default.similarity_threshold),
+            allow_credentials: env_or("ALLOW_CREDENTIALS", default.allow_credentials),
+            allowed_origins: std::env::var("ALLOWED_ORIGINS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|origin| !origin.is_empty())
+                .map(str::to_string)
+                .collect(),
+            read_only: env_or("READ_ONLY", default.read_only),
+            internal_api_key: std::env::var("INTERNAL_API_KEY")
+                .ok()
+                .filter(|key| !key.is_empty()),
+            webhook_secret: std::env::var("WEBHOOK_SECRET")
+                .ok()
+                .filter(|secret| !secret.is_empty()),
+            pricing: TokenPricing {
+                prompt_per_1k: env_or("OPENAI_PROMPT_PRICE_PER_1K", default.pricing.prompt_per_1k),
+                completion_per_1k: env_or(
+                    "OPENAI_COMPLETION_PRICE_PER_1K",
+                    default.pricing.completion_per_1k,
+                ),
+            },
+            debug_endpoints: env_or("DEBUG_ENDPOINTS", default.debug_endpoints),
+            environment: env_or("ENVIRONMENT", default.environment),
+            compress_after_days: env_or("COMPRESS_AFTER_DAYS", default.compress_after_days),
+            openai_requests_per_minute: env_or(
+                "OPENAI_REQUESTS_PER_MINUTE",
+                default.openai_requests_per_minute,
+            ),
+            openai_queue_timeout: Duration::from_secs(env_or(
+                "OPENAI_QUEUE_TIMEOUT_SECS",
+                default.openai_queue_timeout.as_secs(),
+            )),
+        })
+    }
+
+    pub fn is_development(&self) -> bool {
+        self.environment == "development"
+    }
+
+    /// CORS ouvert à tous, sauf avec `allow_credentials` : on reflète alors
+    /// l'origine de la requête si elle fait partie de `allowed_origins`.
+    pub fn cors_layer(&self) -> CorsLayer {
+        if self.allow_credentials {
+            credentials_cors_layer(
+                self.allowed_origins
+                    .iter()
+                    .filter_map(|origin| HeaderValue::from_str(origin).ok())
+                    .collect(),
+            )
+        } else {
+            public_cors_layer()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono_tz::Europe::Paris;
+
+    use crate::{routes::DEFAULT_LIST_LIMIT, usage::TokenPricing};
+
+    use super::AppConfig;
+
+    #[test]
+    fn default_config() {
+        let config = AppConfig::default();
+        assert_eq!(config.chat_model, "gpt-3.5-turbo");
+        assert_eq!(config.embedding_model, "text-embedding-3-small");
+        assert_eq!(config.temperature, 1.0);
+        assert_eq!(config.request_timeout, Duration::from_secs(60));
+        assert_eq!(config.timezone, Paris);
+        assert_eq!(config.default_list_limit, DEFAULT_LIST_LIMIT);
+        assert_eq!(config.max_summary_length, 100_000);
+        assert_eq!(config.similarity_threshold, 0.95);
+        assert!(!config.allow_credentials);
+        assert!(!config.read_only);
+        assert_eq!(config.internal_api_key, None);
+        assert_eq!(config.webhook_secret, None);
+        assert_eq!(config.pricing, TokenPricing::default());
+        assert!(!config.debug_endpoints);
+        assert!(!config.is_development());
+        assert_eq!(config.openai_requests_per_minute, 60);
+        assert_eq!(config.openai_queue_timeout, Duration::from_secs(10));
+    }
+}
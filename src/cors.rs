@@ -0,0 +1,79 @@
+use axum::http::{HeaderName, HeaderValue};
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, Any, CorsLayer};
+
+/// Headers de réponse lisibles par le client.
+const EXPOSED_HEADERS: [HeaderName; 1] = [HeaderName::from_static("x-processing-time-ms")];
+
+pub fn public_cors_layer() -> CorsLayer {
+    CorsLayer::new()
+        .expose_headers(EXPOSED_HEADERS)
+        .allow_methods(Any)
+        .allow_origin(Any)
+        .allow_headers(Any)
+}
+
+pub fn credentials_cors_layer(allowed_origins: Vec<HeaderValue>) -> CorsLayer {
+    CorsLayer::new()
+        .expose_headers(EXPOSED_HEADERS)
+        .allow_credentials(true)
+        .allow_methods(AllowMethods::mirror_request())
+        .allow_headers(AllowHeaders::mirror_request())
+        .allow_origin(AllowOrigin::predicate(move |origin, _| {
+            allowed_origins.contains(origin)
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, HeaderValue, Request, Response},
+        routing::post,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    use super::credentials_cors_layer;
+
+    async fn preflight(origin: &str) -> Response<Body> {
+        Router::new()
+            .route("/", post(|| async {}))
+            .layer(credentials_cors_layer(vec![HeaderValue::from_static(
+                "https://journai.app",
+            )]))
+            .oneshot(
+                Request::builder()
+                    .method("OPTIONS")
+                    .uri("/")
+                    .header(header::ORIGIN, origin)
+                    .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+                    .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "x-api-key")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn reflect_allowed_origin() {
+        let response = preflight("https://journai.app").await;
+        let headers = response.headers();
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://journai.app"
+        );
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_METHODS], "POST");
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_HEADERS], "x-api-key");
+    }
+
+    #[tokio::test]
+    async fn reject_unknown_origin() {
+        let response = preflight("https://evil.example").await;
+        assert!(response
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none());
+    }
+}
@@ -0,0 +1,123 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use thiserror::Error;
+
+/// Préfixe des textes chiffrés, qui les distingue des textes stockés en clair.
+const ENCRYPTED_PREFIX: &str = "aes-gcm:";
+const NONCE_LENGTH: usize = 12;
+
+#[derive(Error, Debug)]
+pub enum CryptoError {
+    #[error("ENCRYPTION_KEY must be a base64 encoded 32 bytes key")]
+    InvalidKey,
+    #[error("an encrypted text cannot be read without ENCRYPTION_KEY")]
+    MissingKey,
+    #[error("unable to decrypt text")]
+    Decryption,
+    #[error("unable to decompress text")]
+    Decompression,
+}
+
+/// Chiffrement au repos du texte brut des entrées. Sans clé, les textes sont
+/// stockés en clair.
+#[derive(Clone, Default)]
+pub struct EntryCipher {
+    cipher: Option<Aes256Gcm>,
+}
+
+impl EntryCipher {
+    pub fn new(key: &[u8]) -> Result<Self, CryptoError> {
+        Ok(EntryCipher {
+            cipher: Some(Aes256Gcm::new_from_slice(key).map_err(|_| CryptoError::InvalidKey)?),
+        })
+    }
+
+    /// Clé lue depuis `ENCRYPTION_KEY`, encodée en base64.
+    pub fn from_env() -> Result<Self, CryptoError> {
+        match std::env::var("ENCRYPTION_KEY") {
+            Ok(key) => EntryCipher::new(
+                &STANDARD
+                    .decode(key.trim())
+                    .map_err(|_| CryptoError::InvalidKey)?,
+            ),
+            Err(_) => {
+                tracing::warn!("ENCRYPTION_KEY is not set, raw texts are stored unencrypted");
+                Ok(EntryCipher::default())
+            }
+        }
+    }
+
+    pub fn encrypt(&self, text: &str) -> String {
+        let Some(cipher) = &self.cipher else {
+            return text.to_string();
+        };
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let mut payload = nonce.to_vec();
+        payload.extend(
+            cipher
+                .encrypt(&nonce, text.as_bytes())
+                .expect("AES-GCM encryption of an in-memory text cannot fail"),
+        );
+        format!("{ENCRYPTED_PREFIX}{}", STANDARD.encode(payload))
+    }
+
+    /// Déchiffre un texte stocké ; un texte en clair est renvoyé tel quel.
+    pub fn decrypt(&self, stored: &str) -> Result<String, CryptoError> {
+        let Some(encoded) = stored.strip_prefix(ENCRYPTED_PREFIX) else {
+            return Ok(stored.to_string());
+        };
+        let cipher = self.cipher.as_ref().ok_or(CryptoError::MissingKey)?;
+        let payload = STANDARD
+            .decode(encoded)
+            .map_err(|_| CryptoError::Decryption)?;
+        if payload.len() < NONCE_LENGTH {
+            return Err(CryptoError::Decryption);
+        }
+        let (nonce, ciphertext) = payload.split_at(NONCE_LENGTH);
+        let text = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| CryptoError::Decryption)?;
+        String::from_utf8(text).map_err(|_| CryptoError::Decryption)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CryptoError, EntryCipher};
+
+    #[test]
+    fn encryption_round_trip() {
+        let cipher = EntryCipher::new(&[7; 32]).unwrap();
+        let text = "Journée difficile, mais j'ai parlé à Léa.";
+
+        let stored = cipher.encrypt(text);
+        assert!(stored.starts_with("aes-gcm:"));
+        assert!(!stored.contains("Léa"));
+        assert_ne!(stored, cipher.encrypt(text));
+        assert_eq!(cipher.decrypt(&stored).unwrap(), text);
+
+        let other = EntryCipher::new(&[8; 32]).unwrap();
+        assert!(matches!(
+            other.decrypt(&stored),
+            Err(CryptoError::Decryption)
+        ));
+        assert!(matches!(
+            EntryCipher::default().decrypt(&stored),
+            Err(CryptoError::MissingKey)
+        ));
+    }
+
+    #[test]
+    fn store_in_clear_without_key() {
+        let cipher = EntryCipher::default();
+        assert_eq!(cipher.encrypt("Bonne journée"), "Bonne journée");
+        assert_eq!(cipher.decrypt("Bonne journée").unwrap(), "Bonne journée");
+        assert!(matches!(
+            EntryCipher::new(&[7; 16]),
+            Err(CryptoError::InvalidKey)
+        ));
+    }
+}
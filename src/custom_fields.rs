@@ -0,0 +1,195 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{extract::Path, http::StatusCode, Extension, Json};
+use axum_thiserror::ErrorStatus;
+use chrono::NaiveDate;
+use futures_util::TryStreamExt;
+use mongodb::{bson::doc, options::FindOptions, Collection};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::routes::JournalEntry;
+
+/// Longueur maximale du nom d'un champ personnalisé.
+pub const MAX_CUSTOM_FIELD_NAME_LENGTH: usize = 32;
+/// Nombre maximal de champs personnalisés d'une entrée.
+pub const MAX_CUSTOM_FIELDS: usize = 20;
+
+#[derive(Error, Debug)]
+pub enum CustomFieldError {
+    #[error("invalid custom field name \"{0}\", expected up to {MAX_CUSTOM_FIELD_NAME_LENGTH} lowercase letters, digits or underscores, starting with a letter")]
+    InvalidName(String),
+    #[error("an entry cannot have more than {MAX_CUSTOM_FIELDS} custom fields, got {0}")]
+    TooMany(usize),
+}
+
+/// Nom utilisable tel quel comme chemin Mongo (`custom_fields.sleep_hours`) :
+/// ni point, ni `$`, ni caractère inattendu.
+pub fn validate_custom_field_name(name: &str) -> Result<(), CustomFieldError> {
+    let mut chars = name.chars();
+    let valid = name.len() <= MAX_CUSTOM_FIELD_NAME_LENGTH
+        && chars.next().is_some_and(|c| c.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(CustomFieldError::InvalidName(name.to_string()))
+    }
+}
+
+pub fn validate_custom_fields(fields: &HashMap<String, Value>) -> Result<(), CustomFieldError> {
+    if fields.len() > MAX_CUSTOM_FIELDS {
+        return Err(CustomFieldError::TooMany(fields.len()));
+    }
+    fields
+        .keys()
+        .try_for_each(|name| validate_custom_field_name(name))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CustomFieldValue {
+    pub date: NaiveDate,
+    pub value: f64,
+}
+
+/// Valeurs numériques d'un champ personnalisé dans le temps ; les autres valeurs sont ignorées.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CustomFieldStats {
+    pub field: String,
+    pub count: usize,
+    pub average: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub values: Vec<CustomFieldValue>,
+}
+
+pub fn custom_field_stats(field: &str, entries: &[JournalEntry]) -> CustomFieldStats {
+    let values: Vec<CustomFieldValue> = entries
+        .iter()
+        .filter_map(|entry| {
+            Some(CustomFieldValue {
+                date: entry.date,
+                value: entry.custom_fields.get(field)?.as_f64()?,
+            })
+        })
+        .collect();
+    let numbers = || values.iter().map(|value| value.value);
+
+    CustomFieldStats {
+        field: field.to_string(),
+        count: values.len(),
+        average: (!values.is_empty()).then(|| numbers().sum::<f64>() / values.len() as f64),
+        min: numbers().reduce(f64::min),
+        max: numbers().reduce(f64::max),
+        values,
+    }
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum CustomFieldStatsError {
+    #[error(transparent)]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidField(CustomFieldError),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+pub async fn get_custom_field_stats(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Path(field): Path<String>,
+) -> Result<Json<CustomFieldStats>, CustomFieldStatsError> {
+    validate_custom_field_name(&field).map_err(CustomFieldStatsError::InvalidField)?;
+
+    let entries: Vec<JournalEntry> = mongo_entries
+        .find(
+            doc! { format!("custom_fields.{field}"): { "$exists": true } },
+            FindOptions::builder().sort(doc! { "date": 1 }).build(),
+        )
+        .await
+        .map_err(CustomFieldStatsError::Mongo)?
+        .try_collect()
+        .await
+        .map_err(CustomFieldStatsError::Mongo)?;
+
+    Ok(Json(custom_field_stats(&field, &entries)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::NaiveDate;
+    use mongodb::bson;
+    use serde_json::json;
+
+    use crate::routes::JournalEntry;
+
+    use super::{
+        custom_field_stats, validate_custom_field_name, validate_custom_fields, CustomFieldError,
+        CustomFieldValue,
+    };
+
+    #[test]
+    fn validate_field_names() {
+        assert!(validate_custom_field_name("sleep_hours").is_ok());
+        assert!(validate_custom_field_name("steps2").is_ok());
+        for name in [
+            "",
+            "Sleep",
+            "2steps",
+            "sleep.hours",
+            "$where",
+            "heures de sommeil",
+        ] {
+            assert!(matches!(
+                validate_custom_field_name(name),
+                Err(CustomFieldError::InvalidName(_))
+            ));
+        }
+        assert!(validate_custom_field_name(&"a".repeat(33)).is_err());
+
+        let fields: HashMap<_, _> = (0..21).map(|i| (format!("field_{i}"), json!(i))).collect();
+        assert!(matches!(
+            validate_custom_fields(&fields),
+            Err(CustomFieldError::TooMany(21))
+        ));
+    }
+
+    #[test]
+    fn store_and_aggregate_sleep_hours() {
+        let entries: Vec<JournalEntry> = [(1, json!(7.5)), (2, json!(6)), (3, json!("mal dormi"))]
+            .into_iter()
+            .map(|(day, sleep_hours)| {
+                let entry = JournalEntry {
+                    date: NaiveDate::from_ymd_opt(2024, 3, day).unwrap(),
+                    custom_fields: HashMap::from([
+                        ("sleep_hours".to_string(), sleep_hours),
+                        ("steps".to_string(), json!(8000)),
+                    ]),
+                    ..Default::default()
+                };
+                // Stocké tel quel en BSON
+                bson::from_document(bson::to_document(&entry).unwrap()).unwrap()
+            })
+            .collect();
+        assert_eq!(entries[0].custom_fields["sleep_hours"], json!(7.5));
+        assert_eq!(entries[2].custom_fields["sleep_hours"], json!("mal dormi"));
+
+        let stats = custom_field_stats("sleep_hours", &entries);
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.average, Some(6.75));
+        assert_eq!(stats.min, Some(6.0));
+        assert_eq!(stats.max, Some(7.5));
+        assert_eq!(
+            stats.values[1],
+            CustomFieldValue {
+                date: NaiveDate::from_ymd_opt(2024, 3, 2).unwrap(),
+                value: 6.0
+            }
+        );
+
+        assert_eq!(custom_field_stats("weight", &entries).average, None);
+    }
+}
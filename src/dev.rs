@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use axum::{http::StatusCode, Extension, Json};
+use axum_thiserror::ErrorStatus;
+use chrono::NaiveDate;
+use mongodb::{
+    bson::{doc, Document},
+    Collection,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config::AppConfig;
+use crate::routes::JournalEntry;
+
+/// Marqueur à placer dans le résumé d'une entrée de test.
+pub const TEST_ENTRY_MARKER: &str = "#test";
+
+/// Dates réservées aux entrées de test, qu'aucune vraie entrée ne peut avoir.
+pub fn test_dates() -> (NaiveDate, NaiveDate) {
+    (
+        NaiveDate::from_ymd_opt(1900, 1, 1).unwrap(),
+        NaiveDate::from_ymd_opt(1900, 12, 31).unwrap(),
+    )
+}
+
+/// Entrées de test : marquées dans leur résumé ou datées de la plage réservée.
+pub fn test_entries_filter() -> Document {
+    let (first, last) = test_dates();
+    doc! {
+        "$or": [
+            { "short_summary": { "$regex": TEST_ENTRY_MARKER, "$options": "i" } },
+            { "date": { "$gte": first.to_string(), "$lte": last.to_string() } },
+        ]
+    }
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum CleanupError {
+    #[error("not found")]
+    #[status(StatusCode::NOT_FOUND)]
+    NotDevelopment,
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CleanupResult {
+    pub removed: u64,
+}
+
+/// Supprime les entrées de test. Hors `ENVIRONMENT=development`, la route
+/// répond comme si elle n'existait pas.
+pub async fn cleanup_test_entries(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<Json<CleanupResult>, CleanupError> {
+    if !config.is_development() {
+        return Err(CleanupError::NotDevelopment);
+    }
+
+    let result = mongo_entries
+        .delete_many(test_entries_filter(), None)
+        .await
+        .map_err(CleanupError::Mongo)?;
+
+    Ok(Json(CleanupResult {
+        removed: result.deleted_count,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::Extension;
+    use mongodb::bson::doc;
+
+    use crate::{config::AppConfig, routes::JournalEntry};
+
+    use super::{cleanup_test_entries, test_entries_filter, CleanupError};
+
+    #[test]
+    fn match_test_entries() {
+        assert_eq!(
+            test_entries_filter(),
+            doc! {
+                "$or": [
+                    { "short_summary": { "$regex": "#test", "$options": "i" } },
+                    { "date": { "$gte": "1900-01-01", "$lte": "1900-12-31" } },
+                ]
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn inactive_outside_development() {
+        // Jamais contactée : la route doit refuser avant tout accès à la base
+        let entries = mongodb::Client::with_uri_str("mongodb://localhost:27017")
+            .await
+            .unwrap()
+            .database("journai_test")
+            .collection::<JournalEntry>("entries");
+        for environment in ["production", "staging", ""] {
+            let config = AppConfig {
+                environment: environment.to_string(),
+                ..Default::default()
+            };
+            assert!(matches!(
+                cleanup_test_entries(
+                    Extension(Arc::new(entries.clone())),
+                    Extension(Arc::new(config))
+                )
+                .await,
+                Err(CleanupError::NotDevelopment)
+            ));
+        }
+    }
+}
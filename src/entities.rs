@@ -0,0 +1,122 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{http::StatusCode, Extension, Json};
+use axum_thiserror::ErrorStatus;
+use futures_util::TryStreamExt;
+use mongodb::Collection;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::routes::JournalEntry;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Mention {
+    pub name: String,
+    pub count: u32,
+}
+
+/// Compte les mentions sans tenir compte de la casse ; le nom affiché est la
+/// première orthographe rencontrée.
+pub fn count_mentions<'a>(names: impl IntoIterator<Item = &'a String>) -> Vec<Mention> {
+    let mut mentions: Vec<Mention> = vec![];
+    let mut indexes: HashMap<String, usize> = HashMap::new();
+    for name in names {
+        let name = name.trim();
+        if name.is_empty() {
+            continue;
+        }
+        match indexes.get(&name.to_lowercase()) {
+            Some(&index) => mentions[index].count += 1,
+            None => {
+                indexes.insert(name.to_lowercase(), mentions.len());
+                mentions.push(Mention {
+                    name: name.to_string(),
+                    count: 1,
+                });
+            }
+        }
+    }
+
+    mentions.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+    mentions
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum EntitiesError {
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+async fn load_entries(
+    mongo_entries: &Collection<JournalEntry>,
+) -> Result<Vec<JournalEntry>, EntitiesError> {
+    mongo_entries
+        .find(None, None)
+        .await
+        .map_err(EntitiesError::Mongo)?
+        .try_collect()
+        .await
+        .map_err(EntitiesError::Mongo)
+}
+
+pub async fn get_people(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+) -> Result<Json<Vec<Mention>>, EntitiesError> {
+    let entries = load_entries(&mongo_entries).await?;
+    Ok(Json(count_mentions(
+        entries.iter().flat_map(|entry| &entry.people),
+    )))
+}
+
+pub async fn get_places(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+) -> Result<Json<Vec<Mention>>, EntitiesError> {
+    let entries = load_entries(&mongo_entries).await?;
+    Ok(Json(count_mentions(
+        entries.iter().flat_map(|entry| &entry.places),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::JournalEntry;
+
+    use super::{count_mentions, Mention};
+
+    #[test]
+    fn deserialize_entities() {
+        let entry = serde_json::from_str::<JournalEntry>(
+            r#"{"date":"2024-01-24","rate":7.0,"short_summary":"Café avec Paul","tags":[],"people":["Paul"],"places":["Lyon"]}"#,
+        )
+        .unwrap();
+        assert_eq!(entry.people, vec!["Paul".to_string()]);
+        assert_eq!(entry.places, vec!["Lyon".to_string()]);
+    }
+
+    #[test]
+    fn count_people_ignoring_case() {
+        let people: Vec<String> = ["Paul", "marie", "paul ", "Marie", "PAUL", "Léa"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+
+        assert_eq!(
+            count_mentions(&people),
+            vec![
+                Mention {
+                    name: "Paul".to_string(),
+                    count: 3
+                },
+                Mention {
+                    name: "marie".to_string(),
+                    count: 2
+                },
+                Mention {
+                    name: "Léa".to_string(),
+                    count: 1
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use std::sync::Arc;
+
+use axum::{
+    body::{Body, Bytes},
+    extract::Query,
+    http::{header, StatusCode},
+    response::IntoResponse,
+    BoxError, Extension,
+};
+use axum_thiserror::ErrorStatus;
+use futures_util::{stream, Stream, StreamExt};
+use mongodb::{bson::doc, options::FindOptions, Collection};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{crypto::EntryCipher, routes::JournalEntry};
+
+/// Une entrée JSON par ligne, écrite au fil du flux.
+pub fn ndjson_stream<S, E>(entries: S) -> impl Stream<Item = Result<Bytes, BoxError>>
+where
+    S: Stream<Item = Result<JournalEntry, E>>,
+    E: Into<BoxError>,
+{
+    entries.map(|entry| {
+        let mut line = serde_json::to_vec(&entry.map_err(Into::into)?)?;
+        line.push(b'\n');
+        Ok(Bytes::from(line))
+    })
+}
+
+/// Colonne de l'export CSV. Les listes sont jointes par `;`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CsvField {
+    Date,
+    Rate,
+    Summary,
+    Tags,
+    Topics,
+    WordCount,
+}
+
+/// Colonnes exportées par défaut.
+pub const DEFAULT_CSV_FIELDS: [CsvField; 4] = [
+    CsvField::Date,
+    CsvField::Rate,
+    CsvField::Summary,
+    CsvField::Tags,
+];
+/// Colonnes de l'export anonymisé : aucun contenu, seulement les notes.
+pub const ANONYMIZED_CSV_FIELDS: [CsvField; 2] = [CsvField::Date, CsvField::Rate];
+
+impl CsvField {
+    pub fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "date" => CsvField::Date,
+            "rate" => CsvField::Rate,
+            "summary" => CsvField::Summary,
+            "tags" => CsvField::Tags,
+            "topics" => CsvField::Topics,
+            "word_count" => CsvField::WordCount,
+            _ => return None,
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CsvField::Date => "date",
+            CsvField::Rate => "rate",
+            CsvField::Summary => "summary",
+            CsvField::Tags => "tags",
+            CsvField::Topics => "topics",
+            CsvField::WordCount => "word_count",
+        }
+    }
+
+    fn value(self, entry: &JournalEntry) -> String {
+        match self {
+            CsvField::Date => entry.date.to_string(),
+            CsvField::Rate => entry.rate.to_string(),
+            CsvField::Summary => entry.short_summary.clone(),
+            CsvField::Tags => entry.tags.join(";"),
+            CsvField::Topics => entry
+                .topic_ratings
+                .iter()
+                .map(|rating| rating.topic.as_str())
+                .collect::<Vec<_>>()
+                .join(";"),
+            CsvField::WordCount => entry
+                .word_count
+                .map(|count| count.to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Valeur entre guillemets si elle contient un séparateur, un guillemet ou un retour à la ligne.
+fn csv_value(value: String) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+fn csv_line(values: impl Iterator<Item = String>) -> Bytes {
+    let mut line = values.map(csv_value).collect::<Vec<_>>().join(",");
+    line.push_str("\r\n");
+    Bytes::from(line)
+}
+
+/// En-tête puis une ligne par entrée, écrites au fil du flux.
+pub fn csv_stream<S, E>(
+    entries: S,
+    fields: Vec<CsvField>,
+) -> impl Stream<Item = Result<Bytes, BoxError>>
+where
+    S: Stream<Item = Result<JournalEntry, E>>,
+    E: Into<BoxError>,
+{
+    let header = csv_line(fields.iter().map(|field| field.name().to_string()));
+    stream::once(async move { Ok(header) }).chain(entries.map(move |entry| {
+        let entry = entry.map_err(Into::into)?;
+        Ok(csv_line(fields.iter().map(|field| field.value(&entry))))
+    }))
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct CsvExport {
+    /// N'exporte que la date et la note, pour partager des stats sans le contenu.
+    #[serde(default)]
+    pub anonymize: bool,
+    /// Colonnes séparées par des virgules, par exemple `date,rate,topics`.
+    pub fields: Option<String>,
+}
+
+impl CsvExport {
+    pub fn fields(&self) -> Result<Vec<CsvField>, ExportError> {
+        match (&self.fields, self.anonymize) {
+            (Some(_), true) => Err(ExportError::AnonymizedFields),
+            (None, true) => Ok(ANONYMIZED_CSV_FIELDS.to_vec()),
+            (None, false) => Ok(DEFAULT_CSV_FIELDS.to_vec()),
+            (Some(fields), false) => {
+                let fields = fields
+                    .split(',')
+                    .map(str::trim)
+                    .map(|name| {
+                        CsvField::parse(name)
+                            .ok_or_else(|| ExportError::UnknownField(name.to_string()))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(fields)
+            }
+        }
+    }
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum ExportError {
+    #[error("unknown field \"{0}\", expected date, rate, summary, tags, topics or word_count")]
+    #[status(StatusCode::BAD_REQUEST)]
+    UnknownField(String),
+    #[error("anonymize cannot be combined with fields")]
+    #[status(StatusCode::BAD_REQUEST)]
+    AnonymizedFields,
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+pub async fn get_entries_ndjson(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(cipher): Extension<Arc<EntryCipher>>,
+) -> Result<impl IntoResponse, ExportError> {
+    let cursor = mongo_entries
+        .find(
+            None,
+            FindOptions::builder().sort(doc! { "date": -1 }).build(),
+        )
+        .await
+        .map_err(ExportError::Mongo)?;
+    let entries = cursor.map(move |entry| {
+        entry
+            .map_err(BoxError::from)?
+            .decrypted(&cipher)
+            .map_err(BoxError::from)
+    });
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(ndjson_stream(entries)),
+    ))
+}
+
+pub async fn get_entries_csv(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(cipher): Extension<Arc<EntryCipher>>,
+    Query(query): Query<CsvExport>,
+) -> Result<impl IntoResponse, ExportError> {
+    let fields = query.fields()?;
+    let cursor = mongo_entries
+        .find(
+            None,
+            FindOptions::builder().sort(doc! { "date": -1 }).build(),
+        )
+        .await
+        .map_err(ExportError::Mongo)?;
+    let entries = cursor.map(move |entry| {
+        entry
+            .map_err(BoxError::from)?
+            .decrypted(&cipher)
+            .map_err(BoxError::from)
+    });
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/csv; charset=utf-8")],
+        Body::from_stream(csv_stream(entries, fields)),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use futures_util::{stream, TryStreamExt};
+
+    use crate::routes::{JournalEntry, TopicRating};
+
+    use super::{csv_stream, ndjson_stream, CsvExport, CsvField, ExportError};
+
+    async fn csv(entries: Vec<JournalEntry>, fields: Vec<CsvField>) -> String {
+        let chunks: Vec<_> = csv_stream(
+            stream::iter(entries.into_iter().map(Ok::<_, std::io::Error>)),
+            fields,
+        )
+        .try_collect()
+        .await
+        .unwrap();
+        String::from_utf8(chunks.concat()).unwrap()
+    }
+
+    fn entry() -> JournalEntry {
+        JournalEntry {
+            date: NaiveDate::from_ymd_opt(2024, 1, 24).unwrap(),
+            rate: 6.5,
+            short_summary: "Dîner chez \"Marc\", très sympa".to_string(),
+            tags: vec!["amis".to_string(), "cuisine".to_string()],
+            topic_ratings: vec![TopicRating {
+                topic: "social".to_string(),
+                rate: 8.0,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn one_json_entry_per_line() {
+        let entries: Vec<JournalEntry> = [24, 25, 26]
+            .into_iter()
+            .map(|day| JournalEntry {
+                date: NaiveDate::from_ymd_opt(2024, 1, day).unwrap(),
+                rate: 6.0,
+                short_summary: "Une ligne\navec un retour".to_string(),
+                ..Default::default()
+            })
+            .collect();
+
+        let chunks: Vec<_> = ndjson_stream(stream::iter(
+            entries.clone().into_iter().map(Ok::<_, std::io::Error>),
+        ))
+        .try_collect()
+        .await
+        .unwrap();
+        let body = String::from_utf8(chunks.concat()).unwrap();
+
+        let lines: Vec<JournalEntry> = body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines, entries);
+        assert!(body.ends_with('\n'));
+    }
+
+    #[tokio::test]
+    async fn export_default_columns() {
+        let query = CsvExport::default();
+        assert_eq!(
+            csv(vec![entry()], query.fields().unwrap()).await,
+            "date,rate,summary,tags\r\n2024-01-24,6.5,\"Dîner chez \"\"Marc\"\", très sympa\",amis;cuisine\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn anonymized_export_has_no_summary() {
+        let query = CsvExport {
+            anonymize: true,
+            ..Default::default()
+        };
+        let body = csv(vec![entry()], query.fields().unwrap()).await;
+        assert_eq!(body, "date,rate\r\n2024-01-24,6.5\r\n");
+        assert!(!body.contains("Marc"));
+    }
+
+    #[tokio::test]
+    async fn choose_exported_columns() {
+        let query = CsvExport {
+            fields: Some("date, rate,topics".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            csv(vec![entry()], query.fields().unwrap()).await,
+            "date,rate,topics\r\n2024-01-24,6.5,social\r\n"
+        );
+    }
+
+    #[test]
+    fn reject_unknown_fields() {
+        let query = CsvExport {
+            fields: Some("date,raw_text".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            query.fields(),
+            Err(ExportError::UnknownField(field)) if field == "raw_text"
+        ));
+
+        let query = CsvExport {
+            anonymize: true,
+            fields: Some("date,summary".to_string()),
+        };
+        assert!(matches!(query.fields(), Err(ExportError::AnonymizedFields)));
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use crate::routes::{CreateJournalEntry, JournalEntry};
+
+/// Note attribuée quand aucune analyse n'a pu juger la journée.
+pub const NEUTRAL_RATE: f32 = 5.0;
+
+const SUMMARY_SENTENCES: usize = 2;
+const MAX_TOPICS: usize = 5;
+const MIN_TOPIC_LENGTH: usize = 4;
+
+const STOP_WORDS: &[&str] = &[
+    "alors", "aussi", "avec", "avoir", "cette", "comme", "dans", "depuis", "elle", "elles",
+    "encore", "être", "fait", "faire", "mais", "même", "nous", "pour", "puis", "quand", "sans",
+    "suis", "sont", "sous", "tout", "tous", "très", "vous", "about", "after", "also", "been",
+    "from", "have", "just", "that", "then", "there", "they", "this", "very", "were", "what",
+    "when", "with",
+];
+
+/// Premières phrases du texte.
+pub fn extractive_summary(text: &str) -> String {
+    let mut summary = String::new();
+    let mut sentences = 0;
+    for c in text.chars() {
+        summary.push(c);
+        if matches!(c, '.' | '!' | '?') {
+            sentences += 1;
+            if sentences == SUMMARY_SENTENCES {
+                break;
+            }
+        }
+    }
+    summary.trim().to_string()
+}
+
+/// Mots les plus fréquents du texte, hors mots vides et mots trop courts.
+pub fn extract_topics(text: &str) -> Vec<String> {
+    let mut frequencies: HashMap<String, usize> = HashMap::new();
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .filter(|word| word.chars().count() >= MIN_TOPIC_LENGTH)
+        .filter(|word| !STOP_WORDS.contains(&word.as_str()))
+    {
+        *frequencies.entry(word).or_default() += 1;
+    }
+
+    let mut topics: Vec<(String, usize)> = frequencies.into_iter().collect();
+    topics.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    topics
+        .into_iter()
+        .take(MAX_TOPICS)
+        .map(|(topic, _)| topic)
+        .collect()
+}
+
+/// Analyse de repli, sans appel réseau, quand OpenAI est indisponible.
+pub fn extractive_entry(entry: &CreateJournalEntry) -> JournalEntry {
+    JournalEntry {
+        date: entry.date,
+        rate: NEUTRAL_RATE,
+        short_summary: extractive_summary(&entry.summary),
+        tags: extract_topics(&entry.summary),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::routes::CreateJournalEntry;
+
+    use super::{extract_topics, extractive_entry, extractive_summary, NEUTRAL_RATE};
+
+    const TEXT: &str = "Ce matin, réunion de projet avec l'équipe. Le projet avance bien ! \
+        L'après-midi, séance de sport puis révisions du partiel. Le soir, encore du projet.";
+
+    #[test]
+    fn summarize_first_sentences() {
+        assert_eq!(
+            extractive_summary(TEXT),
+            "Ce matin, réunion de projet avec l'équipe. Le projet avance bien !"
+        );
+        assert_eq!(extractive_summary("Sans ponctuation"), "Sans ponctuation");
+    }
+
+    #[test]
+    fn extract_most_frequent_topics() {
+        let topics = extract_topics(TEXT);
+        assert_eq!(topics.len(), 5);
+        assert_eq!(topics[0], "projet");
+        assert!(!topics.contains(&"avec".to_string()));
+    }
+
+    #[test]
+    fn neutral_fallback_entry() {
+        let entry = extractive_entry(&CreateJournalEntry {
+            name: "Alice".to_string(),
+            summary: TEXT.to_string(),
+            date: NaiveDate::from_ymd_opt(2024, 1, 24).unwrap(),
+            ..Default::default()
+        });
+        assert_eq!(entry.rate, NEUTRAL_RATE);
+        assert_eq!(entry.date, NaiveDate::from_ymd_opt(2024, 1, 24).unwrap());
+        assert!(entry.validate().is_ok());
+    }
+}
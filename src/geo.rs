@@ -0,0 +1,198 @@
+use std::sync::Arc;
+
+use axum::{extract::Query, http::StatusCode, Extension, Json};
+use axum_thiserror::ErrorStatus;
+use futures_util::TryStreamExt;
+use mongodb::{
+    bson::{doc, Document},
+    Collection,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::crypto::{CryptoError, EntryCipher};
+use crate::routes::JournalEntry;
+
+/// Lieu d'une entrée. Accepte `{ lat, lng, label }` en entrée mais est toujours
+/// sérialisé en point GeoJSON, pour que Mongo puisse l'indexer en `2dsphere`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "LocationRepr", into = "GeoJsonLocation")]
+pub struct Location {
+    pub lat: f64,
+    pub lng: f64,
+    pub label: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GeoJsonLocation {
+    r#type: String,
+    coordinates: [f64; 2],
+    label: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LocationRepr {
+    LatLng { lat: f64, lng: f64, label: String },
+    GeoJson(GeoJsonLocation),
+}
+
+impl Location {
+    pub fn new(lat: f64, lng: f64, label: String) -> Result<Self, String> {
+        validate_coordinates(lat, lng)?;
+        Ok(Location { lat, lng, label })
+    }
+}
+
+fn validate_coordinates(lat: f64, lng: f64) -> Result<(), String> {
+    if !(-90.0..=90.0).contains(&lat) {
+        return Err(format!("latitude {lat} is not between -90 and 90"));
+    }
+    if !(-180.0..=180.0).contains(&lng) {
+        return Err(format!("longitude {lng} is not between -180 and 180"));
+    }
+    Ok(())
+}
+
+impl TryFrom<LocationRepr> for Location {
+    type Error = String;
+
+    fn try_from(repr: LocationRepr) -> Result<Self, Self::Error> {
+        match repr {
+            LocationRepr::LatLng { lat, lng, label } => Location::new(lat, lng, label),
+            LocationRepr::GeoJson(GeoJsonLocation {
+                coordinates: [lng, lat],
+                label,
+                ..
+            }) => Location::new(lat, lng, label),
+        }
+    }
+}
+
+impl From<Location> for GeoJsonLocation {
+    fn from(location: Location) -> Self {
+        GeoJsonLocation {
+            r#type: "Point".to_string(),
+            coordinates: [location.lng, location.lat],
+            label: location.label,
+        }
+    }
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum NearbyError {
+    #[error("{0}")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidCoordinates(String),
+    #[error("radius_km must be positive")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidRadius,
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Crypto(CryptoError),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NearbyQuery {
+    pub lat: f64,
+    pub lng: f64,
+    pub radius_km: f64,
+}
+
+impl NearbyQuery {
+    /// Filtre `$near` des entrées situées dans le rayon demandé, les plus proches en premier.
+    pub fn filter(&self) -> Result<Document, NearbyError> {
+        validate_coordinates(self.lat, self.lng).map_err(NearbyError::InvalidCoordinates)?;
+        if self.radius_km.is_nan() || self.radius_km <= 0.0 {
+            return Err(NearbyError::InvalidRadius);
+        }
+
+        Ok(doc! {
+            "location": {
+                "$near": {
+                    "$geometry": { "type": "Point", "coordinates": [self.lng, self.lat] },
+                    "$maxDistance": self.radius_km * 1000.0
+                }
+            }
+        })
+    }
+}
+
+pub async fn get_nearby_entries(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(cipher): Extension<Arc<EntryCipher>>,
+    Query(query): Query<NearbyQuery>,
+) -> Result<Json<Vec<JournalEntry>>, NearbyError> {
+    mongo_entries
+        .find(query.filter()?, None)
+        .await
+        .map_err(NearbyError::Mongo)?
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(NearbyError::Mongo)?
+        .into_iter()
+        .map(|entry| entry.decrypted(&cipher))
+        .collect::<Result<_, _>>()
+        .map(Json)
+        .map_err(NearbyError::Crypto)
+}
+
+#[cfg(test)]
+mod tests {
+    use mongodb::bson::{self, doc};
+
+    use super::{Location, NearbyError, NearbyQuery};
+
+    #[test]
+    fn store_location_as_geojson() {
+        let location =
+            serde_json::from_str::<Location>(r#"{"lat":48.8566,"lng":2.3522,"label":"Paris"}"#)
+                .unwrap();
+
+        let stored = bson::to_bson(&location).unwrap();
+        assert_eq!(
+            stored,
+            bson::Bson::Document(doc! {
+                "type": "Point",
+                "coordinates": [2.3522, 48.8566],
+                "label": "Paris"
+            })
+        );
+        assert_eq!(bson::from_bson::<Location>(stored).unwrap(), location);
+    }
+
+    #[test]
+    fn reject_out_of_bounds_location() {
+        assert!(serde_json::from_str::<Location>(r#"{"lat":91,"lng":2,"label":"?"}"#).is_err());
+        assert!(serde_json::from_str::<Location>(r#"{"lat":0,"lng":-181,"label":"?"}"#).is_err());
+    }
+
+    #[test]
+    fn search_nearby() {
+        let query = NearbyQuery {
+            lat: 45.764,
+            lng: 4.8357,
+            radius_km: 2.5,
+        };
+        assert_eq!(
+            query.filter().unwrap(),
+            doc! {
+                "location": {
+                    "$near": {
+                        "$geometry": { "type": "Point", "coordinates": [4.8357, 45.764] },
+                        "$maxDistance": 2500.0
+                    }
+                }
+            }
+        );
+
+        let query = NearbyQuery {
+            radius_km: 0.0,
+            ..query
+        };
+        assert!(matches!(query.filter(), Err(NearbyError::InvalidRadius)));
+    }
+}
@@ -0,0 +1,205 @@
+use std::sync::Arc;
+
+use axum::{http::StatusCode, Extension, Json};
+use axum_thiserror::ErrorStatus;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures_util::TryStreamExt;
+use mongodb::{bson::doc, Collection};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    config::AppConfig,
+    locale::Locale,
+    recap::{parse_iso_week, parse_month},
+    routes::JournalEntry,
+    stats::{load_entries, writing_stats, StatsError},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GoalMetric {
+    AverageRate,
+    EntriesCount,
+    TotalWords,
+}
+
+/// Objectif sur une période, un mois (`2024-03`) ou une semaine ISO (`2024-W10`).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Goal {
+    pub period: String,
+    pub metric: GoalMetric,
+    pub target: f32,
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GoalProgress {
+    pub goal: Goal,
+    pub current: f32,
+    pub target: f32,
+    pub achieved: bool,
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum GoalError {
+    #[error("invalid period \"{0}\", expected a month like 2024-03 or a week like 2024-W10")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidPeriod(String),
+    #[error("target must be a positive number")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidTarget,
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Stats(StatsError),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+impl Goal {
+    /// Premier et dernier jour de la période de l'objectif.
+    pub fn period_bounds(&self) -> Result<(NaiveDate, NaiveDate), GoalError> {
+        parse_month(&self.period)
+            .or_else(|| parse_iso_week(&self.period))
+            .ok_or_else(|| GoalError::InvalidPeriod(self.period.clone()))
+    }
+
+    /// Avancement de l'objectif au jour `today`, ou `None` si sa période est passée.
+    pub fn progress(&self, entries: &[JournalEntry], today: NaiveDate) -> Option<GoalProgress> {
+        let (start, end) = self.period_bounds().ok()?;
+        if end < today {
+            return None;
+        }
+
+        let entries: Vec<JournalEntry> = entries
+            .iter()
+            .filter(|entry| (start..=end).contains(&entry.date))
+            .cloned()
+            .collect();
+        let current = match self.metric {
+            GoalMetric::AverageRate if entries.is_empty() => 0.0,
+            GoalMetric::AverageRate => {
+                entries.iter().map(|entry| entry.rate).sum::<f32>() / entries.len() as f32
+            }
+            GoalMetric::EntriesCount => entries.len() as f32,
+            GoalMetric::TotalWords => writing_stats(&entries, Locale::default()).total_words as f32,
+        };
+
+        Some(GoalProgress {
+            goal: self.clone(),
+            current,
+            target: self.target,
+            achieved: current >= self.target,
+        })
+    }
+}
+
+pub async fn create_goal(
+    Extension(mongo_goals): Extension<Arc<Collection<Goal>>>,
+    Json(mut goal): Json<Goal>,
+) -> Result<Json<Goal>, GoalError> {
+    goal.period_bounds()?;
+    if !goal.target.is_finite() || goal.target < 0.0 {
+        return Err(GoalError::InvalidTarget);
+    }
+
+    goal.created_at = Some(Utc::now());
+    mongo_goals
+        .insert_one(&goal, None)
+        .await
+        .map_err(GoalError::Mongo)?;
+    Ok(Json(goal))
+}
+
+/// Avancement des objectifs dont la période n'est pas terminée.
+pub async fn get_goals_progress(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(mongo_goals): Extension<Arc<Collection<Goal>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<Json<Vec<GoalProgress>>, GoalError> {
+    let goals: Vec<Goal> = mongo_goals
+        .find(None, None)
+        .await
+        .map_err(GoalError::Mongo)?
+        .try_collect()
+        .await
+        .map_err(GoalError::Mongo)?;
+
+    let timezone = config.timezone;
+    let entries = load_entries(&mongo_entries, timezone, doc! {})
+        .await
+        .map_err(GoalError::Stats)?;
+    let today = Utc::now().with_timezone(&timezone).date_naive();
+
+    Ok(Json(
+        goals
+            .iter()
+            .filter_map(|goal| goal.progress(&entries, today))
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::routes::JournalEntry;
+
+    use super::{Goal, GoalMetric};
+
+    fn goal(metric: GoalMetric, target: f32) -> Goal {
+        Goal {
+            period: "2024-03".to_string(),
+            metric,
+            target,
+            created_at: None,
+        }
+    }
+
+    #[test]
+    fn follow_goal_progress() {
+        let entries: Vec<JournalEntry> = [(28, 2, 9.0), (1, 3, 6.0), (2, 3, 8.0), (3, 3, 8.5)]
+            .into_iter()
+            .map(|(day, month, rate)| JournalEntry {
+                date: NaiveDate::from_ymd_opt(2024, month, day).unwrap(),
+                rate,
+                ..Default::default()
+            })
+            .collect();
+        let today = NaiveDate::from_ymd_opt(2024, 3, 15).unwrap();
+
+        let progress = goal(GoalMetric::AverageRate, 7.0)
+            .progress(&entries, today)
+            .unwrap();
+        assert_eq!(progress.current, 7.5);
+        assert!(progress.achieved);
+
+        let progress = goal(GoalMetric::EntriesCount, 20.0)
+            .progress(&entries, today)
+            .unwrap();
+        assert_eq!(progress.current, 3.0);
+        assert!(!progress.achieved);
+    }
+
+    #[test]
+    fn ignore_expired_goals() {
+        let goal = goal(GoalMetric::AverageRate, 7.0);
+        assert!(goal
+            .progress(&[], NaiveDate::from_ymd_opt(2024, 4, 1).unwrap())
+            .is_none());
+        assert!(goal
+            .progress(&[], NaiveDate::from_ymd_opt(2024, 3, 31).unwrap())
+            .is_some());
+
+        let week = Goal {
+            period: "2024-W10".to_string(),
+            ..goal
+        };
+        assert_eq!(
+            week.period_bounds().unwrap().1,
+            NaiveDate::from_ymd_opt(2024, 3, 10).unwrap()
+        );
+    }
+}
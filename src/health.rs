@@ -0,0 +1,204 @@
+use std::{
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_openai::{config::OpenAIConfig, Client};
+use axum::{http::StatusCode, Extension, Json};
+use mongodb::{bson::doc, Database};
+use serde::{Deserialize, Serialize};
+
+/// Délai au-delà duquel une dépendance est considérée comme indisponible.
+pub const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Up,
+    Down,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DependencyHealth {
+    pub status: HealthStatus,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Mesure la latence de `check`, en échec s'il ne répond pas à temps.
+async fn check<T, E: ToString>(check: impl Future<Output = Result<T, E>>) -> DependencyHealth {
+    let start = Instant::now();
+    let result = tokio::time::timeout(HEALTH_CHECK_TIMEOUT, check).await;
+    let latency_ms = start.elapsed().as_millis() as u64;
+
+    let error = match result {
+        Ok(Ok(_)) => None,
+        Ok(Err(error)) => Some(error.to_string()),
+        Err(_) => Some(format!(
+            "no answer after {}s",
+            HEALTH_CHECK_TIMEOUT.as_secs()
+        )),
+    };
+    DependencyHealth {
+        status: if error.is_none() {
+            HealthStatus::Up
+        } else {
+            HealthStatus::Down
+        },
+        latency_ms,
+        error,
+    }
+}
+
+/// Vérifie qu'OpenAI répond en listant les modèles, ce qui ne consomme aucun token.
+pub async fn check_openai(openai: &Client<OpenAIConfig>) -> DependencyHealth {
+    check(openai.models().list()).await
+}
+
+pub async fn check_mongo(database: &Database) -> DependencyHealth {
+    check(database.run_command(doc! { "ping": 1 }, None)).await
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OverallStatus {
+    Ok,
+    /// Une dépendance optionnelle (OpenAI) est indisponible : les entrées sont
+    /// encore enregistrées, avec un résumé extractif.
+    Degraded,
+    Down,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DetailedHealth {
+    pub status: OverallStatus,
+    pub version: String,
+    pub mongo: DependencyHealth,
+    pub openai: DependencyHealth,
+}
+
+impl DetailedHealth {
+    pub fn new(mongo: DependencyHealth, openai: DependencyHealth) -> Self {
+        let status = match (mongo.status, openai.status) {
+            (HealthStatus::Down, _) => OverallStatus::Down,
+            (HealthStatus::Up, HealthStatus::Down) => OverallStatus::Degraded,
+            (HealthStatus::Up, HealthStatus::Up) => OverallStatus::Ok,
+        };
+        DetailedHealth {
+            status,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            mongo,
+            openai,
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self.status {
+            OverallStatus::Ok | OverallStatus::Degraded => StatusCode::OK,
+            OverallStatus::Down => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+pub async fn detailed_health(
+    Extension(database): Extension<Arc<Database>>,
+    Extension(openai): Extension<Arc<Client<OpenAIConfig>>>,
+) -> (StatusCode, Json<DetailedHealth>) {
+    let (mongo, openai) = tokio::join!(check_mongo(&database), check_openai(&openai));
+    let health = DetailedHealth::new(mongo, openai);
+    (health.status_code(), Json(health))
+}
+
+pub async fn openai_health(
+    Extension(openai): Extension<Arc<Client<OpenAIConfig>>>,
+) -> (StatusCode, Json<DependencyHealth>) {
+    let health = check_openai(&openai).await;
+    let status = match health.status {
+        HealthStatus::Up => StatusCode::OK,
+        HealthStatus::Down => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (status, Json(health))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{http::StatusCode, routing::get, Extension, Json, Router};
+    use serde_json::json;
+
+    use crate::testing::mock_openai;
+
+    use super::{
+        check_openai, openai_health, DependencyHealth, DetailedHealth, HealthStatus, OverallStatus,
+    };
+
+    async fn invalid_key_openai() -> async_openai::Client<async_openai::config::OpenAIConfig> {
+        mock_openai(Router::new().route(
+            "/models",
+            get(|| async {
+                (
+                    StatusCode::UNAUTHORIZED,
+                    Json(json!({ "error": {
+                        "message": "Incorrect API key provided",
+                        "type": "invalid_request_error",
+                        "param": null,
+                        "code": "invalid_api_key"
+                    } })),
+                )
+            }),
+        ))
+        .await
+    }
+
+    #[tokio::test]
+    async fn openai_up() {
+        let openai = mock_openai(Router::new().route(
+            "/models",
+            get(|| async { Json(json!({ "object": "list", "data": [] })) }),
+        ))
+        .await;
+
+        let (status, Json(health)) = openai_health(Extension(Arc::new(openai))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(health.status, HealthStatus::Up);
+        assert_eq!(health.error, None);
+    }
+
+    #[tokio::test]
+    async fn openai_down() {
+        let openai = invalid_key_openai().await;
+
+        let (status, Json(health)) = openai_health(Extension(Arc::new(openai))).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(health.status, HealthStatus::Down);
+        assert!(health.error.unwrap().contains("Incorrect API key"));
+    }
+
+    #[tokio::test]
+    async fn degraded_when_openai_is_down() {
+        let mongo = DependencyHealth {
+            status: HealthStatus::Up,
+            latency_ms: 2,
+            error: None,
+        };
+        let openai = check_openai(&invalid_key_openai().await).await;
+
+        let health = DetailedHealth::new(mongo.clone(), openai.clone());
+        assert_eq!(health.status, OverallStatus::Degraded);
+        assert_eq!(health.status_code(), StatusCode::OK);
+        assert_eq!(health.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(health.openai.status, HealthStatus::Down);
+
+        let mongo_down = DependencyHealth {
+            status: HealthStatus::Down,
+            error: Some("connection refused".to_string()),
+            ..mongo
+        };
+        let health = DetailedHealth::new(mongo_down, openai);
+        assert_eq!(health.status, OverallStatus::Down);
+        assert_eq!(health.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
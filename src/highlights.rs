@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use axum::{extract::Path, http::StatusCode, Extension, Json};
+use axum_thiserror::ErrorStatus;
+use chrono::NaiveDate;
+use mongodb::{bson::doc, options::FindOneOptions, Collection};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::routes::{date_filter, JournalEntry};
+
+/// Phrase marquante de l'entrée repérée par GPT.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Highlight {
+    pub text: String,
+    /// `positive`, `negative` ou `neutral`.
+    pub sentiment: String,
+    /// Position de la phrase dans le texte saisi, en caractères, si elle y figure telle quelle.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<usize>,
+}
+
+/// Renseigne la position de chaque moment fort dans `text` : GPT recopie les
+/// phrases mais ne sait pas compter les caractères.
+pub fn locate_highlights(highlights: &mut [Highlight], text: &str) {
+    for highlight in highlights {
+        highlight.position = text
+            .find(highlight.text.trim())
+            .map(|index| text[..index].chars().count());
+    }
+}
+
+#[derive(Deserialize)]
+struct EntryHighlights {
+    #[serde(default)]
+    highlights: Vec<Highlight>,
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum HighlightsError {
+    #[error("no journal entry for {0}")]
+    #[status(StatusCode::NOT_FOUND)]
+    NotFound(NaiveDate),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+pub async fn get_entry_highlights(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Path(date): Path<NaiveDate>,
+) -> Result<Json<Vec<Highlight>>, HighlightsError> {
+    mongo_entries
+        .clone_with_type::<EntryHighlights>()
+        .find_one(
+            date_filter(date),
+            FindOneOptions::builder()
+                .projection(doc! { "_id": 0, "highlights": 1 })
+                .build(),
+        )
+        .await
+        .map_err(HighlightsError::Mongo)?
+        .map(|entry| Json(entry.highlights))
+        .ok_or(HighlightsError::NotFound(date))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::prompt::parse_entry_response;
+
+    use super::{locate_highlights, Highlight};
+
+    #[test]
+    fn deserialize_highlights() {
+        let entry = parse_entry_response(
+            r#"{"date":"2024-03-04","rate":8.0,"short_summary":"Belle journée","tags":[],"highlights":[{"text":"J'ai eu mon partiel.","sentiment":"positive"},{"text":"Le bus était en retard.","sentiment":"negative"}]}"#,
+            NaiveDate::from_ymd_opt(2024, 3, 4).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            entry.highlights,
+            vec![
+                Highlight {
+                    text: "J'ai eu mon partiel.".to_string(),
+                    sentiment: "positive".to_string(),
+                    position: None,
+                },
+                Highlight {
+                    text: "Le bus était en retard.".to_string(),
+                    sentiment: "negative".to_string(),
+                    position: None,
+                },
+            ]
+        );
+
+        let without = parse_entry_response(
+            r#"{"date":"2024-03-04","rate":8.0,"short_summary":"Belle journée","tags":[]}"#,
+            NaiveDate::from_ymd_opt(2024, 3, 4).unwrap(),
+        )
+        .unwrap();
+        assert!(without.highlights.is_empty());
+    }
+
+    #[test]
+    fn locate_highlights_in_text() {
+        let mut highlights = vec![
+            Highlight {
+                text: "J'ai eu mon partiel.".to_string(),
+                sentiment: "positive".to_string(),
+                position: None,
+            },
+            Highlight {
+                text: "Phrase reformulée".to_string(),
+                sentiment: "neutral".to_string(),
+                position: None,
+            },
+        ];
+        locate_highlights(&mut highlights, "Réveil tôt. J'ai eu mon partiel.");
+
+        assert_eq!(highlights[0].position, Some(12));
+        assert_eq!(highlights[1].position, None);
+    }
+}
@@ -0,0 +1,270 @@
+use std::sync::Arc;
+
+use async_openai::{
+    config::OpenAIConfig,
+    error::OpenAIError,
+    types::{
+        ChatCompletionRequestMessage, ChatCompletionRequestSystemMessageArgs,
+        ChatCompletionRequestUserMessageArgs, CreateChatCompletionRequestArgs,
+    },
+    Client,
+};
+use axum::{http::StatusCode, Extension, Json};
+use axum_thiserror::ErrorStatus;
+use futures_util::TryStreamExt;
+use mongodb::Collection;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config::AppConfig;
+use crate::routes::JournalEntry;
+use crate::stats::{topic_correlation_pipeline, topic_correlations, StatsError, TopicCorrelation};
+use crate::throttle::{take_turn, OpenAiThrottle, ThrottleError};
+use crate::usage::{TokenUsage, UsageCounter};
+
+/// Nombre maximal de topics pour lesquels une suggestion est demandée.
+pub const MAX_INSIGHTS: usize = 3;
+/// Entrées minimales d'un topic pour que sa moyenne soit significative.
+pub const MIN_TOPIC_ENTRIES: u32 = 3;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Insight {
+    pub topic: String,
+    pub avg_rate: f32,
+    pub suggestion: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct InsightsResponse {
+    suggestions: Vec<TopicSuggestion>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TopicSuggestion {
+    topic: String,
+    suggestion: String,
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum InsightsError {
+    #[error(transparent)]
+    #[status(StatusCode::TOO_MANY_REQUESTS)]
+    Throttle(ThrottleError),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    OpenAI(OpenAIError),
+    #[error("no output from GPT-4")]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    NoOutput,
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Parse(serde_json::Error),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Stats(StatsError),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+/// Topics qui tirent la note vers le bas, les pires en premier, en écartant
+/// ceux qui n'apparaissent pas assez souvent.
+pub fn worst_topics(correlations: &[TopicCorrelation]) -> Vec<TopicCorrelation> {
+    let mut topics: Vec<TopicCorrelation> = correlations
+        .iter()
+        .filter(|topic| topic.delta < 0.0 && topic.count >= MIN_TOPIC_ENTRIES)
+        .cloned()
+        .collect();
+    topics.sort_by(|a, b| a.delta.total_cmp(&b.delta));
+    topics.truncate(MAX_INSIGHTS);
+    topics
+}
+
+pub fn worst_topics_message(topics: &[TopicCorrelation]) -> String {
+    topics
+        .iter()
+        .map(|topic| {
+            format!(
+                "{}: {:.1}/10 over {} entries (global average {:.1}/10)",
+                topic.topic, topic.average_rate, topic.count, topic.global_average
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Demande à GPT une suggestion par topic, en un seul appel. Sans topic assez
+/// négatif, aucun appel n'est fait. Les suggestions pour des topics qui n'ont
+/// pas été envoyés sont ignorées.
+pub async fn generate_insights(
+    openai: &Client<OpenAIConfig>,
+    throttle: Option<&OpenAiThrottle>,
+    usage: &UsageCounter,
+    model: &str,
+    topics: &[TopicCorrelation],
+) -> Result<Vec<Insight>, InsightsError> {
+    if topics.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let completion_request = CreateChatCompletionRequestArgs::default()
+        .model(model)
+        .messages(vec![
+            ChatCompletionRequestMessage::System(
+                ChatCompletionRequestSystemMessageArgs::default()
+                    .content(include_str!("./insights_message.txt"))
+                    .build()
+                    .map_err(InsightsError::OpenAI)?,
+            ),
+            ChatCompletionRequestMessage::User(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content(worst_topics_message(topics))
+                    .build()
+                    .map_err(InsightsError::OpenAI)?,
+            ),
+        ])
+        .n(1)
+        .build()
+        .map_err(InsightsError::OpenAI)?;
+
+    take_turn(throttle).await.map_err(InsightsError::Throttle)?;
+    let response = openai
+        .chat()
+        .create(completion_request)
+        .await
+        .map_err(InsightsError::OpenAI)?;
+    usage.record(response.usage.as_ref().map(TokenUsage::from));
+    let content = response
+        .choices
+        .first()
+        .and_then(|o| o.message.clone().content)
+        .filter(|content| !content.trim().is_empty())
+        .ok_or(InsightsError::NoOutput)?;
+    let response =
+        serde_json::from_str::<InsightsResponse>(&content).map_err(InsightsError::Parse)?;
+
+    Ok(topics
+        .iter()
+        .filter_map(|topic| {
+            let suggestion = response
+                .suggestions
+                .iter()
+                .find(|suggestion| suggestion.topic.trim().eq_ignore_ascii_case(&topic.topic))?;
+            Some(Insight {
+                topic: topic.topic.clone(),
+                avg_rate: topic.average_rate,
+                suggestion: suggestion.suggestion.trim().to_string(),
+            })
+        })
+        .collect())
+}
+
+pub async fn get_insights(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(openai): Extension<Arc<Client<OpenAIConfig>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(usage): Extension<Arc<UsageCounter>>,
+    throttle: Option<Extension<Arc<OpenAiThrottle>>>,
+) -> Result<Json<Vec<Insight>>, InsightsError> {
+    let facets = mongo_entries
+        .aggregate(topic_correlation_pipeline(), None)
+        .await
+        .map_err(InsightsError::Mongo)?
+        .try_next()
+        .await
+        .map_err(InsightsError::Mongo)?
+        .unwrap_or_default();
+    let correlations = topic_correlations(facets).map_err(InsightsError::Stats)?;
+
+    Ok(Json(
+        generate_insights(
+            &openai,
+            throttle.as_deref().map(Arc::as_ref),
+            &usage,
+            &config.chat_model,
+            &worst_topics(&correlations),
+        )
+        .await?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::Router;
+
+    use crate::{
+        config::CHAT_MODEL,
+        stats::TopicCorrelation,
+        testing::{mock_chat_completion, mock_openai},
+        usage::UsageCounter,
+    };
+
+    use super::{generate_insights, worst_topics, Insight};
+
+    fn correlation(topic: &str, average_rate: f32, count: u32) -> TopicCorrelation {
+        TopicCorrelation {
+            topic: topic.to_string(),
+            average_rate,
+            global_average: 6.0,
+            delta: average_rate - 6.0,
+            count,
+        }
+    }
+
+    #[test]
+    fn keep_frequent_negative_topics() {
+        let correlations = [
+            correlation("examens", 3.0, 8),
+            correlation("dispute", 2.0, 1),
+            correlation("fatigue", 5.0, 4),
+            correlation("sport", 8.0, 10),
+            correlation("pluie", 5.5, 3),
+            correlation("transports", 5.8, 5),
+        ];
+
+        let topics: Vec<String> = worst_topics(&correlations)
+            .into_iter()
+            .map(|topic| topic.topic)
+            .collect();
+        assert_eq!(topics, ["examens", "fatigue", "pluie"]);
+    }
+
+    #[tokio::test]
+    async fn suggest_improvements_for_negative_topic() {
+        let openai = mock_chat_completion(
+            r#"{"suggestions":[{"topic":"Examens","suggestion":" Prévois des pauses courtes pendant tes révisions. "},{"topic":"météo","suggestion":"Sors quand même."}]}"#,
+        )
+        .await;
+        let topics = worst_topics(&[
+            correlation("examens", 3.0, 8),
+            correlation("sport", 8.0, 10),
+        ]);
+
+        let usage = UsageCounter::default();
+        let insights = generate_insights(&openai, None, &usage, CHAT_MODEL, &topics)
+            .await
+            .unwrap();
+        assert_eq!(usage.stats().total_tokens, 150);
+        assert_eq!(
+            insights,
+            [Insight {
+                topic: "examens".to_string(),
+                avg_rate: 3.0,
+                suggestion: "Prévois des pauses courtes pendant tes révisions.".to_string(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn not_enough_data_skips_gpt() {
+        let openai = mock_openai(Router::new()).await;
+        let topics = worst_topics(&[correlation("examens", 3.0, 2)]);
+        assert!(topics.is_empty());
+        assert!(
+            generate_insights(&openai, None, &UsageCounter::default(), CHAT_MODEL, &topics)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_openai::{config::OpenAIConfig, Client};
+use axum::{
+    extract::{self, Path},
+    http::StatusCode,
+    Extension, Json,
+};
+use axum_thiserror::ErrorStatus;
+use mongodb::{bson::oid::ObjectId, Collection};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config::AppConfig;
+use crate::crypto::EntryCipher;
+use crate::revisions::EntryRevision;
+use crate::routes::{process_journal_entry, CreateJournalEntry, JournalEntry};
+use crate::throttle::OpenAiThrottle;
+use crate::usage::UsageCounter;
+
+/// Durée de conservation d'un job après sa création.
+pub const JOB_TTL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum JobStatus {
+    Pending,
+    Done { result: Box<JournalEntry> },
+    Failed { error: String },
+}
+
+#[derive(Debug, Default)]
+pub struct JobStore {
+    jobs: Mutex<HashMap<String, (Instant, JobStatus)>>,
+}
+
+impl JobStore {
+    /// Lance `task` en tâche de fond et renvoie l'identifiant du job associé.
+    pub fn spawn<F>(self: &Arc<Self>, task: F) -> String
+    where
+        F: Future<Output = Result<JournalEntry, String>> + Send + 'static,
+    {
+        let id = ObjectId::new().to_hex();
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            jobs.retain(|_, (created_at, _)| created_at.elapsed() < JOB_TTL);
+            jobs.insert(id.clone(), (Instant::now(), JobStatus::Pending));
+        }
+
+        let store = self.clone();
+        let job_id = id.clone();
+        tokio::spawn(async move {
+            let status = match task.await {
+                Ok(result) => JobStatus::Done {
+                    result: Box::new(result),
+                },
+                Err(error) => JobStatus::Failed { error },
+            };
+            if let Some(job) = store.jobs.lock().unwrap().get_mut(&job_id) {
+                job.1 = status;
+            }
+        });
+
+        id
+    }
+
+    pub fn status(&self, id: &str) -> Option<JobStatus> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .get(id)
+            .filter(|(created_at, _)| created_at.elapsed() < JOB_TTL)
+            .map(|(_, status)| status.clone())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreatedJob {
+    pub job_id: String,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn create_journal_entry_async(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(revisions): Extension<Arc<Collection<EntryRevision>>>,
+    Extension(openai): Extension<Arc<Client<OpenAIConfig>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(cipher): Extension<Arc<EntryCipher>>,
+    Extension(usage): Extension<Arc<UsageCounter>>,
+    Extension(jobs): Extension<Arc<JobStore>>,
+    throttle: Option<Extension<Arc<OpenAiThrottle>>>,
+    extract::Json(journal_entry): extract::Json<CreateJournalEntry>,
+) -> (StatusCode, Json<CreatedJob>) {
+    let job_id = jobs.spawn(async move {
+        let entry = process_journal_entry(
+            &mongo_entries,
+            &revisions,
+            &openai,
+            throttle.as_deref().map(Arc::as_ref),
+            &config,
+            &cipher,
+            journal_entry,
+            false,
+        )
+        .await
+        .map_err(|error| error.to_string())?;
+        usage.record(entry.tokens);
+        Ok(entry)
+    });
+
+    (StatusCode::ACCEPTED, Json(CreatedJob { job_id }))
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum JobStatusError {
+    #[error("no job with id {0}")]
+    #[status(StatusCode::NOT_FOUND)]
+    NotFound(String),
+}
+
+pub async fn get_job_status(
+    Extension(jobs): Extension<Arc<JobStore>>,
+    Path(id): Path<String>,
+) -> Result<Json<JobStatus>, JobStatusError> {
+    jobs.status(&id)
+        .map(Json)
+        .ok_or(JobStatusError::NotFound(id))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use chrono::NaiveDate;
+
+    use crate::routes::JournalEntry;
+
+    use super::{JobStatus, JobStore};
+
+    async fn wait_for(jobs: &JobStore, id: &str) -> JobStatus {
+        loop {
+            match jobs.status(id) {
+                Some(JobStatus::Pending) => tokio::time::sleep(Duration::from_millis(5)).await,
+                Some(status) => return status,
+                None => panic!("job {id} disappeared"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn follow_job_until_done() {
+        let jobs = Arc::new(JobStore::default());
+        let entry = JournalEntry {
+            date: NaiveDate::from_ymd_opt(2024, 1, 24).unwrap(),
+            rate: 7.0,
+            short_summary: "Bonne journée".to_string(),
+            ..Default::default()
+        };
+
+        let (sender, receiver) = tokio::sync::oneshot::channel::<()>();
+        let result = entry.clone();
+        let id = jobs.spawn(async move {
+            receiver.await.unwrap();
+            Ok(result)
+        });
+        assert_eq!(jobs.status(&id), Some(JobStatus::Pending));
+
+        sender.send(()).unwrap();
+        assert_eq!(
+            wait_for(&jobs, &id).await,
+            JobStatus::Done {
+                result: Box::new(entry)
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn follow_failed_job() {
+        let jobs = Arc::new(JobStore::default());
+        let id = jobs.spawn(async { Err("no output from GPT-4".to_string()) });
+
+        assert_eq!(
+            wait_for(&jobs, &id).await,
+            JobStatus::Failed {
+                error: "no output from GPT-4".to_string()
+            }
+        );
+        assert_eq!(jobs.status("unknown"), None);
+    }
+}
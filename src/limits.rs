@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::config::AppConfig;
+use crate::routes::{MAX_TAGS, MAX_TAG_LENGTH, RATE_RANGE, STYLE_HINT_MAX_LENGTH};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RateLimits {
+    pub min: f32,
+    pub max: f32,
+}
+
+/// Contraintes de saisie, pour que le frontend affiche les compteurs sans
+/// coder les limites en dur. Les longueurs sont en caractères.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Limits {
+    pub max_summary_length: usize,
+    pub max_style_hint_length: usize,
+    pub max_tags: usize,
+    pub max_tag_length: usize,
+    pub rate: RateLimits,
+    pub chat_model: String,
+}
+
+impl Limits {
+    pub fn new(config: &AppConfig) -> Self {
+        Limits {
+            max_summary_length: config.max_summary_length,
+            max_style_hint_length: STYLE_HINT_MAX_LENGTH,
+            max_tags: MAX_TAGS,
+            max_tag_length: MAX_TAG_LENGTH,
+            rate: RateLimits {
+                min: *RATE_RANGE.start(),
+                max: *RATE_RANGE.end(),
+            },
+            chat_model: config.chat_model.clone(),
+        }
+    }
+}
+
+pub async fn get_limits(Extension(config): Extension<Arc<AppConfig>>) -> Json<Limits> {
+    Json(Limits::new(&config))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::Extension;
+    use serde_json::json;
+
+    use crate::config::AppConfig;
+
+    use super::get_limits;
+
+    #[tokio::test]
+    async fn expose_current_limits() {
+        let config = AppConfig {
+            max_summary_length: 5000,
+            chat_model: "gpt-4o-mini".to_string(),
+            ..Default::default()
+        };
+
+        let limits = get_limits(Extension(Arc::new(config))).await.0;
+        assert_eq!(
+            serde_json::to_value(limits).unwrap(),
+            json!({
+                "max_summary_length": 5000,
+                "max_style_hint_length": 100,
+                "max_tags": 20,
+                "max_tag_length": 30,
+                "rate": { "min": 0.0, "max": 10.0 },
+                "chat_model": "gpt-4o-mini",
+            })
+        );
+    }
+}
@@ -0,0 +1,241 @@
+use std::sync::{Arc, RwLock};
+
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+    Extension, Json,
+};
+use axum_thiserror::ErrorStatus;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config::AppConfig;
+
+/// Configuration courante, remplacée d'un bloc à chaque mise à jour : une
+/// requête en cours garde la configuration qu'elle a reçue à son arrivée.
+#[derive(Debug, Clone)]
+pub struct LiveConfig {
+    current: Arc<RwLock<Arc<AppConfig>>>,
+}
+
+impl LiveConfig {
+    pub fn new(config: Arc<AppConfig>) -> Self {
+        LiveConfig {
+            current: Arc::new(RwLock::new(config)),
+        }
+    }
+
+    pub fn get(&self) -> Arc<AppConfig> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Applique la mise à jour si toutes ses valeurs sont valides, sans rien changer sinon.
+    pub fn update(&self, update: ConfigUpdate) -> Result<Arc<AppConfig>, ConfigUpdateError> {
+        let mut current = self.current.write().unwrap();
+        let updated = Arc::new(update.apply(&current)?);
+        *current = updated.clone();
+        Ok(updated)
+    }
+}
+
+/// Fournit à chaque requête la configuration courante en `Extension<Arc<AppConfig>>`,
+/// les handlers n'ayant pas à savoir qu'elle peut changer.
+pub async fn with_current_config(
+    State(config): State<LiveConfig>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    request.extensions_mut().insert(config.get());
+    next.run(request).await
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum ConfigUpdateError {
+    #[error("model names cannot be empty")]
+    #[status(StatusCode::BAD_REQUEST)]
+    EmptyModel,
+    #[error("temperature must be between 0 and 2, got {0}")]
+    #[status(StatusCode::BAD_REQUEST)]
+    Temperature(f32),
+    #[error("default_list_limit must be at least 1")]
+    #[status(StatusCode::BAD_REQUEST)]
+    ListLimit,
+    #[error("similarity_threshold must be between 0 and 1, got {0}")]
+    #[status(StatusCode::BAD_REQUEST)]
+    SimilarityThreshold(f32),
+}
+
+/// Paramètres modifiables à chaud. Ceux fixés au démarrage (base, CORS, délai des
+/// requêtes, débit OpenAI…) sont refusés comme champs inconnus.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigUpdate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat_model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_list_limit: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub similarity_threshold: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compress_after_days: Option<u64>,
+}
+
+impl ConfigUpdate {
+    pub fn apply(self, config: &AppConfig) -> Result<AppConfig, ConfigUpdateError> {
+        let model = |name: Option<String>, current: &str| match name {
+            Some(name) if name.trim().is_empty() => Err(ConfigUpdateError::EmptyModel),
+            Some(name) => Ok(name.trim().to_string()),
+            None => Ok(current.to_string()),
+        };
+        let temperature = self.temperature.unwrap_or(config.temperature);
+        if !(0.0..=2.0).contains(&temperature) {
+            return Err(ConfigUpdateError::Temperature(temperature));
+        }
+        let default_list_limit = self.default_list_limit.unwrap_or(config.default_list_limit);
+        if default_list_limit == 0 {
+            return Err(ConfigUpdateError::ListLimit);
+        }
+        let similarity_threshold = self
+            .similarity_threshold
+            .unwrap_or(config.similarity_threshold);
+        if !(0.0..=1.0).contains(&similarity_threshold) {
+            return Err(ConfigUpdateError::SimilarityThreshold(similarity_threshold));
+        }
+
+        Ok(AppConfig {
+            chat_model: model(self.chat_model, &config.chat_model)?,
+            embedding_model: model(self.embedding_model, &config.embedding_model)?,
+            temperature,
+            default_list_limit,
+            similarity_threshold,
+            compress_after_days: self
+                .compress_after_days
+                .unwrap_or(config.compress_after_days),
+            ..config.clone()
+        })
+    }
+
+    /// Valeurs courantes des paramètres modifiables à chaud.
+    pub fn current(config: &AppConfig) -> Self {
+        ConfigUpdate {
+            chat_model: Some(config.chat_model.clone()),
+            embedding_model: Some(config.embedding_model.clone()),
+            temperature: Some(config.temperature),
+            default_list_limit: Some(config.default_list_limit),
+            similarity_threshold: Some(config.similarity_threshold),
+            compress_after_days: Some(config.compress_after_days),
+        }
+    }
+}
+
+pub async fn get_config(Extension(config): Extension<LiveConfig>) -> Json<ConfigUpdate> {
+    Json(ConfigUpdate::current(&config.get()))
+}
+
+pub async fn update_config(
+    Extension(config): Extension<LiveConfig>,
+    Json(update): Json<ConfigUpdate>,
+) -> Result<Json<ConfigUpdate>, ConfigUpdateError> {
+    let updated = config.update(update.clone())?;
+    tracing::warn!("Configuration updated: {update:?}");
+    Ok(Json(ConfigUpdate::current(&updated)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+        middleware,
+        routing::{get, post},
+        Extension, Router,
+    };
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    use crate::config::AppConfig;
+
+    use super::{get_config, update_config, with_current_config, ConfigUpdate, LiveConfig};
+
+    async fn call(app: &Router, method: &str, uri: &str, body: &str) -> (StatusCode, String) {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn change_temperature_without_restart() {
+        let config = LiveConfig::new(Arc::new(AppConfig::default()));
+        let app = Router::new()
+            .route(
+                "/temperature",
+                get(|Extension(config): Extension<Arc<AppConfig>>| async move {
+                    config.temperature.to_string()
+                }),
+            )
+            .route("/admin/config", post(update_config).get(get_config))
+            .layer(Extension(config.clone()))
+            .layer(middleware::from_fn_with_state(
+                config.clone(),
+                with_current_config,
+            ));
+
+        assert_eq!(call(&app, "GET", "/temperature", "").await.1, "1");
+
+        let (status, body) = call(&app, "POST", "/admin/config", r#"{"temperature":0.2}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        let current: ConfigUpdate = serde_json::from_str(&body).unwrap();
+        assert_eq!(current.temperature, Some(0.2));
+        assert_eq!(current.chat_model.as_deref(), Some("gpt-3.5-turbo"));
+        assert_eq!(call(&app, "GET", "/temperature", "").await.1, "0.2");
+
+        // Une valeur invalide ne change rien
+        let (status, _) = call(&app, "POST", "/admin/config", r#"{"temperature":3}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(config.get().temperature, 0.2);
+
+        let (_, body) = call(&app, "GET", "/admin/config", "").await;
+        assert!(body.contains(r#""temperature":0.2"#));
+    }
+
+    #[tokio::test]
+    async fn reject_settings_fixed_at_startup() {
+        let config = LiveConfig::new(Arc::new(AppConfig::default()));
+        let app = Router::new()
+            .route("/admin/config", post(update_config))
+            .layer(Extension(config.clone()));
+
+        for body in [
+            r#"{"mongo":"mongodb://elsewhere"}"#,
+            r#"{"request_timeout":1}"#,
+            r#"{"chat_model":"  "}"#,
+            r#"{"default_list_limit":0}"#,
+            r#"{"similarity_threshold":1.5}"#,
+        ] {
+            let (status, _) = call(&app, "POST", "/admin/config", body).await;
+            assert!(status.is_client_error(), "{body} was accepted");
+        }
+        assert_eq!(config.get().chat_model, "gpt-3.5-turbo");
+        assert_eq!(config.get().default_list_limit, 1000);
+    }
+}
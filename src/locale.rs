@@ -0,0 +1,140 @@
+use axum::http::{header, HeaderMap};
+use chrono::{Datelike, NaiveDate, Weekday};
+use serde::Deserialize;
+
+/// Langue des libellés (mois, jours) renvoyés par les statistiques.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    Fr,
+    En,
+}
+
+const FRENCH_MONTHS: [&str; 12] = [
+    "janvier",
+    "février",
+    "mars",
+    "avril",
+    "mai",
+    "juin",
+    "juillet",
+    "août",
+    "septembre",
+    "octobre",
+    "novembre",
+    "décembre",
+];
+const ENGLISH_MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+const FRENCH_WEEKDAYS: [&str; 7] = [
+    "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche",
+];
+const ENGLISH_WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+impl Locale {
+    /// Locale demandée, sinon la première langue gérée d'`Accept-Language`, sinon le français.
+    pub fn resolve(requested: Option<Locale>, headers: &HeaderMap) -> Locale {
+        requested
+            .or_else(|| {
+                let header = headers.get(header::ACCEPT_LANGUAGE)?.to_str().ok()?;
+                Locale::from_accept_language(header)
+            })
+            .unwrap_or_default()
+    }
+
+    /// Première langue gérée de l'en-tête, par ordre de préférence (`q`).
+    pub fn from_accept_language(header: &str) -> Option<Locale> {
+        let mut languages: Vec<(f32, &str)> = header
+            .split(',')
+            .filter_map(|language| {
+                let mut parts = language.split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts
+                    .find_map(|part| part.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |quality| quality.parse().ok())?;
+                Some((quality, tag))
+            })
+            .collect();
+        languages.sort_by(|a, b| b.0.total_cmp(&a.0));
+        languages.into_iter().find_map(|(_, tag)| {
+            match tag.split('-').next()?.to_ascii_lowercase().as_str() {
+                "fr" => Some(Locale::Fr),
+                "en" => Some(Locale::En),
+                _ => None,
+            }
+        })
+    }
+
+    pub fn month_name(self, month: u32) -> &'static str {
+        let months = match self {
+            Locale::Fr => &FRENCH_MONTHS,
+            Locale::En => &ENGLISH_MONTHS,
+        };
+        months[(month as usize - 1) % 12]
+    }
+
+    pub fn weekday_name(self, weekday: Weekday) -> &'static str {
+        let weekdays = match self {
+            Locale::Fr => &FRENCH_WEEKDAYS,
+            Locale::En => &ENGLISH_WEEKDAYS,
+        };
+        weekdays[weekday.num_days_from_monday() as usize]
+    }
+
+    /// Mois et année de `date`, par exemple « mars 2024 » ou « March 2024 ».
+    pub fn month_label(self, date: NaiveDate) -> String {
+        format!("{} {}", self.month_name(date.month()), date.year())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderMap;
+    use chrono::{NaiveDate, Weekday};
+
+    use super::Locale;
+
+    #[test]
+    fn translate_labels() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        assert_eq!(Locale::Fr.month_label(date), "janvier 2024");
+        assert_eq!(Locale::En.month_label(date), "January 2024");
+        assert_eq!(Locale::Fr.weekday_name(Weekday::Mon), "lundi");
+        assert_eq!(Locale::En.weekday_name(Weekday::Sun), "Sunday");
+    }
+
+    #[test]
+    fn follow_accept_language() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(Locale::resolve(None, &headers), Locale::Fr);
+
+        headers.insert(
+            "Accept-Language",
+            "de-DE, en-US;q=0.8, fr;q=0.5".parse().unwrap(),
+        );
+        assert_eq!(Locale::resolve(None, &headers), Locale::En);
+        assert_eq!(Locale::resolve(Some(Locale::Fr), &headers), Locale::Fr);
+        assert_eq!(Locale::from_accept_language("de, es"), None);
+    }
+}
@@ -1,18 +1,118 @@
+pub mod admin;
+pub mod auth;
+pub mod backup;
+pub mod calendar;
+pub mod chunking;
+pub mod community;
+pub mod compression;
+pub mod config;
+pub mod cors;
+pub mod crypto;
+pub mod custom_fields;
+pub mod debug;
+pub mod dev;
+pub mod entities;
+pub mod export;
+pub mod fallback;
+pub mod geo;
+pub mod goals;
+pub mod health;
+pub mod highlights;
+pub mod insights;
+pub mod jobs;
+pub mod limits;
+pub mod live_config;
+pub mod locale;
+pub mod maintenance;
+pub mod markdown;
+pub mod merge;
+pub mod msgpack;
+pub mod period;
+pub mod prompt;
+pub mod recap;
+pub mod reminders;
+pub mod revisions;
 pub mod routes;
+pub mod search;
+pub mod similar;
+pub mod stats;
+#[cfg(test)]
+mod testing;
+pub mod throttle;
+pub mod topics;
+pub mod transaction;
+pub mod undo;
+pub mod upload;
+pub mod usage;
+pub mod webhook;
+pub mod yearly_recap;
 
 use std::sync::Arc;
 
-use axum::{routing::get, Extension, Router};
-use color_eyre::eyre::Ok;
+use admin::{backfill_embeddings, dedupe_entries, normalize_dates};
+use async_openai::config::OpenAIConfig;
+use auth::{require_api_key, ApiKeyConfig};
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue},
+    middleware::{self, Next},
+    response::Response,
+    routing::{delete, get, patch, post, MethodRouter},
+    Extension, Router,
+};
+use backup::{get_backup, restore_backup};
+use calendar::get_calendar;
+use color_eyre::eyre::{eyre, Ok};
+use community::get_community_stats;
+use compression::compress_old_entries;
+use config::AppConfig;
+use crypto::EntryCipher;
+use custom_fields::get_custom_field_stats;
+use debug::{debug_parse, preview_prompt};
+use dev::cleanup_test_entries;
+use entities::{get_people, get_places};
+use export::{get_entries_csv, get_entries_ndjson};
+use geo::get_nearby_entries;
+use goals::{create_goal, get_goals_progress, Goal};
+use health::{detailed_health, openai_health};
+use highlights::get_entry_highlights;
+use insights::get_insights;
+use jobs::{create_journal_entry_async, get_job_status, JobStore};
+use limits::get_limits;
+use live_config::{get_config, update_config, with_current_config, LiveConfig};
+use maintenance::{reject_writes_when_read_only, set_read_only, ReadOnlyMode};
+use markdown::{get_journal_entry_html, get_journal_entry_markdown};
+use merge::merge_entries;
 use mongodb::{
     bson::doc,
     options::{ClientOptions, IndexOptions},
-    Client, IndexModel,
+    Client, Database, IndexModel,
+};
+use recap::{
+    create_monthly_recap, get_monthly_recap, get_weekly_summary, get_word_of_the_week, MonthlyRecap,
 };
-use routes::{create_journal_entry, delete_journal_entry, list_journal_entries, JournalEntry};
+use reminders::get_reminders;
+use revisions::{get_entry_history, EntryRevision};
+use routes::{
+    create_journal_entry, delete_journal_entry, delete_journal_entry_by_id,
+    get_journal_entry_by_id, get_latest_entry, journal_entry_exists, list_entry_dates,
+    list_grouped_entries, list_journal_entries, update_journal_entry, JournalEntry,
+};
+use search::search;
+use similar::get_similar_entries;
+use stats::{
+    get_consistency, get_rate_anomalies, get_rate_distribution, get_rolling_average,
+    get_seasonal_stats, get_topic_correlation, get_topic_ratings, get_writing_stats,
+};
+use throttle::OpenAiThrottle;
 use tokio::net::TcpListener;
-use tower_http::cors::{Any, CorsLayer};
-use tracing_subscriber::{fmt, layer::SubscriberExt, EnvFilter};
+use topics::{get_recurring_topics, get_topic_momentum};
+use tower_http::timeout::TimeoutLayer;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use undo::{undo_delete, DeletedEntries};
+use usage::{get_usage, UsageCounter};
+use webhook::{create_entry_from_webhook, WEBHOOK_ROUTE};
+use yearly_recap::{get_yearly_recap, YearlyRecap};
 
 #[tokio::main]
 async fn main() -> color_eyre::Result<()> {
@@ -22,26 +122,72 @@ async fn main() -> color_eyre::Result<()> {
     color_eyre::install()?;
     tracing_subscriber::registry()
         .with(fmt::layer())
-        .with(EnvFilter::from_default_env());
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .init();
 
-    let listener = TcpListener::bind(format!(
-        "{}:{}",
-        option_env!("SERVER_IP").unwrap_or("0.0.0.0"),
-        option_env!("SERVER_PORT").unwrap_or("3000")
-    ))
-    .await?;
+    let listener = bind_listener(&server_address()).await?;
+    tracing::info!("Listening on {}", listener.local_addr()?);
 
     axum::serve(listener, app().await?).await?;
     Ok(())
 }
 
+/// Adresse d'écoute, lue à l'exécution depuis `SERVER_IP` et `SERVER_PORT`.
+fn server_address() -> String {
+    format!(
+        "{}:{}",
+        std::env::var("SERVER_IP").unwrap_or_else(|_| "0.0.0.0".to_string()),
+        std::env::var("SERVER_PORT").unwrap_or_else(|_| "3000".to_string())
+    )
+}
+
+async fn bind_listener(address: &str) -> color_eyre::Result<TcpListener> {
+    TcpListener::bind(address).await.map_err(|error| {
+        if error.kind() == std::io::ErrorKind::AddrInUse {
+            eyre!("address {address} already in use, change SERVER_PORT")
+        } else {
+            eyre!("unable to listen on {address}: {error}")
+        }
+    })
+}
+
+/// Nom d'application annoncé à MongoDB quand `APP_NAME` n'est pas défini.
+const DEFAULT_APP_NAME: &str = "Journai";
+/// Base de donnée utilisée quand `MONGO_DB_NAME` n'est pas défini.
+const DEFAULT_DATABASE_NAME: &str = "journai";
+
+/// Options de connexion à MongoDB, annoncées sous le nom `app_name`.
+async fn mongo_client_options(uri: &str, app_name: &str) -> color_eyre::Result<ClientOptions> {
+    let mut options = ClientOptions::parse(uri).await?;
+    options.app_name = Some(app_name.to_string());
+    Ok(options)
+}
+
+/// Base de donnée nommée `name`, pour pouvoir isoler les tests.
+fn journai_database(client: &Client, name: &str) -> Database {
+    client.database(name)
+}
+
+/// Configuration OpenAI, pointant vers `api_base` pour un endpoint compatible
+/// (Azure OpenAI, LiteLLM, Ollama…) et vers OpenAI sinon.
+fn openai_config(api_base: Option<String>) -> OpenAIConfig {
+    match api_base {
+        Some(api_base) => OpenAIConfig::new().with_api_base(api_base),
+        None => OpenAIConfig::new(),
+    }
+}
+
 async fn app() -> color_eyre::Result<Router> {
+    let config = Arc::new(AppConfig::from_env()?);
+
     // Base de donnée
-    let mut options = ClientOptions::parse(std::env::var("MONGO")?).await?;
-    options.app_name = Some("Journai".to_string());
+    let app_name = std::env::var("APP_NAME").unwrap_or_else(|_| DEFAULT_APP_NAME.to_string());
+    let options = mongo_client_options(&std::env::var("MONGO")?, &app_name).await?;
     let mongo_client = Arc::new(Client::with_options(options)?);
-    let database = Arc::new(mongo_client.database("journai"));
-    let entries_collection = Arc::new(database.collection::<JournalEntry>("entries"));
+    let database_name =
+        std::env::var("MONGO_DB_NAME").unwrap_or_else(|_| DEFAULT_DATABASE_NAME.to_string());
+    let database = Arc::new(journai_database(&mongo_client, &database_name));
+    let entries_collection = database.collection::<JournalEntry>("entries");
     entries_collection
         .create_index(
             IndexModel::builder()
@@ -51,23 +197,176 @@ async fn app() -> color_eyre::Result<Router> {
             None,
         )
         .await?;
+    entries_collection
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "location": "2dsphere" })
+                .build(),
+            None,
+        )
+        .await?;
 
-    let cors = CorsLayer::new()
-        .allow_methods(Any)
-        .allow_origin(Any)
-        .allow_headers(Any);
+    // Client OpenAI partagé entre les requêtes
+    let openai_client = Arc::new(async_openai::Client::with_config(openai_config(
+        std::env::var("OPENAI_API_BASE").ok(),
+    )));
+
+    Ok(app_with(
+        &database,
+        openai_client,
+        Arc::new(EntryCipher::from_env()?),
+        Arc::new(ApiKeyConfig::from_env()),
+        config,
+    ))
+}
+
+/// Liste, création et suppression des entrées, servies à la racine.
+fn entries_route() -> MethodRouter {
+    get(list_journal_entries)
+        .post(create_journal_entry)
+        .delete(delete_journal_entry)
+}
 
-    let app = Router::new()
+/// Routes de l'API, servies sous `/v1`.
+fn api_routes() -> Router {
+    Router::new()
+        .route("/", entries_route())
+        .route("/stats/topic-ratings", get(get_topic_ratings))
+        .route("/stats/topic-correlation", get(get_topic_correlation))
+        .route("/stats/rolling", get(get_rolling_average))
+        .route("/stats/writing", get(get_writing_stats))
+        .route("/stats/consistency", get(get_consistency))
+        .route("/stats/distribution", get(get_rate_distribution))
+        .route("/stats/anomalies", get(get_rate_anomalies))
+        .route("/stats/seasonal", get(get_seasonal_stats))
+        .route("/stats/usage", get(get_usage))
+        .route("/stats/community", get(get_community_stats))
+        .route("/stats/custom/:field", get(get_custom_field_stats))
+        .route("/latest", get(get_latest_entry))
+        .route("/limits", get(get_limits))
+        .route("/dates", get(list_entry_dates))
+        .route("/search", get(search))
+        .route("/grouped", get(list_grouped_entries))
+        .route("/calendar.ics", get(get_calendar))
+        .route("/entries.ndjson", get(get_entries_ndjson))
+        .route("/export.csv", get(get_entries_csv))
+        .route("/backup", get(get_backup))
+        .route("/restore-backup", post(restore_backup))
+        .route("/admin/dedupe", post(dedupe_entries))
+        .route("/admin/read-only", post(set_read_only))
+        .route("/admin/config", get(get_config).post(update_config))
+        .route("/admin/backfill-embeddings", post(backfill_embeddings))
+        .route("/admin/compress-old", post(compress_old_entries))
+        .route("/admin/normalize-dates", post(normalize_dates))
+        .route("/merge", post(merge_entries))
+        .route("/goals", post(create_goal))
+        .route("/goals/progress", get(get_goals_progress))
+        .route("/undo-delete", post(undo_delete))
+        .route("/entry/:date/html", get(get_journal_entry_html))
+        .route("/entry/:date/markdown", get(get_journal_entry_markdown))
+        .route("/entry/:date", patch(update_journal_entry))
+        .route("/entry/:date/exists", get(journal_entry_exists))
+        .route("/entry/:date/history", get(get_entry_history))
+        .route("/entry/:date/highlights", get(get_entry_highlights))
         .route(
-            "/",
-            get(list_journal_entries)
-                .post(create_journal_entry)
-                .delete(delete_journal_entry),
+            "/entry/id/:id",
+            get(get_journal_entry_by_id).delete(delete_journal_entry_by_id),
         )
-        .layer(Extension(entries_collection))
-        .layer(cors);
+        .route("/weekly-summary", get(get_weekly_summary))
+        .route("/word-of-the-week", get(get_word_of_the_week))
+        .route("/insights", get(get_insights))
+        .route(
+            "/monthly-recap/:month",
+            get(get_monthly_recap).post(create_monthly_recap),
+        )
+        .route("/yearly-recap/:year", get(get_yearly_recap))
+        .route("/topics/recurring", get(get_recurring_topics))
+        .route("/topics/momentum", get(get_topic_momentum))
+        .route("/entities/people", get(get_people))
+        .route("/entities/places", get(get_places))
+        .route("/async", post(create_journal_entry_async))
+        .route(WEBHOOK_ROUTE, post(create_entry_from_webhook))
+        .route("/jobs/:id", get(get_job_status))
+        .route("/health/openai", get(openai_health))
+        .route("/health/detailed", get(detailed_health))
+        .route("/nearby", get(get_nearby_entries))
+        .route("/internal/reminders", get(get_reminders))
+        .route("/similar/:date", get(get_similar_entries))
+        .route("/debug/parse", post(debug_parse))
+        .route("/debug/preview-prompt", post(preview_prompt))
+        .route("/dev/cleanup", delete(cleanup_test_entries))
+}
 
-    Ok(app)
+/// Les routes sans préfixe de version restent servies le temps que les clients
+/// migrent vers `/v1`, mais signalent leur dépréciation.
+async fn deprecated_unversioned_route(request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+    tracing::warn!("Deprecated unversioned route {path} called, use /v1{path} instead");
+
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert("Deprecation", HeaderValue::from_static("true"));
+    if let Result::Ok(link) =
+        HeaderValue::from_str(&format!("</v1{path}>; rel=\"successor-version\""))
+    {
+        response.headers_mut().insert(header::LINK, link);
+    }
+    response
+}
+
+/// Construit le routeur à partir de ses dépendances, sans contacter la base :
+/// les tests peuvent y injecter leurs propres doubles.
+fn app_with(
+    database: &Database,
+    openai_client: Arc<async_openai::Client<OpenAIConfig>>,
+    cipher: Arc<EntryCipher>,
+    api_key: Arc<ApiKeyConfig>,
+    config: Arc<AppConfig>,
+) -> Router {
+    let read_only = ReadOnlyMode::new(config.read_only);
+    let live_config = LiveConfig::new(config.clone());
+    Router::new()
+        .nest("/v1", api_routes())
+        // `nest` ne sert la racine que sur `/v1`, sans la barre finale
+        .route("/v1/", entries_route())
+        .merge(api_routes().layer(middleware::from_fn(deprecated_unversioned_route)))
+        .layer(Extension(Arc::new(database.clone())))
+        .layer(Extension(Arc::new(
+            database.collection::<JournalEntry>("entries"),
+        )))
+        .layer(Extension(Arc::new(
+            database.collection::<MonthlyRecap>("monthly_recaps"),
+        )))
+        .layer(Extension(Arc::new(
+            database.collection::<YearlyRecap>("yearly_recaps"),
+        )))
+        .layer(Extension(Arc::new(database.collection::<Goal>("goals"))))
+        .layer(Extension(Arc::new(
+            database.collection::<EntryRevision>("entry_revisions"),
+        )))
+        .layer(Extension(openai_client))
+        .layer(Extension(cipher))
+        .layer(Extension(Arc::new(JobStore::default())))
+        .layer(Extension(Arc::new(DeletedEntries::default())))
+        .layer(Extension(Arc::new(UsageCounter::new(config.pricing))))
+        .layer(Extension(OpenAiThrottle::new(
+            config.openai_requests_per_minute,
+            config.openai_queue_timeout,
+        )))
+        .layer(Extension(read_only.clone()))
+        .layer(middleware::from_fn_with_state(
+            read_only,
+            reject_writes_when_read_only,
+        ))
+        .layer(Extension(live_config.clone()))
+        .layer(middleware::from_fn_with_state(
+            live_config,
+            with_current_config,
+        ))
+        .layer(TimeoutLayer::new(config.request_timeout))
+        .layer(middleware::from_fn_with_state(api_key, require_api_key))
+        .layer(config.cors_layer())
 }
 
 #[cfg(test)]
@@ -79,17 +378,60 @@ mod tests {
     use http_body_util::BodyExt; // for `collect`
     use tower::ServiceExt; // for `call`, `oneshot`, and `ready` // for `collect`
 
-    use crate::app;
+    use async_openai::config::Config;
+
+    use std::sync::Arc;
+
+    use crate::{
+        app_with, auth::ApiKeyConfig, bind_listener, config::AppConfig, crypto::EntryCipher,
+        journai_database, mongo_client_options, openai_config, DEFAULT_APP_NAME,
+    };
+
+    /// Routeur branché sur une base jamais contactée et un client OpenAI local :
+    /// seules les routes qui échouent avant tout accès externe peuvent être testées.
+    async fn offline_app() -> axum::Router {
+        let options = mongo_client_options("mongodb://localhost:27017", DEFAULT_APP_NAME)
+            .await
+            .unwrap();
+        let database = mongodb::Client::with_options(options)
+            .unwrap()
+            .database("journai_test");
+        let openai = async_openai::Client::with_config(openai_config(Some(
+            "http://localhost:0".to_string(),
+        )));
+        app_with(
+            &database,
+            Arc::new(openai),
+            Arc::new(EntryCipher::default()),
+            Arc::new(ApiKeyConfig::default()),
+            Arc::new(AppConfig::default()),
+        )
+    }
 
     #[tokio::test]
+    #[ignore = "requires a MongoDB server in MONGO"]
     async fn get_entries() {
-        dotenvy::dotenv().expect("Requires environment variables");
-        let app = app().await.expect("Unable to start router");
+        dotenvy::dotenv().ok();
+        let options = mongo_client_options(&std::env::var("MONGO").unwrap(), DEFAULT_APP_NAME)
+            .await
+            .unwrap();
+        let database = mongodb::Client::with_options(options)
+            .unwrap()
+            .database("journai_test");
+        let app = app_with(
+            &database,
+            Arc::new(async_openai::Client::with_config(openai_config(Some(
+                "http://localhost:0".to_string(),
+            )))),
+            Arc::new(EntryCipher::default()),
+            Arc::new(ApiKeyConfig::default()),
+            Arc::new(AppConfig::default()),
+        );
 
         // `Router` implements `tower::Service<Request<Body>>` so we can
         // call it like any tower service, no need to run an HTTP server.
         let response = app
-            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .oneshot(Request::builder().uri("/v1/").body(Body::empty()).unwrap())
             .await
             .unwrap();
 
@@ -107,4 +449,114 @@ mod tests {
         .expect("We expect to have a body");
         assert!(!body.is_empty())
     }
+
+    #[tokio::test]
+    async fn use_configured_database() {
+        let options = mongo_client_options("mongodb://localhost:27017", "Journai tests")
+            .await
+            .unwrap();
+        assert_eq!(options.app_name.as_deref(), Some("Journai tests"));
+
+        let client = mongodb::Client::with_options(options).unwrap();
+        assert_eq!(
+            journai_database(&client, "journai_test").name(),
+            "journai_test"
+        );
+    }
+
+    #[test]
+    fn use_configured_openai_base() {
+        let config = openai_config(Some("http://localhost:4000/v1".to_string()));
+        assert_eq!(config.api_base(), "http://localhost:4000/v1");
+
+        assert_eq!(openai_config(None).api_base(), "https://api.openai.com/v1");
+    }
+
+    #[tokio::test]
+    async fn injected_dependencies() {
+        let response = offline_app()
+            .await
+            .oneshot(
+                Request::builder()
+                    .uri("/v1/jobs/unknown")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = offline_app()
+            .await
+            .oneshot(
+                Request::builder()
+                    .uri("/v1/stats/rolling?window=0")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = offline_app()
+            .await
+            .oneshot(
+                Request::builder()
+                    .uri("/v1/stats/rolling?window=1000000000")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn versioned_routes() {
+        let response = offline_app()
+            .await
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/v1/")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from("{}"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(response.headers().get("Deprecation").is_none());
+
+        let response = offline_app()
+            .await
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from("{}"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(response.headers()["Deprecation"], "true");
+        assert_eq!(
+            response.headers()["Link"],
+            "</v1/>; rel=\"successor-version\""
+        );
+    }
+
+    #[tokio::test]
+    async fn explain_port_in_use() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        let error = bind_listener(&address).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("address {address} already in use, change SERVER_PORT")
+        );
+    }
 }
@@ -0,0 +1,145 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use axum::{
+    extract::{Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+
+/// Route qui reste accessible en lecture seule, pour pouvoir en sortir.
+pub const READ_ONLY_ROUTE: &str = "/admin/read-only";
+
+/// Mode maintenance : les écritures sont refusées pendant une migration.
+#[derive(Debug, Default, Clone)]
+pub struct ReadOnlyMode {
+    enabled: Arc<AtomicBool>,
+}
+
+impl ReadOnlyMode {
+    pub fn new(enabled: bool) -> Self {
+        let mode = ReadOnlyMode::default();
+        mode.set(enabled);
+        mode
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    fn rejects(&self, request: &Request) -> bool {
+        self.is_enabled()
+            && !matches!(
+                *request.method(),
+                Method::GET | Method::HEAD | Method::OPTIONS
+            )
+            && !request.uri().path().ends_with(READ_ONLY_ROUTE)
+    }
+}
+
+pub async fn reject_writes_when_read_only(
+    State(mode): State<ReadOnlyMode>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if mode.rejects(&request) {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "the API is in read-only mode for maintenance, try again later",
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ReadOnlyStatus {
+    pub enabled: bool,
+}
+
+pub async fn set_read_only(
+    Extension(mode): Extension<ReadOnlyMode>,
+    Json(status): Json<ReadOnlyStatus>,
+) -> Json<ReadOnlyStatus> {
+    mode.set(status.enabled);
+    tracing::warn!(
+        "Read-only mode {}",
+        if status.enabled {
+            "enabled"
+        } else {
+            "disabled"
+        }
+    );
+    Json(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+        middleware,
+        routing::{get, post},
+        Extension, Router,
+    };
+    use tower::ServiceExt;
+
+    use super::{reject_writes_when_read_only, set_read_only, ReadOnlyMode};
+
+    async fn status(app: &Router, method: &str, uri: &str, body: &str) -> StatusCode {
+        app.clone()
+            .oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn block_writes_in_read_only_mode() {
+        let mode = ReadOnlyMode::default();
+        let app = Router::new()
+            .route("/", get(|| async {}).post(|| async {}).delete(|| async {}))
+            .route("/admin/read-only", post(set_read_only))
+            .layer(Extension(mode.clone()))
+            .layer(middleware::from_fn_with_state(
+                mode.clone(),
+                reject_writes_when_read_only,
+            ));
+
+        assert_eq!(status(&app, "POST", "/", "").await, StatusCode::OK);
+
+        mode.set(true);
+        assert_eq!(
+            status(&app, "POST", "/", "").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            status(&app, "DELETE", "/", "").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(status(&app, "GET", "/", "").await, StatusCode::OK);
+
+        assert_eq!(
+            status(&app, "POST", "/admin/read-only", r#"{"enabled":false}"#).await,
+            StatusCode::OK
+        );
+        assert!(!mode.is_enabled());
+        assert_eq!(status(&app, "POST", "/", "").await, StatusCode::OK);
+    }
+}
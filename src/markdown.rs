@@ -0,0 +1,146 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{Html, IntoResponse},
+    Extension,
+};
+use axum_thiserror::ErrorStatus;
+use chrono::NaiveDate;
+use mongodb::Collection;
+use pulldown_cmark::{html, Parser};
+use thiserror::Error;
+
+use crate::routes::{date_filter, JournalEntry};
+
+/// Convertit un résumé Markdown en HTML assaini : le HTML brut éventuellement
+/// présent dans le Markdown ne peut pas injecter de script.
+pub fn render_summary_html(markdown: &str) -> String {
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, Parser::new(markdown));
+    ammonia::clean(&unsafe_html)
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum JournalEntryHtmlError {
+    #[error("no journal entry for {0}")]
+    #[status(StatusCode::NOT_FOUND)]
+    NotFound(NaiveDate),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+pub async fn get_journal_entry_html(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Path(date): Path<NaiveDate>,
+) -> Result<Html<String>, JournalEntryHtmlError> {
+    let entry = mongo_entries
+        .find_one(date_filter(date), None)
+        .await
+        .map_err(JournalEntryHtmlError::Mongo)?
+        .ok_or(JournalEntryHtmlError::NotFound(date))?;
+
+    Ok(Html(render_summary_html(&entry.short_summary)))
+}
+
+/// Note sur cinq étoiles (une étoile pour deux points, arrondie), suivie de la note exacte.
+pub fn rate_stars(rate: f32) -> String {
+    let stars = ((rate / 2.0).round() as usize).min(5);
+    format!("{}{} ({rate}/10)", "★".repeat(stars), "☆".repeat(5 - stars))
+}
+
+/// Entrée au format Markdown, à coller dans un outil de notes (Obsidian, Notion…).
+pub fn entry_markdown(entry: &JournalEntry) -> String {
+    let mut markdown = format!(
+        "# Journal du {}\n\n**Note :** {}\n",
+        entry.date,
+        rate_stars(entry.rate)
+    );
+    if !entry.tags.is_empty() {
+        markdown.push_str("\n**Topics :**\n\n");
+        for tag in &entry.tags {
+            markdown.push_str(&format!("- {tag}\n"));
+        }
+    }
+    markdown.push_str(&format!("\n{}\n", entry.short_summary.trim()));
+    markdown
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum JournalEntryMarkdownError {
+    #[error("no journal entry for {0}")]
+    #[status(StatusCode::NOT_FOUND)]
+    NotFound(NaiveDate),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+pub async fn get_journal_entry_markdown(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Path(date): Path<NaiveDate>,
+) -> Result<impl IntoResponse, JournalEntryMarkdownError> {
+    let entry = mongo_entries
+        .find_one(date_filter(date), None)
+        .await
+        .map_err(JournalEntryMarkdownError::Mongo)?
+        .ok_or(JournalEntryMarkdownError::NotFound(date))?;
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/markdown; charset=utf-8")],
+        entry_markdown(&entry),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::routes::JournalEntry;
+
+    use super::{entry_markdown, rate_stars, render_summary_html};
+
+    #[test]
+    fn render_markdown_summary() {
+        let html = render_summary_html("J'ai **enfin** fini :\n\n- le projet\n- le rapport");
+        assert!(html.contains("<strong>enfin</strong>"));
+        assert!(html.contains("<li>le projet</li>"));
+    }
+
+    #[test]
+    fn strip_dangerous_html() {
+        let html = render_summary_html(
+            "Salut <script>alert('xss')</script> <img src=x onerror=\"alert(1)\"> [lien](javascript:alert(1))",
+        );
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("onerror"));
+        assert!(!html.contains("javascript:"));
+    }
+
+    #[test]
+    fn format_entry_as_markdown() {
+        let entry = JournalEntry {
+            date: NaiveDate::from_ymd_opt(2024, 3, 14).unwrap(),
+            rate: 7.5,
+            short_summary: "Journée de révisions, puis un footing au parc. ".to_string(),
+            tags: vec!["travail".to_string(), "sport".to_string()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            entry_markdown(&entry),
+            "# Journal du 2024-03-14\n\n**Note :** ★★★★☆ (7.5/10)\n\n**Topics :**\n\n- travail\n- sport\n\nJournée de révisions, puis un footing au parc.\n"
+        );
+
+        let untagged = JournalEntry {
+            tags: vec![],
+            ..entry
+        };
+        assert!(!entry_markdown(&untagged).contains("Topics"));
+
+        assert_eq!(rate_stars(0.0), "☆☆☆☆☆ (0/10)");
+        assert_eq!(rate_stars(10.0), "★★★★★ (10/10)");
+    }
+}
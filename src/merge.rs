@@ -0,0 +1,282 @@
+use std::sync::Arc;
+
+use async_openai::{config::OpenAIConfig, Client};
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::NaiveDate;
+use mongodb::{options::ReplaceOptions, ClientSession, Collection};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::config::AppConfig;
+use crate::crypto::{CryptoError, EntryCipher};
+use crate::revisions::{insert_revision, prune_revisions, EntryRevision};
+use crate::routes::{
+    date_filter, prepare_journal_entry, CreateJournalEntry, CreateJournalEntryError, JournalEntry,
+};
+use crate::throttle::OpenAiThrottle;
+use crate::transaction::in_transaction;
+use crate::usage::UsageCounter;
+
+#[derive(Error, Debug)]
+pub enum MergeError {
+    #[error("cannot merge the entry of {0} into itself")]
+    SameDate(NaiveDate),
+    #[error("no journal entry for {0}")]
+    NotFound(NaiveDate),
+    #[error(transparent)]
+    Analysis(CreateJournalEntryError),
+    #[error(transparent)]
+    Crypto(CryptoError),
+    #[error(transparent)]
+    Mongo(mongodb::error::Error),
+}
+
+impl IntoResponse for MergeError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            MergeError::SameDate(_) => StatusCode::BAD_REQUEST,
+            MergeError::NotFound(_) => StatusCode::NOT_FOUND,
+            // Texte fusionné trop long, file saturée… : le statut de l'analyse est conservé
+            MergeError::Analysis(error) => return error.into_response(),
+            MergeError::Crypto(_) | MergeError::Mongo(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MergeEntries {
+    pub from: NaiveDate,
+    pub into: NaiveDate,
+    #[serde(default)]
+    pub name: String,
+}
+
+/// Texte brut de l'entrée, ou son résumé si elle a été créée avant qu'il ne soit conservé.
+fn text(entry: &JournalEntry) -> &str {
+    entry.raw_text.as_deref().unwrap_or(&entry.short_summary)
+}
+
+/// Entrée à réanalyser : les textes bruts des deux entrées dans l'ordre
+/// chronologique, à la date de `into`.
+pub fn merged_entry(name: &str, from: &JournalEntry, into: &JournalEntry) -> CreateJournalEntry {
+    let (first, second) = if from.date < into.date {
+        (from, into)
+    } else {
+        (into, from)
+    };
+    CreateJournalEntry {
+        name: name.to_string(),
+        summary: format!("{}\n\n{}", text(first), text(second)),
+        date: into.date,
+        style_hint: into.style_hint.clone(),
+        timezone: Some(into.timezone.clone()).filter(|timezone| !timezone.is_empty()),
+        custom_fields: from
+            .custom_fields
+            .clone()
+            .into_iter()
+            .chain(into.custom_fields.clone())
+            .collect(),
+        location: into.location.clone().or_else(|| from.location.clone()),
+        ..Default::default()
+    }
+}
+
+async fn write_merge(
+    mongo_entries: &Collection<JournalEntry>,
+    revisions: &Collection<EntryRevision>,
+    session: Option<&mut ClientSession>,
+    merged: &JournalEntry,
+    from: NaiveDate,
+    revision: &EntryRevision,
+) -> mongodb::error::Result<()> {
+    let options = ReplaceOptions::builder().upsert(true).build();
+    match session {
+        Some(session) => {
+            mongo_entries
+                .replace_one_with_session(date_filter(merged.date), merged, options, session)
+                .await?;
+            mongo_entries
+                .delete_one_with_session(date_filter(from), None, session)
+                .await?;
+            insert_revision(revisions, Some(session), revision).await
+        }
+        None => {
+            mongo_entries
+                .replace_one(date_filter(merged.date), merged, options)
+                .await?;
+            mongo_entries.delete_one(date_filter(from), None).await?;
+            insert_revision(revisions, None, revision).await
+        }
+    }
+}
+
+/// Reprend de `into`, ou à défaut de `from`, ce que l'analyse ne produit pas :
+/// l'identifiant, la date de création et la photo.
+fn keep_stored_fields(
+    mut merged: JournalEntry,
+    from: &JournalEntry,
+    into: &JournalEntry,
+) -> JournalEntry {
+    merged.id = into.id.clone();
+    merged.created_at = into.created_at.or(from.created_at);
+    merged.image_url = into.image_url.clone().or_else(|| from.image_url.clone());
+    merged
+}
+
+/// Remplace `into`, supprime `from` et archive l'ancienne version de `into`
+/// dans une même transaction.
+async fn save_merge(
+    mongo_entries: &Collection<JournalEntry>,
+    revisions: &Collection<EntryRevision>,
+    merged: &JournalEntry,
+    from: NaiveDate,
+    previous: JournalEntry,
+) -> mongodb::error::Result<()> {
+    let revision = EntryRevision::of(previous);
+    in_transaction(mongo_entries.client(), "the merge", |session| {
+        let (mongo_entries, revisions) = (mongo_entries.clone(), revisions.clone());
+        let (merged, revision) = (merged.clone(), revision.clone());
+        Box::pin(async move {
+            write_merge(
+                &mongo_entries,
+                &revisions,
+                session,
+                &merged,
+                from,
+                &revision,
+            )
+            .await
+        })
+    })
+    .await?;
+    prune_revisions(revisions, revision.date).await;
+    Ok(())
+}
+
+/// Fusionne l'entrée `from` dans `into` : les textes sont combinés et réanalysés.
+#[allow(clippy::too_many_arguments)]
+pub async fn merge_entries(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(revisions): Extension<Arc<Collection<EntryRevision>>>,
+    Extension(openai): Extension<Arc<Client<OpenAIConfig>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(cipher): Extension<Arc<EntryCipher>>,
+    Extension(usage): Extension<Arc<UsageCounter>>,
+    throttle: Option<Extension<Arc<OpenAiThrottle>>>,
+    Json(request): Json<MergeEntries>,
+) -> Result<Json<JournalEntry>, MergeError> {
+    if request.from == request.into {
+        return Err(MergeError::SameDate(request.from));
+    }
+
+    let mut entries = Vec::with_capacity(2);
+    for date in [request.from, request.into] {
+        entries.push(
+            mongo_entries
+                .find_one(date_filter(date), None)
+                .await
+                .map_err(MergeError::Mongo)?
+                .ok_or(MergeError::NotFound(date))?
+                .decrypted(&cipher)
+                .map_err(MergeError::Crypto)?,
+        );
+    }
+
+    let merged = prepare_journal_entry(
+        &openai,
+        throttle.as_deref().map(Arc::as_ref),
+        &config,
+        merged_entry(&request.name, &entries[0], &entries[1]),
+        false,
+    )
+    .await
+    .map_err(MergeError::Analysis)?;
+    let merged = keep_stored_fields(merged, &entries[0], &entries[1]);
+    usage.record(merged.tokens);
+    save_merge(
+        &mongo_entries,
+        &revisions,
+        &merged.encrypted(&cipher),
+        request.from,
+        entries[1].encrypted(&cipher),
+    )
+    .await
+    .map_err(MergeError::Mongo)?;
+
+    Ok(Json(merged))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{http::StatusCode, response::IntoResponse};
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    use crate::{
+        config::AppConfig,
+        routes::{prepare_journal_entry, CreateJournalEntryError, JournalEntry},
+        testing::mock_chat_completion,
+    };
+
+    use super::{keep_stored_fields, merged_entry, MergeError};
+
+    fn entry(day: u32, summary: &str) -> JournalEntry {
+        JournalEntry {
+            date: NaiveDate::from_ymd_opt(2024, 3, day).unwrap(),
+            rate: 5.0,
+            short_summary: summary.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn merge_two_entries() {
+        let from = JournalEntry {
+            raw_text: Some("Le soir, cinéma avec Léa.".to_string()),
+            image_url: Some("https://example.com/cinema.jpg".to_string()),
+            ..entry(5, "Cinéma")
+        };
+        let into = JournalEntry {
+            id: "65b0f2a1c3d4e5f607182930".to_string(),
+            created_at: Some(Utc.with_ymd_and_hms(2024, 3, 4, 9, 0, 0).unwrap()),
+            ..entry(4, "Matinée de révisions.")
+        };
+
+        let merged = merged_entry("Alice", &from, &into);
+        assert_eq!(merged.date, into.date);
+        assert_eq!(
+            merged.summary,
+            "Matinée de révisions.\n\nLe soir, cinéma avec Léa."
+        );
+
+        let openai = mock_chat_completion(
+            r#"{"date":"2024-03-05","rate":7.0,"short_summary":"Révisions puis cinéma","tags":["études"]}"#,
+        )
+        .await;
+        let analyzed = prepare_journal_entry(&openai, None, &AppConfig::default(), merged, false)
+            .await
+            .unwrap();
+        assert_eq!(analyzed.date, into.date);
+        assert_eq!(analyzed.short_summary, "Révisions puis cinéma");
+        assert_eq!(analyzed.word_count, Some(8));
+
+        // Sans photo, `into` reprend celle de `from`
+        let stored = keep_stored_fields(analyzed, &from, &into);
+        assert_eq!(stored.id, into.id);
+        assert_eq!(stored.created_at, into.created_at);
+        assert_eq!(stored.image_url, from.image_url);
+    }
+
+    #[test]
+    fn keep_the_status_of_the_analysis() {
+        let too_long = MergeError::Analysis(CreateJournalEntryError::SummaryTooLong(5000));
+        assert_eq!(too_long.into_response().status(), StatusCode::BAD_REQUEST);
+
+        let same_date = MergeError::SameDate(NaiveDate::from_ymd_opt(2024, 3, 4).unwrap());
+        assert_eq!(same_date.into_response().status(), StatusCode::BAD_REQUEST);
+    }
+}
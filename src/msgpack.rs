@@ -0,0 +1,166 @@
+use std::convert::Infallible;
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+pub const MSGPACK_CONTENT_TYPE: &str = "application/msgpack";
+
+/// Format de réponse négocié via `Accept` : MessagePack pour les clients qui le
+/// demandent (listes plus légères sur mobile), JSON par défaut.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ResponseFormat {
+    #[default]
+    Json,
+    MessagePack,
+}
+
+impl ResponseFormat {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let accepts_msgpack = headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|media_type| media_type.split(';').next())
+            .any(|media_type| {
+                matches!(
+                    media_type.trim(),
+                    MSGPACK_CONTENT_TYPE | "application/x-msgpack"
+                )
+            });
+        if accepts_msgpack {
+            ResponseFormat::MessagePack
+        } else {
+            ResponseFormat::Json
+        }
+    }
+
+    pub fn respond<T: Serialize>(self, value: T) -> Negotiated<T> {
+        Negotiated {
+            format: self,
+            value,
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ResponseFormat {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(ResponseFormat::from_headers(&parts.headers))
+    }
+}
+
+/// Réponse sérialisée dans le format négocié.
+#[derive(Debug)]
+pub struct Negotiated<T> {
+    pub format: ResponseFormat,
+    pub value: T,
+}
+
+/// Encode `value` en MessagePack avec les noms des champs, comme le JSON.
+pub fn to_msgpack<T: Serialize>(value: &T) -> Result<Vec<u8>, rmp_serde::encode::Error> {
+    let mut bytes = Vec::new();
+    // Les identifiants et les dates sont écrits en chaînes, comme en JSON
+    value.serialize(
+        &mut rmp_serde::Serializer::new(&mut bytes)
+            .with_struct_map()
+            .with_human_readable(),
+    )?;
+    Ok(bytes)
+}
+
+impl<T: Serialize> IntoResponse for Negotiated<T> {
+    fn into_response(self) -> Response {
+        match self.format {
+            ResponseFormat::Json => Json(self.value).into_response(),
+            ResponseFormat::MessagePack => match to_msgpack(&self.value) {
+                Ok(bytes) => (
+                    [(
+                        header::CONTENT_TYPE,
+                        HeaderValue::from_static(MSGPACK_CONTENT_TYPE),
+                    )],
+                    bytes,
+                )
+                    .into_response(),
+                Err(error) => {
+                    (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()).into_response()
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        http::{header, HeaderMap, HeaderValue},
+        response::IntoResponse,
+    };
+    use chrono::NaiveDate;
+    use http_body_util::BodyExt;
+    use mongodb::bson::oid::ObjectId;
+
+    use crate::routes::{EntriesPage, JournalEntry, Page};
+
+    use super::{ResponseFormat, MSGPACK_CONTENT_TYPE};
+
+    fn accept(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn negotiate_format() {
+        assert_eq!(
+            ResponseFormat::from_headers(&HeaderMap::new()),
+            ResponseFormat::Json
+        );
+        assert_eq!(
+            ResponseFormat::from_headers(&accept("application/json, */*;q=0.8")),
+            ResponseFormat::Json
+        );
+        assert_eq!(
+            ResponseFormat::from_headers(&accept("application/msgpack;q=1, application/json")),
+            ResponseFormat::MessagePack
+        );
+    }
+
+    #[tokio::test]
+    async fn respond_with_msgpack() {
+        let page: EntriesPage = Page::new(
+            vec![JournalEntry {
+                id: ObjectId::new().to_hex(),
+                date: NaiveDate::from_ymd_opt(2024, 1, 24).unwrap(),
+                rate: 7.0,
+                short_summary: "Belle course".to_string(),
+                tags: vec!["sport".to_string()],
+                ..Default::default()
+            }],
+            1,
+            Some(20),
+            0,
+        )
+        .into();
+
+        let response = ResponseFormat::from_headers(&accept(MSGPACK_CONTENT_TYPE))
+            .respond(&page)
+            .into_response();
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            MSGPACK_CONTENT_TYPE
+        );
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let decoded: EntriesPage = rmp_serde::from_slice(&body).unwrap();
+        assert_eq!(decoded, page);
+    }
+}
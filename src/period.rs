@@ -0,0 +1,154 @@
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+
+/// Période relative à aujourd'hui, traduite en plage de dates côté serveur
+/// pour que le frontend n'ait pas à calculer les bornes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RelativePeriod {
+    Today,
+    Yesterday,
+    ThisWeek,
+    LastWeek,
+    ThisMonth,
+    LastMonth,
+    ThisYear,
+    LastYear,
+}
+
+impl RelativePeriod {
+    pub fn parse(period: &str) -> Option<Self> {
+        Some(match period {
+            "today" => RelativePeriod::Today,
+            "yesterday" => RelativePeriod::Yesterday,
+            "this-week" => RelativePeriod::ThisWeek,
+            "last-week" => RelativePeriod::LastWeek,
+            "this-month" => RelativePeriod::ThisMonth,
+            "last-month" => RelativePeriod::LastMonth,
+            "this-year" => RelativePeriod::ThisYear,
+            "last-year" => RelativePeriod::LastYear,
+            _ => return None,
+        })
+    }
+
+    /// Premier et dernier jours de la période, inclus. Les semaines commencent le lundi.
+    pub fn range(self, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+        let week = |day: NaiveDate| {
+            let week = day.week(Weekday::Mon);
+            (week.first_day(), week.last_day())
+        };
+        let month = |day: NaiveDate| {
+            let first = day.with_day(1).unwrap();
+            (first, first + Months::new(1) - Days::new(1))
+        };
+        let year = |year: i32| {
+            (
+                NaiveDate::from_ymd_opt(year, 1, 1).unwrap(),
+                NaiveDate::from_ymd_opt(year, 12, 31).unwrap(),
+            )
+        };
+
+        match self {
+            RelativePeriod::Today => (today, today),
+            RelativePeriod::Yesterday => {
+                let yesterday = today.pred_opt().unwrap();
+                (yesterday, yesterday)
+            }
+            RelativePeriod::ThisWeek => week(today),
+            RelativePeriod::LastWeek => week(today - Days::new(7)),
+            RelativePeriod::ThisMonth => month(today),
+            RelativePeriod::LastMonth => month(today - Months::new(1)),
+            RelativePeriod::ThisYear => year(today.year()),
+            RelativePeriod::LastYear => year(today.year() - 1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::RelativePeriod;
+
+    fn day(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn range(period: &str, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+        RelativePeriod::parse(period).unwrap().range(today)
+    }
+
+    #[test]
+    fn parse_periods() {
+        assert_eq!(
+            RelativePeriod::parse("last-week"),
+            Some(RelativePeriod::LastWeek)
+        );
+        assert_eq!(RelativePeriod::parse("last_week"), None);
+        assert_eq!(RelativePeriod::parse("demain"), None);
+    }
+
+    #[test]
+    fn today_and_yesterday() {
+        assert_eq!(
+            range("today", day(2024, 3, 14)),
+            (day(2024, 3, 14), day(2024, 3, 14))
+        );
+        assert_eq!(
+            range("yesterday", day(2024, 3, 1)),
+            (day(2024, 2, 29), day(2024, 2, 29))
+        );
+        assert_eq!(
+            range("yesterday", day(2024, 1, 1)),
+            (day(2023, 12, 31), day(2023, 12, 31))
+        );
+    }
+
+    #[test]
+    fn weeks() {
+        // Le jeudi 14 mars 2024
+        assert_eq!(
+            range("this-week", day(2024, 3, 14)),
+            (day(2024, 3, 11), day(2024, 3, 17))
+        );
+        assert_eq!(
+            range("last-week", day(2024, 3, 14)),
+            (day(2024, 3, 4), day(2024, 3, 10))
+        );
+        // Le mercredi 3 janvier 2024 : la semaine précédente chevauche l'année
+        assert_eq!(
+            range("last-week", day(2024, 1, 3)),
+            (day(2023, 12, 25), day(2023, 12, 31))
+        );
+        assert_eq!(
+            range("this-week", day(2024, 3, 17)),
+            (day(2024, 3, 11), day(2024, 3, 17))
+        );
+    }
+
+    #[test]
+    fn months() {
+        assert_eq!(
+            range("this-month", day(2024, 2, 10)),
+            (day(2024, 2, 1), day(2024, 2, 29))
+        );
+        assert_eq!(
+            range("last-month", day(2024, 3, 31)),
+            (day(2024, 2, 1), day(2024, 2, 29))
+        );
+        assert_eq!(
+            range("last-month", day(2024, 1, 15)),
+            (day(2023, 12, 1), day(2023, 12, 31))
+        );
+    }
+
+    #[test]
+    fn years() {
+        assert_eq!(
+            range("this-year", day(2024, 3, 14)),
+            (day(2024, 1, 1), day(2024, 12, 31))
+        );
+        assert_eq!(
+            range("last-year", day(2024, 3, 14)),
+            (day(2023, 1, 1), day(2023, 12, 31))
+        );
+    }
+}
@@ -0,0 +1,725 @@
+use std::sync::Arc;
+
+use async_openai::{
+    config::OpenAIConfig,
+    error::OpenAIError,
+    types::{
+        ChatCompletionRequestMessage, ChatCompletionRequestSystemMessageArgs,
+        ChatCompletionRequestUserMessageArgs, CreateChatCompletionRequestArgs,
+    },
+    Client,
+};
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    Extension, Json,
+};
+use axum_thiserror::ErrorStatus;
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, Utc, Weekday};
+use futures_util::TryStreamExt;
+use mongodb::{
+    bson::{doc, Document},
+    options::{FindOptions, ReplaceOptions},
+    Collection,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config::AppConfig;
+use crate::routes::JournalEntry;
+use crate::stats::{rated_filter, unrated_filter, RatedStats};
+use crate::throttle::{take_turn, OpenAiThrottle, ThrottleError};
+use crate::usage::{TokenUsage, UsageCounter};
+
+/// Renvoie le lundi et le dimanche d'une semaine ISO au format `2024-W10`.
+pub fn parse_iso_week(week: &str) -> Option<(NaiveDate, NaiveDate)> {
+    let (year, week) = week.split_once("-W")?;
+    let monday = NaiveDate::from_isoywd_opt(year.parse().ok()?, week.parse().ok()?, Weekday::Mon)?;
+    Some((monday, monday.checked_add_days(Days::new(6))?))
+}
+
+/// Renvoie le premier et le dernier jour d'un mois au format `2024-03`.
+pub fn parse_month(month: &str) -> Option<(NaiveDate, NaiveDate)> {
+    let first = NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d").ok()?;
+    Some((first, first.checked_add_months(Months::new(1))?.pred_opt()?))
+}
+
+/// Filtre des entrées stockées entre `first` et `last` inclus.
+pub fn stored_period_filter(first: NaiveDate, last: NaiveDate) -> Document {
+    doc! { "date": { "$gte": first.to_string(), "$lte": last.to_string() } }
+}
+
+/// Entrées notées de la période triées par date, avec le nombre d'entrées de la
+/// période écartées faute de note (brouillons non analysés).
+pub async fn rated_period_entries(
+    mongo_entries: &Collection<JournalEntry>,
+    first: NaiveDate,
+    last: NaiveDate,
+) -> Result<RatedStats<Vec<JournalEntry>>, mongodb::error::Error> {
+    let period = stored_period_filter(first, last);
+    let mut rated = period.clone();
+    rated.extend(rated_filter());
+    let mut unrated = period;
+    unrated.extend(unrated_filter());
+
+    Ok(RatedStats {
+        stats: mongo_entries
+            .find(
+                rated,
+                FindOptions::builder().sort(doc! { "date": 1 }).build(),
+            )
+            .await?
+            .try_collect()
+            .await?,
+        excluded_count: mongo_entries.count_documents(unrated, None).await?,
+    })
+}
+
+/// Message utilisateur listant les résumés quotidiens, un par ligne.
+pub fn daily_summaries_message(entries: &[JournalEntry]) -> String {
+    entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "{} ({}/10): {}",
+                entry.date, entry.rate, entry.short_summary
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WeeklySummaryQuery {
+    pub week: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WeeklySummary {
+    pub week: String,
+    pub summary: String,
+    pub entries_count: u32,
+    /// Entrées de la semaine ignorées faute de note.
+    pub excluded_count: u64,
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum WeeklySummaryError {
+    #[error("invalid week \"{0}\", expected a week like 2024-W10")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidWeek(String),
+    #[error(transparent)]
+    #[status(StatusCode::TOO_MANY_REQUESTS)]
+    Throttle(ThrottleError),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    OpenAI(OpenAIError),
+    #[error("no output from GPT-4")]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    NoOutput,
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+pub async fn get_weekly_summary(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(openai): Extension<Arc<Client<OpenAIConfig>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(usage): Extension<Arc<UsageCounter>>,
+    Query(query): Query<WeeklySummaryQuery>,
+    throttle: Option<Extension<Arc<OpenAiThrottle>>>,
+) -> Result<Json<WeeklySummary>, WeeklySummaryError> {
+    let (monday, sunday) = parse_iso_week(&query.week)
+        .ok_or_else(|| WeeklySummaryError::InvalidWeek(query.week.clone()))?;
+
+    let RatedStats {
+        stats: entries,
+        excluded_count,
+    } = rated_period_entries(&mongo_entries, monday, sunday)
+        .await
+        .map_err(WeeklySummaryError::Mongo)?;
+
+    if entries.is_empty() {
+        return Ok(Json(WeeklySummary {
+            week: query.week,
+            summary: "Aucune entrée n'a été écrite cette semaine.".to_string(),
+            entries_count: 0,
+            excluded_count,
+        }));
+    }
+
+    let completion_request = CreateChatCompletionRequestArgs::default()
+        .model(&config.chat_model)
+        .messages(vec![
+            ChatCompletionRequestMessage::System(
+                ChatCompletionRequestSystemMessageArgs::default()
+                    .content(include_str!("./weekly_summary_message.txt"))
+                    .build()
+                    .map_err(WeeklySummaryError::OpenAI)?,
+            ),
+            ChatCompletionRequestMessage::User(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content(daily_summaries_message(&entries))
+                    .build()
+                    .map_err(WeeklySummaryError::OpenAI)?,
+            ),
+        ])
+        .n(1)
+        .build()
+        .map_err(WeeklySummaryError::OpenAI)?;
+
+    take_turn(throttle.as_deref().map(Arc::as_ref))
+        .await
+        .map_err(WeeklySummaryError::Throttle)?;
+    let response = openai
+        .chat()
+        .create(completion_request)
+        .await
+        .map_err(WeeklySummaryError::OpenAI)?;
+    usage.record(response.usage.as_ref().map(TokenUsage::from));
+    let summary = response
+        .choices
+        .first()
+        .and_then(|o| o.message.clone().content)
+        // Un contenu vide est une absence de réponse, pas une réponse à interpréter
+        .filter(|content| !content.trim().is_empty())
+        .ok_or(WeeklySummaryError::NoOutput)?;
+
+    Ok(Json(WeeklySummary {
+        week: query.week,
+        summary,
+        entries_count: entries.len() as u32,
+        excluded_count,
+    }))
+}
+
+/// Semaine ISO précédant `today`, au format `2024-W10`.
+pub fn previous_iso_week(today: NaiveDate) -> String {
+    let week = (today - Days::new(7)).iso_week();
+    format!("{}-W{:02}", week.year(), week.week())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WordOfTheWeekQuery {
+    /// Semaine au format `2024-W10`, la semaine écoulée par défaut.
+    pub week: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WordOfTheWeek {
+    pub week: String,
+    /// Mot choisi par GPT, absent pour une semaine vide.
+    pub word: Option<String>,
+    pub justification: String,
+    /// Entrées de la semaine ignorées faute de note.
+    #[serde(default)]
+    pub excluded_count: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct WordOfTheWeekResponse {
+    word: String,
+    justification: String,
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum WordOfTheWeekError {
+    #[error("invalid week \"{0}\", expected a week like 2024-W10")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidWeek(String),
+    #[error(transparent)]
+    #[status(StatusCode::TOO_MANY_REQUESTS)]
+    Throttle(ThrottleError),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    OpenAI(OpenAIError),
+    #[error("no output from GPT-4")]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    NoOutput,
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Parse(serde_json::Error),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+/// Demande à GPT le mot qui résume le mieux la semaine. Une semaine sans entrée
+/// ne donne lieu à aucun appel.
+pub async fn generate_word_of_the_week(
+    openai: &Client<OpenAIConfig>,
+    throttle: Option<&OpenAiThrottle>,
+    usage: &UsageCounter,
+    model: &str,
+    week: &str,
+    entries: &[JournalEntry],
+) -> Result<WordOfTheWeek, WordOfTheWeekError> {
+    if entries.is_empty() {
+        return Ok(WordOfTheWeek {
+            week: week.to_string(),
+            word: None,
+            justification: "Aucune entrée n'a été écrite cette semaine.".to_string(),
+            excluded_count: 0,
+        });
+    }
+
+    let completion_request = CreateChatCompletionRequestArgs::default()
+        .model(model)
+        .messages(vec![
+            ChatCompletionRequestMessage::System(
+                ChatCompletionRequestSystemMessageArgs::default()
+                    .content(include_str!("./word_of_the_week_message.txt"))
+                    .build()
+                    .map_err(WordOfTheWeekError::OpenAI)?,
+            ),
+            ChatCompletionRequestMessage::User(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content(daily_summaries_message(entries))
+                    .build()
+                    .map_err(WordOfTheWeekError::OpenAI)?,
+            ),
+        ])
+        .n(1)
+        .build()
+        .map_err(WordOfTheWeekError::OpenAI)?;
+
+    take_turn(throttle)
+        .await
+        .map_err(WordOfTheWeekError::Throttle)?;
+    let response = openai
+        .chat()
+        .create(completion_request)
+        .await
+        .map_err(WordOfTheWeekError::OpenAI)?;
+    usage.record(response.usage.as_ref().map(TokenUsage::from));
+    let content = response
+        .choices
+        .first()
+        .and_then(|o| o.message.clone().content)
+        .filter(|content| !content.trim().is_empty())
+        .ok_or(WordOfTheWeekError::NoOutput)?;
+    let response = serde_json::from_str::<WordOfTheWeekResponse>(&content)
+        .map_err(WordOfTheWeekError::Parse)?;
+
+    Ok(WordOfTheWeek {
+        week: week.to_string(),
+        word: Some(response.word.trim().to_string()).filter(|word| !word.is_empty()),
+        justification: response.justification,
+        excluded_count: 0,
+    })
+}
+
+pub async fn get_word_of_the_week(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(openai): Extension<Arc<Client<OpenAIConfig>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(usage): Extension<Arc<UsageCounter>>,
+    Query(query): Query<WordOfTheWeekQuery>,
+    throttle: Option<Extension<Arc<OpenAiThrottle>>>,
+) -> Result<Json<WordOfTheWeek>, WordOfTheWeekError> {
+    let week = query.week.unwrap_or_else(|| {
+        previous_iso_week(Utc::now().with_timezone(&config.timezone).date_naive())
+    });
+    let (monday, sunday) =
+        parse_iso_week(&week).ok_or_else(|| WordOfTheWeekError::InvalidWeek(week.clone()))?;
+
+    let RatedStats {
+        stats: entries,
+        excluded_count,
+    } = rated_period_entries(&mongo_entries, monday, sunday)
+        .await
+        .map_err(WordOfTheWeekError::Mongo)?;
+
+    Ok(Json(WordOfTheWeek {
+        excluded_count,
+        ..generate_word_of_the_week(
+            &openai,
+            throttle.as_deref().map(Arc::as_ref),
+            &usage,
+            &config.chat_model,
+            &week,
+            &entries,
+        )
+        .await?
+    }))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MonthlyRecap {
+    pub month: String,
+    pub summary: String,
+    /// Note moyenne ressentie estimée par GPT, absente pour un mois vide.
+    pub felt_rate: Option<f32>,
+    pub entries_count: u32,
+    /// Entrées du mois ignorées faute de note.
+    #[serde(default)]
+    pub excluded_count: u64,
+    pub generated_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MonthlyRecapResponse {
+    summary: String,
+    felt_rate: f32,
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum MonthlyRecapError {
+    #[error("invalid month \"{0}\", expected a month like 2024-03")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidMonth(String),
+    #[error("no monthly recap for {0}")]
+    #[status(StatusCode::NOT_FOUND)]
+    NotFound(String),
+    #[error(transparent)]
+    #[status(StatusCode::TOO_MANY_REQUESTS)]
+    Throttle(ThrottleError),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    OpenAI(OpenAIError),
+    #[error("no output from GPT-4")]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    NoOutput,
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Parse(serde_json::Error),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+/// Demande à GPT le récapitulatif du mois à partir des résumés quotidiens.
+/// Un mois sans entrée ne donne lieu à aucun appel.
+pub async fn generate_monthly_recap(
+    openai: &Client<OpenAIConfig>,
+    throttle: Option<&OpenAiThrottle>,
+    usage: &UsageCounter,
+    model: &str,
+    month: &str,
+    entries: &[JournalEntry],
+) -> Result<MonthlyRecap, MonthlyRecapError> {
+    if entries.is_empty() {
+        return Ok(MonthlyRecap {
+            month: month.to_string(),
+            summary: "Aucune entrée n'a été écrite ce mois-ci.".to_string(),
+            felt_rate: None,
+            entries_count: 0,
+            excluded_count: 0,
+            generated_at: Utc::now(),
+        });
+    }
+
+    let completion_request = CreateChatCompletionRequestArgs::default()
+        .model(model)
+        .messages(vec![
+            ChatCompletionRequestMessage::System(
+                ChatCompletionRequestSystemMessageArgs::default()
+                    .content(include_str!("./monthly_recap_message.txt"))
+                    .build()
+                    .map_err(MonthlyRecapError::OpenAI)?,
+            ),
+            ChatCompletionRequestMessage::User(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content(daily_summaries_message(entries))
+                    .build()
+                    .map_err(MonthlyRecapError::OpenAI)?,
+            ),
+        ])
+        .n(1)
+        .build()
+        .map_err(MonthlyRecapError::OpenAI)?;
+
+    take_turn(throttle)
+        .await
+        .map_err(MonthlyRecapError::Throttle)?;
+    let response = openai
+        .chat()
+        .create(completion_request)
+        .await
+        .map_err(MonthlyRecapError::OpenAI)?;
+    usage.record(response.usage.as_ref().map(TokenUsage::from));
+    let content = response
+        .choices
+        .first()
+        .and_then(|o| o.message.clone().content)
+        .filter(|content| !content.trim().is_empty())
+        .ok_or(MonthlyRecapError::NoOutput)?;
+    let response =
+        serde_json::from_str::<MonthlyRecapResponse>(&content).map_err(MonthlyRecapError::Parse)?;
+
+    Ok(MonthlyRecap {
+        month: month.to_string(),
+        summary: response.summary,
+        felt_rate: Some(response.felt_rate)
+            .filter(|rate| rate.is_finite())
+            .map(|rate| rate.clamp(0.0, 10.0)),
+        entries_count: entries.len() as u32,
+        excluded_count: 0,
+        generated_at: Utc::now(),
+    })
+}
+
+/// Régénère le récapitulatif du mois et le stocke, en remplaçant le précédent.
+pub async fn create_monthly_recap(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(mongo_recaps): Extension<Arc<Collection<MonthlyRecap>>>,
+    Extension(openai): Extension<Arc<Client<OpenAIConfig>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(usage): Extension<Arc<UsageCounter>>,
+    Path(month): Path<String>,
+    throttle: Option<Extension<Arc<OpenAiThrottle>>>,
+) -> Result<Json<MonthlyRecap>, MonthlyRecapError> {
+    let (first, last) =
+        parse_month(&month).ok_or_else(|| MonthlyRecapError::InvalidMonth(month.clone()))?;
+
+    let RatedStats {
+        stats: entries,
+        excluded_count,
+    } = rated_period_entries(&mongo_entries, first, last)
+        .await
+        .map_err(MonthlyRecapError::Mongo)?;
+
+    let recap = MonthlyRecap {
+        excluded_count,
+        ..generate_monthly_recap(
+            &openai,
+            throttle.as_deref().map(Arc::as_ref),
+            &usage,
+            &config.chat_model,
+            &month,
+            &entries,
+        )
+        .await?
+    };
+    mongo_recaps
+        .replace_one(
+            doc! { "month": &month },
+            &recap,
+            ReplaceOptions::builder().upsert(true).build(),
+        )
+        .await
+        .map_err(MonthlyRecapError::Mongo)?;
+
+    Ok(Json(recap))
+}
+
+/// Relit le récapitulatif stocké, sans le régénérer.
+pub async fn get_monthly_recap(
+    Extension(mongo_recaps): Extension<Arc<Collection<MonthlyRecap>>>,
+    Path(month): Path<String>,
+) -> Result<Json<MonthlyRecap>, MonthlyRecapError> {
+    if parse_month(&month).is_none() {
+        return Err(MonthlyRecapError::InvalidMonth(month));
+    }
+
+    mongo_recaps
+        .find_one(doc! { "month": &month }, None)
+        .await
+        .map_err(MonthlyRecapError::Mongo)?
+        .map(Json)
+        .ok_or(MonthlyRecapError::NotFound(month))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use axum::Router;
+    use mongodb::bson;
+
+    use crate::{
+        config::CHAT_MODEL,
+        routes::JournalEntry,
+        testing::{mock_chat_completion, mock_openai},
+        usage::UsageCounter,
+    };
+
+    use super::{
+        daily_summaries_message, generate_monthly_recap, generate_word_of_the_week, parse_iso_week,
+        parse_month, previous_iso_week, rated_period_entries, MonthlyRecap,
+    };
+
+    #[test]
+    fn parse_week() {
+        assert_eq!(
+            parse_iso_week("2024-W10"),
+            Some((
+                NaiveDate::from_ymd_opt(2024, 3, 4).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 3, 10).unwrap()
+            ))
+        );
+        assert_eq!(parse_iso_week("2024-W54"), None);
+        assert_eq!(parse_iso_week("2024-10"), None);
+    }
+
+    #[test]
+    fn list_summaries_of_filled_week() {
+        let entries: Vec<JournalEntry> = (4..=10)
+            .map(|day| JournalEntry {
+                date: NaiveDate::from_ymd_opt(2024, 3, day).unwrap(),
+                rate: 6.5,
+                short_summary: format!("Jour {day}"),
+                ..Default::default()
+            })
+            .collect();
+
+        let message = daily_summaries_message(&entries);
+        assert_eq!(message.lines().count(), 7);
+        assert_eq!(message.lines().next(), Some("2024-03-04 (6.5/10): Jour 4"));
+        assert_eq!(message.lines().last(), Some("2024-03-10 (6.5/10): Jour 10"));
+    }
+
+    #[test]
+    fn parse_months() {
+        assert_eq!(
+            parse_month("2024-02"),
+            Some((
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 2, 29).unwrap()
+            ))
+        );
+        assert_eq!(
+            parse_month("2023-12").map(|(_, last)| last),
+            NaiveDate::from_ymd_opt(2023, 12, 31)
+        );
+        assert_eq!(parse_month("2024-13"), None);
+        assert_eq!(parse_month("mars"), None);
+    }
+
+    #[tokio::test]
+    async fn generate_and_read_monthly_recap() {
+        let openai =
+            mock_chat_completion(r#"{"summary":"Un mois de mars sportif.","felt_rate":7.5}"#).await;
+        let entries: Vec<JournalEntry> = [4, 12, 20]
+            .into_iter()
+            .map(|day| JournalEntry {
+                date: NaiveDate::from_ymd_opt(2024, 3, day).unwrap(),
+                rate: 7.0,
+                short_summary: format!("Jour {day}"),
+                ..Default::default()
+            })
+            .collect();
+
+        let usage = UsageCounter::default();
+        let recap = generate_monthly_recap(&openai, None, &usage, CHAT_MODEL, "2024-03", &entries)
+            .await
+            .unwrap();
+        assert_eq!(usage.stats().total_tokens, 150);
+        assert_eq!(recap.summary, "Un mois de mars sportif.");
+        assert_eq!(recap.felt_rate, Some(7.5));
+        assert_eq!(recap.entries_count, 3);
+
+        // Le récap relu depuis `monthly_recaps` est celui qui a été généré
+        let stored = bson::to_document(&recap).unwrap();
+        assert_eq!(bson::from_document::<MonthlyRecap>(stored).unwrap(), recap);
+    }
+
+    #[tokio::test]
+    async fn empty_month_skips_gpt() {
+        // Un appel à GPT échouerait : le serveur ne sert aucune route
+        let openai = mock_openai(Router::new()).await;
+
+        let recap = generate_monthly_recap(
+            &openai,
+            None,
+            &UsageCounter::default(),
+            CHAT_MODEL,
+            "2024-03",
+            &[],
+        )
+        .await
+        .unwrap();
+        assert_eq!(recap.entries_count, 0);
+        assert_eq!(recap.felt_rate, None);
+    }
+
+    #[tokio::test]
+    async fn pick_word_of_the_week() {
+        let openai = mock_chat_completion(
+            r#"{"word":" Course ","justification":"Tu as couru presque tous les jours."}"#,
+        )
+        .await;
+        let entries: Vec<JournalEntry> = (4..=8)
+            .map(|day| JournalEntry {
+                date: NaiveDate::from_ymd_opt(2024, 3, day).unwrap(),
+                rate: 7.0,
+                short_summary: format!("Course du jour {day}"),
+                ..Default::default()
+            })
+            .collect();
+
+        let word = generate_word_of_the_week(
+            &openai,
+            None,
+            &UsageCounter::default(),
+            CHAT_MODEL,
+            "2024-W10",
+            &entries,
+        )
+        .await
+        .unwrap();
+        assert_eq!(word.week, "2024-W10");
+        assert_eq!(word.word.as_deref(), Some("Course"));
+        assert_eq!(word.justification, "Tu as couru presque tous les jours.");
+
+        // Une semaine vide n'appelle pas GPT
+        let openai = mock_openai(Router::new()).await;
+        let word = generate_word_of_the_week(
+            &openai,
+            None,
+            &UsageCounter::default(),
+            CHAT_MODEL,
+            "2024-W11",
+            &[],
+        )
+        .await
+        .unwrap();
+        assert_eq!(word.word, None);
+
+        assert_eq!(
+            previous_iso_week(NaiveDate::from_ymd_opt(2024, 1, 3).unwrap()),
+            "2023-W52"
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server in MONGO"]
+    async fn skip_unrated_entries_of_the_month() {
+        dotenvy::dotenv().ok();
+        let options =
+            crate::mongo_client_options(&std::env::var("MONGO").unwrap(), crate::DEFAULT_APP_NAME)
+                .await
+                .unwrap();
+        let documents = mongodb::Client::with_options(options)
+            .unwrap()
+            .database("journai_test")
+            .collection::<bson::Document>("unrated_recap_entries");
+        documents.drop(None).await.unwrap();
+
+        let entry = |day| {
+            bson::to_document(&JournalEntry {
+                date: NaiveDate::from_ymd_opt(2024, 3, day).unwrap(),
+                rate: 7.0,
+                ..Default::default()
+            })
+            .unwrap()
+        };
+        let mut draft = entry(2);
+        draft.remove("rate");
+        let mut next_month = entry(1);
+        next_month.insert("date", "2024-04-01");
+        next_month.remove("rate");
+        documents
+            .insert_many([entry(1), draft, next_month], None)
+            .await
+            .unwrap();
+
+        let (first, last) = parse_month("2024-03").unwrap();
+        let rated = rated_period_entries(&documents.clone_with_type(), first, last)
+            .await
+            .unwrap();
+        assert_eq!(rated.stats.len(), 1);
+        assert_eq!(rated.excluded_count, 1);
+    }
+}
@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use axum::{
+    http::{HeaderMap, StatusCode},
+    Extension, Json,
+};
+use axum_thiserror::ErrorStatus;
+use chrono::{NaiveDate, Utc};
+use chrono_tz::Tz;
+use mongodb::{bson::doc, options::FindOneOptions, Collection};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+
+use crate::config::AppConfig;
+use crate::routes::{entry_date_projection, EntryDate, JournalEntry};
+
+pub const INTERNAL_KEY_HEADER: &str = "X-Internal-Key";
+
+/// Utilisateur à notifier. L'API ne gérant encore qu'un seul journal, `user_id`
+/// reste absent en attendant le modèle multi-utilisateur.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Reminder {
+    pub user_id: Option<String>,
+    pub timezone: String,
+    pub last_entry_date: Option<NaiveDate>,
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum RemindersError {
+    #[error("missing or invalid internal key")]
+    #[status(StatusCode::UNAUTHORIZED)]
+    Unauthorized,
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+/// Vérifie `X-Internal-Key` contre `INTERNAL_API_KEY` ; sans clé configurée, l'endpoint est fermé.
+pub fn accepts_internal_key(expected: Option<&str>, headers: &HeaderMap) -> bool {
+    match (expected, headers.get(INTERNAL_KEY_HEADER)) {
+        (Some(expected), Some(key)) if !expected.is_empty() => {
+            bool::from(expected.as_bytes().ct_eq(key.as_bytes()))
+        }
+        _ => false,
+    }
+}
+
+/// Rappel à envoyer si rien n'a été écrit aujourd'hui dans le fuseau de l'utilisateur.
+pub fn reminder(
+    user_id: Option<String>,
+    timezone: Tz,
+    last_entry_date: Option<NaiveDate>,
+    today: NaiveDate,
+) -> Option<Reminder> {
+    if last_entry_date.is_some_and(|date| date >= today) {
+        return None;
+    }
+    Some(Reminder {
+        user_id,
+        timezone: timezone.name().to_string(),
+        last_entry_date,
+    })
+}
+
+pub async fn get_reminders(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<Reminder>>, RemindersError> {
+    if !accepts_internal_key(config.internal_api_key.as_deref(), &headers) {
+        return Err(RemindersError::Unauthorized);
+    }
+
+    let timezone = config.timezone;
+    let last_entry = mongo_entries
+        .clone_with_type::<EntryDate>()
+        .find_one(
+            None,
+            FindOneOptions::builder()
+                .projection(entry_date_projection())
+                .sort(doc! { "date": -1 })
+                .build(),
+        )
+        .await
+        .map_err(RemindersError::Mongo)?;
+    let today = Utc::now().with_timezone(&timezone).date_naive();
+
+    Ok(Json(
+        reminder(None, timezone, last_entry.map(|entry| entry.date), today)
+            .into_iter()
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderMap;
+    use chrono::NaiveDate;
+    use chrono_tz::{America::New_York, Europe::Paris};
+
+    use super::{accepts_internal_key, reminder};
+
+    #[test]
+    fn remind_users_who_did_not_write_today() {
+        let date = |day| NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+
+        assert!(reminder(Some("alice".to_string()), Paris, Some(date(10)), date(10)).is_none());
+        let bob = reminder(Some("bob".to_string()), New_York, Some(date(8)), date(10)).unwrap();
+        assert_eq!(bob.timezone, "America/New_York");
+        assert_eq!(bob.last_entry_date, Some(date(8)));
+        let newcomer = reminder(Some("carol".to_string()), Paris, None, date(10)).unwrap();
+        assert_eq!(newcomer.last_entry_date, None);
+    }
+
+    #[test]
+    fn require_internal_key() {
+        let mut headers = HeaderMap::new();
+        assert!(!accepts_internal_key(Some("secret"), &headers));
+        headers.insert("X-Internal-Key", "secret".parse().unwrap());
+        assert!(accepts_internal_key(Some("secret"), &headers));
+        assert!(!accepts_internal_key(Some("other"), &headers));
+        assert!(!accepts_internal_key(None, &headers));
+    }
+}
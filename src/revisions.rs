@@ -0,0 +1,180 @@
+use std::sync::Arc;
+
+use axum::{extract::Path, http::StatusCode, Extension, Json};
+use axum_thiserror::ErrorStatus;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures_util::TryStreamExt;
+use mongodb::{
+    bson::{doc, oid::ObjectId, Document},
+    options::FindOptions,
+    ClientSession, Collection,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::crypto::{CryptoError, EntryCipher};
+use crate::routes::JournalEntry;
+
+/// Nombre de révisions conservées par entrée, les plus anciennes étant supprimées.
+pub const MAX_REVISIONS_PER_ENTRY: u64 = 20;
+
+/// Version passée d'une entrée, archivée avant chaque modification.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EntryRevision {
+    pub date: NaiveDate,
+    pub revised_at: DateTime<Utc>,
+    pub entry: JournalEntry,
+}
+
+impl EntryRevision {
+    /// Révision de `previous`, telle qu'elle était stockée (texte brut chiffré compris).
+    pub fn of(previous: JournalEntry) -> Self {
+        EntryRevision {
+            date: previous.date,
+            revised_at: Utc::now(),
+            entry: previous,
+        }
+    }
+}
+
+fn revisions_filter(date: NaiveDate) -> Document {
+    doc! { "date": date.to_string() }
+}
+
+/// Enregistre la révision, dans la transaction de `session` s'il y en a une.
+pub async fn insert_revision(
+    revisions: &Collection<EntryRevision>,
+    session: Option<&mut ClientSession>,
+    revision: &EntryRevision,
+) -> mongodb::error::Result<()> {
+    match session {
+        Some(session) => revisions
+            .insert_one_with_session(revision, None, session)
+            .await
+            .map(drop),
+        None => revisions.insert_one(revision, None).await.map(drop),
+    }
+}
+
+/// Supprime les révisions de `date` au-delà de `MAX_REVISIONS_PER_ENTRY`.
+/// Un échec est seulement journalisé : il ne doit pas faire échouer la modification.
+pub async fn prune_revisions(revisions: &Collection<EntryRevision>, date: NaiveDate) {
+    let result = async {
+        let outdated: Vec<ObjectId> = revisions
+            .clone_with_type::<Document>()
+            .find(
+                revisions_filter(date),
+                FindOptions::builder()
+                    .projection(doc! { "_id": 1 })
+                    .sort(doc! { "revised_at": -1 })
+                    .skip(MAX_REVISIONS_PER_ENTRY)
+                    .build(),
+            )
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?
+            .iter()
+            .filter_map(|document| document.get_object_id("_id").ok())
+            .collect();
+        if !outdated.is_empty() {
+            revisions
+                .delete_many(doc! { "_id": { "$in": outdated } }, None)
+                .await?;
+        }
+        mongodb::error::Result::Ok(())
+    }
+    .await;
+
+    if let Err(error) = result {
+        tracing::warn!("Could not prune the revisions of {date}: {error}");
+    }
+}
+
+/// Archive `previous` puis supprime les révisions au-delà de `MAX_REVISIONS_PER_ENTRY`.
+/// Un échec est seulement journalisé : il ne doit pas faire échouer la modification.
+pub async fn archive_revision(revisions: &Collection<EntryRevision>, previous: JournalEntry) {
+    let revision = EntryRevision::of(previous);
+    match insert_revision(revisions, None, &revision).await {
+        Ok(()) => prune_revisions(revisions, revision.date).await,
+        Err(error) => tracing::warn!(
+            "Could not archive the revision of {}: {error}",
+            revision.date
+        ),
+    }
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum EntryHistoryError {
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Crypto(CryptoError),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+/// Versions passées d'une entrée, de la plus récente à la plus ancienne.
+pub async fn get_entry_history(
+    Extension(revisions): Extension<Arc<Collection<EntryRevision>>>,
+    Extension(cipher): Extension<Arc<EntryCipher>>,
+    Path(date): Path<NaiveDate>,
+) -> Result<Json<Vec<EntryRevision>>, EntryHistoryError> {
+    revisions
+        .find(
+            revisions_filter(date),
+            FindOptions::builder()
+                .sort(doc! { "revised_at": -1 })
+                .build(),
+        )
+        .await
+        .map_err(EntryHistoryError::Mongo)?
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(EntryHistoryError::Mongo)?
+        .into_iter()
+        .map(|revision| {
+            Ok(EntryRevision {
+                entry: revision.entry.decrypted(&cipher)?,
+                ..revision
+            })
+        })
+        .collect::<Result<_, _>>()
+        .map(Json)
+        .map_err(EntryHistoryError::Crypto)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use mongodb::bson;
+
+    use crate::routes::JournalEntry;
+
+    use super::{revisions_filter, EntryRevision};
+
+    #[test]
+    fn modification_creates_revision() {
+        let previous = JournalEntry {
+            date: NaiveDate::from_ymd_opt(2024, 1, 24).unwrap(),
+            rate: 6.0,
+            short_summary: "Première version".to_string(),
+            ..Default::default()
+        };
+
+        let revision = EntryRevision::of(previous.clone());
+        assert_eq!(revision.entry, previous);
+
+        let stored = bson::to_document(&revision).unwrap();
+        assert_eq!(
+            stored.get("date"),
+            revisions_filter(previous.date).get("date")
+        );
+        assert_eq!(
+            stored
+                .get_document("entry")
+                .unwrap()
+                .get_str("short_summary"),
+            Ok("Première version")
+        );
+    }
+}
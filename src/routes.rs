@@ -1,39 +1,340 @@
-use std::{fmt::Debug, sync::Arc, vec};
+use std::{collections::HashMap, fmt::Debug, ops::RangeInclusive, sync::Arc, time::Instant};
 
 use async_openai::{
-    error::OpenAIError,
-    types::{
-        ChatCompletionRequestMessage, ChatCompletionRequestSystemMessageArgs,
-        ChatCompletionRequestUserMessageArgs, CreateChatCompletionRequestArgs,
-    },
-    Client,
+    config::OpenAIConfig, error::OpenAIError, types::CreateChatCompletionRequestArgs, Client,
+};
+use axum::{
+    extract::{self, Path},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
 };
-use axum::{extract, http::StatusCode, Extension, Json};
 use axum_thiserror::ErrorStatus;
-use chrono::{NaiveDate, TimeZone};
-use chrono_tz::Europe::Paris;
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
 use futures_util::TryStreamExt;
-use mongodb::{bson::doc, Collection};
-use serde::{Deserialize, Serialize};
+use mongodb::{
+    bson::{doc, oid::ObjectId, Bson, Document},
+    options::{CountOptions, FindOneAndUpdateOptions, FindOneOptions, FindOptions, ReturnDocument},
+    Collection,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
 use thiserror::Error;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+use crate::chunking::{condense_text, estimate_tokens, CondenseError, CHUNKING_THRESHOLD_TOKENS};
+use crate::compression::{compress_text, decompress_text, is_old};
+use crate::config::{AppConfig, DEFAULT_TIMEZONE};
+use crate::crypto::{CryptoError, EntryCipher};
+use crate::custom_fields::{validate_custom_fields, CustomFieldError};
+use crate::fallback::extractive_entry;
+use crate::geo::Location;
+use crate::highlights::{locate_highlights, Highlight};
+use crate::msgpack::{Negotiated, ResponseFormat};
+use crate::period::RelativePeriod;
+use crate::prompt::{
+    build_entry_messages, normalize_text, parse_entry_response, Lang, ParseEntryError,
+    SummaryLength,
+};
+use crate::revisions::{archive_revision, insert_revision, prune_revisions, EntryRevision};
+use crate::similar::{embed_text, recent_similar_dates};
+use crate::stats::count_words;
+use crate::throttle::{take_turn, OpenAiThrottle, ThrottleError};
+use crate::transaction::in_transaction;
+use crate::undo::DeletedEntries;
+use crate::upload::EntryPayload;
+use crate::usage::{TokenUsage, UsageCounter};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
 pub struct JournalEntry {
+    /// `ObjectId` Mongo en hexadécimal, vide tant que l'entrée n'est pas enregistrée.
+    #[serde(
+        rename = "_id",
+        default,
+        skip_serializing_if = "String::is_empty",
+        serialize_with = "serialize_entry_id",
+        deserialize_with = "deserialize_entry_id"
+    )]
+    pub id: String,
     pub date: NaiveDate,
     pub rate: f32,
     pub short_summary: String,
+    #[serde(deserialize_with = "deserialize_tags")]
     pub tags: Vec<String>,
+    #[serde(default)]
+    pub topic_ratings: Vec<TopicRating>,
+    /// Date de la première création, conservée quand l'entrée est remplacée.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+    /// Fuseau dans lequel l'entrée a été écrite, celui par défaut pour les anciennes entrées.
+    #[serde(default = "default_entry_timezone")]
+    pub timezone: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub style_hint: Option<String>,
+    /// Longueur de résumé demandée à la création.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary_length: Option<SummaryLength>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub word_count: Option<u32>,
+    #[serde(default)]
+    pub people: Vec<String>,
+    #[serde(default)]
+    pub places: Vec<String>,
+    /// Moments forts repérés par GPT dans le texte.
+    #[serde(default)]
+    pub highlights: Vec<Highlight>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+    /// Texte saisi, chiffré au repos quand `ENCRYPTION_KEY` est configurée.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_text: Option<String>,
+    /// `raw_text` est stocké compressé, ce qui n'est le cas que des anciennes entrées.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub text_compressed: bool,
+    /// Tokens consommés par l'analyse GPT de l'entrée et par son embedding.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<TokenUsage>,
+    /// Embedding du texte, absent des entrées créées avant la recherche par similarité.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<Vec<f32>>,
+    /// Photo jointe à l'entrée, conservée quand l'entrée est réanalysée.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_url: Option<String>,
+    /// Métriques propres à l'utilisateur (heures de sommeil, pas…), stockées telles quelles.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub custom_fields: HashMap<String, Value>,
+    /// Application à l'origine de l'entrée quand elle arrive par webhook (`ifttt`, `telegram`…).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+fn default_entry_timezone() -> String {
+    DEFAULT_TIMEZONE.name().to_string()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TopicRating {
+    pub topic: String,
+    pub rate: f32,
+}
+
+/// Écrit l'identifiant en `ObjectId` dans Mongo et en chaîne dans les réponses JSON.
+fn serialize_entry_id<S: Serializer>(id: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        serializer.serialize_str(id)
+    } else {
+        ObjectId::parse_str(id)
+            .map_err(serde::ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Tags {
+    List(Vec<String>),
+    Joined(String),
+}
+
+/// Accepte les topics en tableau comme en une seule chaîne séparée par des
+/// virgules (`"travail, famille"`), que GPT renvoie parfois.
+pub(crate) fn deserialize_tags<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    Ok(match Tags::deserialize(deserializer)? {
+        Tags::List(tags) => tags,
+        Tags::Joined(tags) => tags
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect(),
+    })
+}
+
+/// Lit l'identifiant depuis un `ObjectId` (Mongo) comme depuis une chaîne (JSON).
+fn deserialize_entry_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    match Bson::deserialize(deserializer)? {
+        Bson::ObjectId(id) => Ok(id.to_hex()),
+        Bson::String(id) => Ok(id),
+        Bson::Null => Ok(String::new()),
+        other => Err(serde::de::Error::custom(format!(
+            "expected an ObjectId, found {other}"
+        ))),
+    }
+}
+
+/// Filtre Mongo d'une entrée par date, sous la forme sérialisée par `JournalEntry`.
+pub fn date_filter(date: NaiveDate) -> Document {
+    doc! { "date": date.to_string() }
+}
+
+#[derive(Error, Debug)]
+pub enum JournalEntryValidationError {
+    #[error("rate must be a finite number, got {0}")]
+    NonFiniteRate(f32),
+    #[error("rate of topic \"{0}\" must be a finite number, got {1}")]
+    NonFiniteTopicRate(String, f32),
+    #[error("rate {0} is not between 0 and 10")]
+    Rate(f32),
+    #[error("rate {1} of topic \"{0}\" is not between 0 and 10")]
+    TopicRate(String, f32),
+}
+
+impl JournalEntry {
+    /// Copie de l'entrée telle qu'elle doit être stockée, texte brut chiffré.
+    pub fn encrypted(&self, cipher: &EntryCipher) -> JournalEntry {
+        JournalEntry {
+            raw_text: self.raw_text.as_deref().map(|text| cipher.encrypt(text)),
+            ..self.clone()
+        }
+    }
+
+    /// Entrée lue depuis Mongo, texte brut déchiffré puis décompressé.
+    pub fn decrypted(mut self, cipher: &EntryCipher) -> Result<JournalEntry, CryptoError> {
+        self.raw_text = self
+            .raw_text
+            .map(|text| cipher.decrypt(&text))
+            .transpose()?;
+        if self.text_compressed {
+            self.raw_text = self.raw_text.as_deref().map(decompress_text).transpose()?;
+            self.text_compressed = false;
+        }
+        Ok(self)
+    }
+
+    /// Entrée au texte brut compressé, à chiffrer ensuite.
+    pub fn compressed(mut self) -> JournalEntry {
+        if !self.text_compressed {
+            self.raw_text = self.raw_text.as_deref().map(compress_text);
+            self.text_compressed = true;
+        }
+        self
+    }
+
+    /// Vérifie que les notes renvoyées par GPT sont dans l'intervalle [0, 10].
+    pub fn validate(&self) -> Result<(), JournalEntryValidationError> {
+        // Un NaN ou un infini fausserait toutes les moyennes calculées ensuite
+        if !self.rate.is_finite() {
+            return Err(JournalEntryValidationError::NonFiniteRate(self.rate));
+        }
+        if let Some(rating) = self
+            .topic_ratings
+            .iter()
+            .find(|rating| !rating.rate.is_finite())
+        {
+            return Err(JournalEntryValidationError::NonFiniteTopicRate(
+                rating.topic.clone(),
+                rating.rate,
+            ));
+        }
+        if !RATE_RANGE.contains(&self.rate) {
+            return Err(JournalEntryValidationError::Rate(self.rate));
+        }
+        if let Some(rating) = self
+            .topic_ratings
+            .iter()
+            .find(|rating| !RATE_RANGE.contains(&rating.rate))
+        {
+            return Err(JournalEntryValidationError::TopicRate(
+                rating.topic.clone(),
+                rating.rate,
+            ));
+        }
+        Ok(())
+    }
 }
 
-#[derive(Deserialize, Debug)]
+/// Notes acceptées, pour l'entrée comme pour chaque topic.
+pub const RATE_RANGE: RangeInclusive<f32> = 0.0..=10.0;
+
+/// Longueur maximale de l'indication de style, pour limiter l'injection de prompt.
+pub const STYLE_HINT_MAX_LENGTH: usize = 100;
+/// Longueur maximale du texte d'une entrée, en caractères, sauf configuration.
+pub const DEFAULT_MAX_SUMMARY_LENGTH: usize = 100_000;
+
+#[derive(Deserialize, Debug, Clone, Default)]
 pub struct CreateJournalEntry {
     pub name: String,
     pub summary: String,
     pub date: NaiveDate,
+    #[serde(default)]
+    pub style_hint: Option<String>,
+    #[serde(default)]
+    pub location: Option<Location>,
+    /// Langue du prompt système, détectée depuis le texte si absente.
+    #[serde(default)]
+    pub lang: Option<Lang>,
+    #[serde(default)]
+    pub summary_length: Option<SummaryLength>,
+    /// Fuseau de l'auteur, par exemple en voyage ; celui configuré par défaut.
+    #[serde(default)]
+    pub timezone: Option<String>,
+    #[serde(default)]
+    pub custom_fields: HashMap<String, Value>,
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Note et résumé déjà calculés (réimport) : GPT n'est alors pas appelé.
+    #[serde(default)]
+    pub rate: Option<f32>,
+    #[serde(default)]
+    pub short_summary: Option<String>,
+}
+
+impl CreateJournalEntry {
+    /// Entrée construite à partir de la note et du résumé fournis, si les deux le sont.
+    pub fn provided_analysis(&self) -> Option<Result<JournalEntry, JournalEntryValidationError>> {
+        let entry = JournalEntry {
+            date: self.date,
+            rate: self.rate?,
+            short_summary: self.short_summary.clone()?,
+            ..Default::default()
+        };
+        Some(entry.validate().map(|_| entry))
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct CreateJournalEntryQuery {
+    #[serde(default)]
+    pub offline: bool,
+    /// `replace=false` refuse d'écraser une entrée existante au lieu de la remplacer.
+    pub replace: Option<bool>,
+}
+
+impl CreateJournalEntryQuery {
+    /// Vérifie l'existence d'une entrée avant l'insertion seulement si on l'a demandé,
+    /// pour garder le chemin rapide par défaut.
+    pub fn rejects_existing(&self) -> bool {
+        self.replace == Some(false)
+    }
 }
 
 #[derive(Error, Debug, ErrorStatus)]
 pub enum CreateJournalEntryError {
+    #[error("summary must not be longer than {0} characters")]
+    #[status(StatusCode::BAD_REQUEST)]
+    SummaryTooLong(usize),
+    #[error("style_hint must not be longer than {STYLE_HINT_MAX_LENGTH} characters")]
+    #[status(StatusCode::BAD_REQUEST)]
+    StyleHintTooLong,
+    #[error("unknown timezone \"{0}\"")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidTimezone(String),
+    #[error(transparent)]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidCustomFields(CustomFieldError),
+    #[error(transparent)]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidEntry(JournalEntryValidationError),
+    #[error("an entry already exists for {0}, use PATCH /entry/{0} to update it")]
+    #[status(StatusCode::CONFLICT)]
+    AlreadyExists(NaiveDate),
+    #[error(transparent)]
+    #[status(StatusCode::TOO_MANY_REQUESTS)]
+    Throttle(ThrottleError),
     #[error(transparent)]
     #[status(StatusCode::INTERNAL_SERVER_ERROR)]
     OpenAI(OpenAIError),
@@ -42,39 +343,338 @@ pub enum CreateJournalEntryError {
     NoOutput,
     #[error(transparent)]
     #[status(StatusCode::INTERNAL_SERVER_ERROR)]
-    Serialization(serde_json::Error),
+    Parse(ParseEntryError),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Bson(bson::ser::Error),
     #[error(transparent)]
     #[status(StatusCode::INTERNAL_SERVER_ERROR)]
     Mongo(mongodb::error::Error),
 }
 
+pub const PROCESSING_TIME_HEADER: &str = "X-Processing-Time-Ms";
+
+/// Header donnant le temps écoulé depuis `start`, en millisecondes.
+pub fn processing_time_header(start: Instant) -> [(&'static str, String); 1] {
+    [(
+        PROCESSING_TIME_HEADER,
+        start.elapsed().as_millis().to_string(),
+    )]
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn create_journal_entry(
     Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
-    journal_entry: extract::Json<CreateJournalEntry>,
-) -> Result<Json<JournalEntry>, CreateJournalEntryError> {
-    let entry_message = ChatCompletionRequestMessage::System(
-        ChatCompletionRequestSystemMessageArgs::default()
-            .content(include_str!("./journal_entry_message.txt"))
-            .build()
-            .map_err(CreateJournalEntryError::OpenAI)?,
-    );
-    let user_message = ChatCompletionRequestMessage::User(
-        ChatCompletionRequestUserMessageArgs::default()
-            .content(format!(
-                "{} ({}): {}",
-                journal_entry.name, journal_entry.date, journal_entry.summary
-            ))
-            .build()
-            .map_err(CreateJournalEntryError::OpenAI)?,
-    );
+    Extension(revisions): Extension<Arc<Collection<EntryRevision>>>,
+    Extension(openai): Extension<Arc<Client<OpenAIConfig>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(cipher): Extension<Arc<EntryCipher>>,
+    Extension(usage): Extension<Arc<UsageCounter>>,
+    extract::Query(query): extract::Query<CreateJournalEntryQuery>,
+    throttle: Option<Extension<Arc<OpenAiThrottle>>>,
+    EntryPayload(journal_entry): EntryPayload,
+) -> Result<impl IntoResponse, CreateJournalEntryError> {
+    let start = Instant::now();
+    if query.rejects_existing()
+        && mongo_entries
+            .clone_with_type::<Document>()
+            .find_one(
+                date_filter(journal_entry.date),
+                FindOneOptions::builder()
+                    .projection(doc! { "_id": 1 })
+                    .build(),
+            )
+            .await
+            .map_err(CreateJournalEntryError::Mongo)?
+            .is_some()
+    {
+        return Err(CreateJournalEntryError::AlreadyExists(journal_entry.date));
+    }
+
+    let entry = process_journal_entry(
+        &mongo_entries,
+        &revisions,
+        &openai,
+        throttle.as_deref().map(Arc::as_ref),
+        &config,
+        &cipher,
+        journal_entry,
+        query.offline,
+    )
+    .await?;
+    usage.record(entry.tokens);
+    let similar_to = recent_similar_dates(&mongo_entries, &entry, config.similarity_threshold)
+        .await
+        .unwrap_or_else(|error| {
+            tracing::warn!("Could not look for similar entries: {error}");
+            vec![]
+        });
+    Ok((
+        processing_time_header(start),
+        Json(CreatedJournalEntry { entry, similar_to }),
+    ))
+}
+
+/// Entrée créée, avec les dates des entrées récentes qui lui ressemblent beaucoup.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct CreatedJournalEntry {
+    #[serde(flatten)]
+    pub entry: JournalEntry,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub similar_to: Vec<NaiveDate>,
+}
+
+/// Entrée à enregistrer : texte nettoyé, analysé, et métadonnées renseignées.
+pub async fn prepare_journal_entry(
+    openai: &Client<OpenAIConfig>,
+    throttle: Option<&OpenAiThrottle>,
+    config: &AppConfig,
+    mut journal_entry: CreateJournalEntry,
+    offline: bool,
+) -> Result<JournalEntry, CreateJournalEntryError> {
+    journal_entry.summary = normalize_text(&journal_entry.summary);
+    if journal_entry.summary.chars().count() > config.max_summary_length {
+        return Err(CreateJournalEntryError::SummaryTooLong(
+            config.max_summary_length,
+        ));
+    }
+    if journal_entry
+        .style_hint
+        .as_ref()
+        .is_some_and(|hint| hint.chars().count() > STYLE_HINT_MAX_LENGTH)
+    {
+        return Err(CreateJournalEntryError::StyleHintTooLong);
+    }
+    let timezone = match journal_entry.timezone.as_deref() {
+        Some(timezone) => timezone
+            .parse::<Tz>()
+            .map_err(|_| CreateJournalEntryError::InvalidTimezone(timezone.to_string()))?,
+        None => config.timezone,
+    };
+    validate_custom_fields(&journal_entry.custom_fields)
+        .map_err(CreateJournalEntryError::InvalidCustomFields)?;
+
+    let mut json = analyze_entry(openai, throttle, config, &journal_entry, offline).await?;
+    // Champs propres au serveur, que l'analyse ne doit jamais renseigner
+    json.id.clear();
+    json.text_compressed = false;
+    json.image_url = None;
+    json.updated_at = Some(Utc::now());
+    json.timezone = timezone.name().to_string();
+    json.style_hint = journal_entry.style_hint.clone();
+    json.summary_length = journal_entry.summary_length;
+    json.word_count = Some(count_words(&journal_entry.summary));
+    json.location = journal_entry.location.clone();
+    json.custom_fields = journal_entry.custom_fields.clone();
+    json.source = journal_entry.source.clone();
+    locate_highlights(&mut json.highlights, &journal_entry.summary);
+    // L'embedding est facultatif : file saturée ou erreur, il sera calculé par le backfill
+    if !offline {
+        let embedding = match take_turn(throttle).await {
+            Ok(()) => embed_text(openai, &config.embedding_model, &journal_entry.summary)
+                .await
+                .unwrap_or_else(|error| {
+                    tracing::warn!("Could not compute the embedding of the entry: {error}");
+                    None
+                }),
+            Err(error) => {
+                tracing::warn!("Skipping the embedding of the entry: {error}");
+                None
+            }
+        };
+        if let Some((embedding, tokens)) = embedding {
+            json.embedding = Some(embedding);
+            json.tokens = Some(json.tokens.unwrap_or_default() + tokens);
+        }
+    }
+    json.raw_text = Some(journal_entry.summary);
+    Ok(json)
+}
+
+/// Champs optionnels absents d'une nouvelle analyse, retirés de l'entrée remplacée.
+const OPTIONAL_FIELDS: [&str; 11] = [
+    "updated_at",
+    "style_hint",
+    "summary_length",
+    "word_count",
+    "location",
+    "raw_text",
+    "text_compressed",
+    "custom_fields",
+    "source",
+    "tokens",
+    "embedding",
+];
+
+/// Mise à jour remplaçant l'entrée du jour par `stored` ; `created_at` n'est
+/// écrit qu'à la création de l'entrée.
+pub fn upsert_update(
+    stored: &JournalEntry,
+    now: DateTime<Utc>,
+) -> Result<Document, bson::ser::Error> {
+    let mut fields = bson::to_document(stored)?;
+    fields.remove("_id");
+    fields.remove("created_at");
+    let mut unset = Document::new();
+    for field in OPTIONAL_FIELDS {
+        if !fields.contains_key(field) {
+            unset.insert(field, "");
+        }
+    }
+
+    let mut update = doc! {
+        "$set": fields,
+        "$setOnInsert": { "created_at": bson::to_bson(&now)? },
+    };
+    if !unset.is_empty() {
+        update.insert("$unset", unset);
+    }
+    Ok(update)
+}
+
+/// Analyse une entrée avec GPT puis l'enregistre, en remplaçant celle du même jour.
+/// En mode `offline`, ou si OpenAI ne répond pas, un résumé extractif est utilisé.
+#[allow(clippy::too_many_arguments)]
+pub async fn process_journal_entry(
+    mongo_entries: &Collection<JournalEntry>,
+    revisions: &Collection<EntryRevision>,
+    openai: &Client<OpenAIConfig>,
+    throttle: Option<&OpenAiThrottle>,
+    config: &AppConfig,
+    cipher: &EntryCipher,
+    journal_entry: CreateJournalEntry,
+    offline: bool,
+) -> Result<JournalEntry, CreateJournalEntryError> {
+    let mut json = prepare_journal_entry(openai, throttle, config, journal_entry, offline).await?;
+    let now = Utc::now();
+    // Une entrée ancienne (réimport, rattrapage) est compressée dès son enregistrement
+    let stored = if is_old(
+        json.date,
+        now.with_timezone(&config.timezone).date_naive(),
+        config.compress_after_days,
+    ) {
+        json.clone().compressed()
+    } else {
+        json.clone()
+    };
+
+    // Un seul upsert atomique : deux créations simultanées du même jour ne
+    // produisent qu'une entrée, Mongo rejouant l'upsert en cas de conflit d'index.
+    let previous = mongo_entries
+        .find_one_and_update(
+            date_filter(json.date),
+            upsert_update(&stored.encrypted(cipher), now).map_err(CreateJournalEntryError::Bson)?,
+            FindOneAndUpdateOptions::builder().upsert(true).build(),
+        )
+        .await
+        .map_err(CreateJournalEntryError::Mongo)?;
+    (json.id, json.created_at) = match previous {
+        Some(previous) => {
+            let stored = (previous.id.clone(), previous.created_at);
+            archive_revision(revisions, previous).await;
+            stored
+        }
+        None => (inserted_id(mongo_entries, json.date).await?, Some(now)),
+    };
+
+    Ok(json)
+}
+
+/// Identifiant de l'entrée du jour que l'upsert vient d'insérer.
+async fn inserted_id(
+    mongo_entries: &Collection<JournalEntry>,
+    date: NaiveDate,
+) -> Result<String, CreateJournalEntryError> {
+    Ok(mongo_entries
+        .clone_with_type::<Document>()
+        .find_one(
+            date_filter(date),
+            FindOneOptions::builder()
+                .projection(doc! { "_id": 1 })
+                .build(),
+        )
+        .await
+        .map_err(CreateJournalEntryError::Mongo)?
+        .and_then(|stored| stored.get_object_id("_id").ok())
+        .map(|id| id.to_hex())
+        .unwrap_or_default())
+}
+
+/// Analyse de l'entrée : celle fournie par le client, sinon celle de GPT, sinon
+/// (mode `offline` ou OpenAI indisponible) un résumé extractif.
+async fn analyze_entry(
+    openai: &Client<OpenAIConfig>,
+    throttle: Option<&OpenAiThrottle>,
+    config: &AppConfig,
+    journal_entry: &CreateJournalEntry,
+    offline: bool,
+) -> Result<JournalEntry, CreateJournalEntryError> {
+    if let Some(entry) = journal_entry.provided_analysis() {
+        return entry.map_err(CreateJournalEntryError::InvalidEntry);
+    }
+
+    Ok(if offline {
+        extractive_entry(journal_entry)
+    } else {
+        match analyze_journal_entry(
+            openai,
+            throttle,
+            &config.chat_model,
+            config.temperature,
+            journal_entry,
+        )
+        .await
+        {
+            Err(CreateJournalEntryError::OpenAI(error)) => {
+                tracing::warn!(
+                    "OpenAI unavailable, falling back to an extractive summary: {error}"
+                );
+                extractive_entry(journal_entry)
+            }
+            result => result?,
+        }
+    })
+}
+
+/// Demande à GPT d'analyser l'entrée, chaque appel attendant son tour dans la file.
+async fn analyze_journal_entry(
+    openai: &Client<OpenAIConfig>,
+    throttle: Option<&OpenAiThrottle>,
+    model: &str,
+    temperature: f32,
+    journal_entry: &CreateJournalEntry,
+) -> Result<JournalEntry, CreateJournalEntryError> {
+    // Un texte trop long pour le contexte est d'abord résumé par morceaux
+    let condensed;
+    let (journal_entry, chunk_tokens) =
+        if estimate_tokens(&journal_entry.summary) > CHUNKING_THRESHOLD_TOKENS {
+            let (summary, tokens) = condense_text(openai, throttle, model, &journal_entry.summary)
+                .await
+                .map_err(|error| match error {
+                    CondenseError::OpenAI(error) => CreateJournalEntryError::OpenAI(error),
+                    CondenseError::Throttle(error) => CreateJournalEntryError::Throttle(error),
+                })?;
+            condensed = CreateJournalEntry {
+                summary,
+                ..journal_entry.clone()
+            };
+            (&condensed, tokens)
+        } else {
+            (journal_entry, TokenUsage::default())
+        };
+
     let completion_request = CreateChatCompletionRequestArgs::default()
-        .model("gpt-3.5-turbo")
-        .messages(vec![entry_message, user_message])
+        .model(model)
+        .messages(build_entry_messages(journal_entry).map_err(CreateJournalEntryError::OpenAI)?)
+        .temperature(temperature)
         .n(1)
         .build()
         .map_err(CreateJournalEntryError::OpenAI)?;
 
-    let response = Client::new()
+    take_turn(throttle)
+        .await
+        .map_err(CreateJournalEntryError::Throttle)?;
+    let response = openai
         .chat()
         .create(completion_request)
         .await
@@ -82,51 +682,299 @@ pub async fn create_journal_entry(
 
     let json = response
         .choices
-        .get(0)
-        .map(|o| o.message.clone().content)
-        .flatten()
+        .first()
+        .and_then(|o| o.message.clone().content)
+        // Un contenu vide est une absence de réponse, pas une réponse à interpréter
+        .filter(|content| !content.trim().is_empty())
         .ok_or(CreateJournalEntryError::NoOutput)?;
 
-    let json = serde_json::from_str::<JournalEntry>(&json)
-        .map_err(CreateJournalEntryError::Serialization)?;
-
-    if let Err(_) = mongo_entries.insert_one(json.clone(), None).await {
-        let date = Paris
-            .from_local_datetime(&json.date.and_hms_opt(0, 0, 0).unwrap())
-            .unwrap();
-
-        mongo_entries
-            .update_one(
-                doc! { "date": date },
-                doc! {
-                    "$set": {
-                        "short_summary": json.short_summary.clone(),
-                        "rate": json.rate.clone(),
-                        "tags": json.tags.clone()
-                    }
-                },
-                None,
-            )
-            .await
-            .map_err(CreateJournalEntryError::Mongo)?;
-    }
-
-    Ok(Json(json))
+    let mut entry =
+        parse_entry_response(&json, journal_entry.date).map_err(CreateJournalEntryError::Parse)?;
+    // Les tokens des résumés de morceaux comptent même sans usage pour l'analyse finale
+    let tokens = response
+        .usage
+        .as_ref()
+        .map(TokenUsage::from)
+        .unwrap_or_default()
+        + chunk_tokens;
+    entry.tokens = Some(tokens).filter(|tokens| *tokens != TokenUsage::default());
+    Ok(entry)
 }
 
 #[derive(Error, Debug, ErrorStatus)]
 pub enum ListJournalEntryError {
+    #[error("rate_min ({0}) must be lower than or equal to rate_max ({1})")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidRateRange(f32, f32),
+    #[error("before and after cannot be used together")]
+    #[status(StatusCode::BAD_REQUEST)]
+    ConflictingCursors,
+    #[error("unknown period \"{0}\", expected one of today, yesterday, this-week, last-week, this-month, last-month, this-year, last-year")]
+    #[status(StatusCode::BAD_REQUEST)]
+    UnknownPeriod(String),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Crypto(CryptoError),
     #[error(transparent)]
     #[status(StatusCode::INTERNAL_SERVER_ERROR)]
     Mongo(mongodb::error::Error),
 }
 
+/// Limite appliquée quand le client n'en fournit pas, pour ne jamais charger
+/// toute la collection en mémoire.
+pub const DEFAULT_LIST_LIMIT: u64 = 1000;
+
+#[derive(Deserialize, Debug, Default)]
+pub struct ListJournalEntries {
+    pub rate_min: Option<f32>,
+    pub rate_max: Option<f32>,
+    pub limit: Option<u64>,
+    #[serde(default)]
+    pub offset: u64,
+    /// Curseurs : entrées strictement antérieures ou postérieures à cette date.
+    pub before: Option<NaiveDate>,
+    pub after: Option<NaiveDate>,
+    /// Entrées avec (`true`) ou sans (`false`) photo jointe.
+    pub has_image: Option<bool>,
+    /// Entrées avec (`true`) ou sans (`false`) au moins un moment fort positif.
+    pub has_positive_highlight: Option<bool>,
+    /// Période relative (`yesterday`, `last-week`, `this-month`…) dans le fuseau configuré.
+    pub period: Option<String>,
+    /// `exact_count=true` compte exactement les entrées même sans filtre, plus lentement.
+    #[serde(default)]
+    pub exact_count: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub total: u64,
+    pub limit: Option<u64>,
+    pub offset: u64,
+    pub has_more: bool,
+    /// `total` vient de l'estimation rapide de Mongo et peut différer légèrement du compte exact.
+    #[serde(default)]
+    pub count_is_estimate: bool,
+}
+
+impl<T> Page<T> {
+    pub fn new(data: Vec<T>, total: u64, limit: Option<u64>, offset: u64) -> Self {
+        let has_more = offset + (data.len() as u64) < total;
+        Page {
+            data,
+            total,
+            limit,
+            offset,
+            has_more,
+            count_is_estimate: false,
+        }
+    }
+}
+
+/// Page d'entrées, avec la note moyenne des entrées renvoyées (`null` si la page est vide).
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct EntriesPage {
+    #[serde(flatten)]
+    pub page: Page<JournalEntry>,
+    pub page_average_rate: Option<f32>,
+}
+
+impl From<Page<JournalEntry>> for EntriesPage {
+    fn from(page: Page<JournalEntry>) -> Self {
+        let page_average_rate = (!page.data.is_empty()).then(|| {
+            page.data.iter().map(|entry| entry.rate).sum::<f32>() / page.data.len() as f32
+        });
+        EntriesPage {
+            page,
+            page_average_rate,
+        }
+    }
+}
+
+impl ListJournalEntries {
+    pub fn limit(&self, default: u64) -> u64 {
+        self.limit.unwrap_or(default)
+    }
+
+    /// Construit le filtre Mongo correspondant aux paramètres de la requête,
+    /// `today` servant de référence à la période relative.
+    pub fn filter(&self, today: NaiveDate) -> Result<Document, ListJournalEntryError> {
+        let mut filter = Document::new();
+
+        if let (Some(rate_min), Some(rate_max)) = (self.rate_min, self.rate_max) {
+            if rate_min > rate_max {
+                return Err(ListJournalEntryError::InvalidRateRange(rate_min, rate_max));
+            }
+        }
+        let mut rate = Document::new();
+        if let Some(rate_min) = self.rate_min {
+            rate.insert("$gte", rate_min);
+        }
+        if let Some(rate_max) = self.rate_max {
+            rate.insert("$lte", rate_max);
+        }
+        if !rate.is_empty() {
+            filter.insert("rate", rate);
+        }
+
+        let mut date = Document::new();
+        match (self.before, self.after) {
+            (Some(_), Some(_)) => return Err(ListJournalEntryError::ConflictingCursors),
+            (Some(before), None) => {
+                date.insert("$lt", before.to_string());
+            }
+            (None, Some(after)) => {
+                date.insert("$gt", after.to_string());
+            }
+            (None, None) => {}
+        }
+        if let Some(period) = &self.period {
+            let (first, last) = RelativePeriod::parse(period)
+                .ok_or_else(|| ListJournalEntryError::UnknownPeriod(period.clone()))?
+                .range(today);
+            date.insert("$gte", first.to_string());
+            date.insert("$lte", last.to_string());
+        }
+        if !date.is_empty() {
+            filter.insert("date", date);
+        }
+
+        match self.has_image {
+            Some(true) => {
+                filter.insert("image_url", doc! { "$exists": true, "$ne": null });
+            }
+            // `null` correspond aussi aux documents sans le champ
+            Some(false) => {
+                filter.insert("image_url", Bson::Null);
+            }
+            None => {}
+        }
+
+        match self.has_positive_highlight {
+            Some(true) => {
+                filter.insert("highlights.sentiment", "positive");
+            }
+            Some(false) => {
+                filter.insert("highlights.sentiment", doc! { "$ne": "positive" });
+            }
+            None => {}
+        }
+
+        Ok(filter)
+    }
+
+    /// L'estimation de Mongo porte sur toute la collection : elle ne sert que
+    /// sans filtre, et seulement si le compte exact n'est pas demandé.
+    pub fn estimates_count(&self, filter: &Document) -> bool {
+        !self.exact_count && filter.is_empty()
+    }
+
+    /// Après un curseur `after`, on lit vers le futur : les entrées les plus proches
+    /// du curseur d'abord, la page étant remise dans l'ordre décroissant ensuite.
+    pub fn sort(&self) -> Document {
+        if self.after.is_some() {
+            doc! { "date": 1 }
+        } else {
+            doc! { "date": -1 }
+        }
+    }
+}
+
+/// Convertit les documents lus un à un : un document qui ne correspond plus au
+/// schéma (migration partielle…) est ignoré et logué au lieu de faire échouer la liste.
+pub fn readable_entries(documents: Vec<Document>) -> Vec<JournalEntry> {
+    documents
+        .into_iter()
+        .filter_map(|document| {
+            let id = document.get("_id").cloned();
+            bson::from_document(document)
+                .map_err(|error| {
+                    tracing::warn!("Skipping unreadable journal entry {id:?}: {error}");
+                })
+                .ok()
+        })
+        .collect()
+}
+
 pub async fn list_journal_entries(
     Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
-) -> Result<Json<Vec<JournalEntry>>, ListJournalEntryError> {
-    Ok(Json(
+    Extension(cipher): Extension<Arc<EntryCipher>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    format: ResponseFormat,
+    extract::Query(query): extract::Query<ListJournalEntries>,
+) -> Result<Negotiated<EntriesPage>, ListJournalEntryError> {
+    let today = Utc::now().with_timezone(&config.timezone).date_naive();
+    let filter = query.filter(today)?;
+    let limit = query.limit(config.default_list_limit);
+    let count_is_estimate = query.estimates_count(&filter);
+    let total = if count_is_estimate {
+        mongo_entries.estimated_document_count(None).await
+    } else {
+        mongo_entries.count_documents(filter.clone(), None).await
+    }
+    .map_err(ListJournalEntryError::Mongo)?;
+    let documents = mongo_entries
+        .clone_with_type::<Document>()
+        .find(
+            filter,
+            FindOptions::builder()
+                .sort(query.sort())
+                .skip(query.offset)
+                .limit(limit as i64)
+                .build(),
+        )
+        .await
+        .map_err(ListJournalEntryError::Mongo)?
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(ListJournalEntryError::Mongo)?;
+    let mut data = readable_entries(documents);
+    if query.after.is_some() {
+        data.reverse();
+    }
+    let data = data
+        .into_iter()
+        .map(|entry| entry.decrypted(&cipher))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(ListJournalEntryError::Crypto)?;
+
+    if query.limit.is_none() && total > query.offset + limit {
+        tracing::warn!("Listing truncated to the default limit of {limit} entries out of {total}");
+    }
+
+    let page = Page {
+        count_is_estimate,
+        ..Page::new(data, total, Some(limit), query.offset)
+    };
+    Ok(format.respond(page.into()))
+}
+
+/// Date et note d'une entrée, pour les vues qui n'ont pas besoin du reste (heatmap).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EntryDate {
+    pub date: NaiveDate,
+    pub rate: f32,
+}
+
+/// Projection ne lisant que les champs de `EntryDate`.
+pub fn entry_date_projection() -> Document {
+    doc! { "_id": 0, "date": 1, "rate": 1 }
+}
+
+pub async fn list_entry_dates(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    format: ResponseFormat,
+) -> Result<Negotiated<Vec<EntryDate>>, ListJournalEntryError> {
+    Ok(format.respond(
         mongo_entries
-            .find(None, None)
+            .clone_with_type::<EntryDate>()
+            .find(
+                None,
+                FindOptions::builder()
+                    .projection(entry_date_projection())
+                    .sort(doc! { "date": 1 })
+                    .build(),
+            )
             .await
             .map_err(ListJournalEntryError::Mongo)?
             .try_collect()
@@ -135,6 +983,78 @@ pub async fn list_journal_entries(
     ))
 }
 
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    #[default]
+    Month,
+    Year,
+}
+
+impl GroupBy {
+    fn key(self, date: NaiveDate) -> String {
+        match self {
+            GroupBy::Month => date.format("%Y-%m").to_string(),
+            GroupBy::Year => date.format("%Y").to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct GroupedEntriesQuery {
+    #[serde(default)]
+    pub by: GroupBy,
+}
+
+/// Entrées d'un mois (`2024-03`), ou d'une année (`2024`) avec `by=year`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EntryGroup {
+    pub month: String,
+    pub entries: Vec<JournalEntry>,
+}
+
+/// Regroupe des entrées déjà triées par date décroissante, en gardant cet ordre.
+pub fn group_entries(entries: Vec<JournalEntry>, by: GroupBy) -> Vec<EntryGroup> {
+    let mut groups: Vec<EntryGroup> = Vec::new();
+    for entry in entries {
+        let key = by.key(entry.date);
+        match groups.last_mut() {
+            Some(group) if group.month == key => group.entries.push(entry),
+            _ => groups.push(EntryGroup {
+                month: key,
+                entries: vec![entry],
+            }),
+        }
+    }
+    groups
+}
+
+pub async fn list_grouped_entries(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(cipher): Extension<Arc<EntryCipher>>,
+    format: ResponseFormat,
+    extract::Query(query): extract::Query<GroupedEntriesQuery>,
+) -> Result<Negotiated<Vec<EntryGroup>>, ListJournalEntryError> {
+    let documents = mongo_entries
+        .clone_with_type::<Document>()
+        .find(
+            None,
+            FindOptions::builder().sort(doc! { "date": -1 }).build(),
+        )
+        .await
+        .map_err(ListJournalEntryError::Mongo)?
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(ListJournalEntryError::Mongo)?;
+    let entries = readable_entries(documents)
+        .into_iter()
+        .map(|entry| entry.decrypted(&cipher))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(ListJournalEntryError::Crypto)?;
+
+    Ok(format.respond(group_entries(entries, query.by)))
+}
+
 #[derive(Error, Debug, ErrorStatus)]
 pub enum DeleteJournalEntryError {
     #[error(transparent)]
@@ -150,17 +1070,1241 @@ pub struct DeleteJournalEntry {
     pub date: NaiveDate,
 }
 
+/// Supprime l'entrée en la gardant quelques secondes pour `POST /undo-delete`.
 pub async fn delete_journal_entry(
     Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(deleted): Extension<Arc<DeletedEntries>>,
     payload: Json<DeleteJournalEntry>,
 ) -> Result<(), DeleteJournalEntryError> {
-    let _ = mongo_entries
-        .delete_one(
+    if let Some(entry) = mongo_entries
+        .find_one_and_delete(
             bson::to_document(&payload.0).map_err(DeleteJournalEntryError::Bson)?,
             None,
         )
         .await
-        .map_err(DeleteJournalEntryError::Mongo)?;
+        .map_err(DeleteJournalEntryError::Mongo)?
+    {
+        deleted.remember(entry);
+    }
 
     Ok(())
 }
+
+/// Longueur maximale d'un tag saisi à la main, en caractères.
+pub const MAX_TAG_LENGTH: usize = 30;
+/// Nombre maximal de tags d'une entrée.
+pub const MAX_TAGS: usize = 20;
+
+#[derive(Error, Debug)]
+pub enum TagsError {
+    #[error("tags must not be empty")]
+    Empty,
+    #[error("tag \"{0}\" is longer than {MAX_TAG_LENGTH} characters")]
+    TooLong(String),
+    #[error("an entry cannot have more than {MAX_TAGS} tags, got {0}")]
+    TooMany(usize),
+}
+
+/// Tags en minuscules, sans espaces autour ni doublons, dans l'ordre de saisie.
+pub fn normalize_tags(tags: &[String]) -> Result<Vec<String>, TagsError> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        if tag.is_empty() {
+            return Err(TagsError::Empty);
+        }
+        if tag.chars().count() > MAX_TAG_LENGTH {
+            return Err(TagsError::TooLong(tag));
+        }
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+
+    if normalized.len() > MAX_TAGS {
+        return Err(TagsError::TooMany(normalized.len()));
+    }
+    Ok(normalized)
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum UpdateJournalEntryError {
+    #[error("the update must contain at least one field")]
+    #[status(StatusCode::BAD_REQUEST)]
+    Empty,
+    #[error("rate {0} is not between 0 and 10")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidRate(f32),
+    #[error(transparent)]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidTags(TagsError),
+    #[error(transparent)]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidCustomFields(CustomFieldError),
+    #[error("no journal entry for {0}")]
+    #[status(StatusCode::NOT_FOUND)]
+    NotFound(NaiveDate),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Bson(bson::ser::Error),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Crypto(CryptoError),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct UpdateJournalEntry {
+    pub rate: Option<f32>,
+    pub short_summary: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub image_url: Option<String>,
+    /// Champs personnalisés modifiés, les autres étant conservés.
+    pub custom_fields: Option<HashMap<String, Value>>,
+}
+
+impl UpdateJournalEntry {
+    /// Construit le `$set` ne contenant que les champs fournis.
+    pub fn update_document(&self) -> Result<Document, UpdateJournalEntryError> {
+        let mut set = Document::new();
+        if let Some(rate) = self.rate {
+            if !RATE_RANGE.contains(&rate) {
+                return Err(UpdateJournalEntryError::InvalidRate(rate));
+            }
+            set.insert("rate", rate);
+        }
+        if let Some(short_summary) = &self.short_summary {
+            set.insert("short_summary", short_summary);
+        }
+        if let Some(tags) = &self.tags {
+            set.insert(
+                "tags",
+                normalize_tags(tags).map_err(UpdateJournalEntryError::InvalidTags)?,
+            );
+        }
+        if let Some(image_url) = &self.image_url {
+            set.insert("image_url", image_url);
+        }
+        if let Some(custom_fields) = &self.custom_fields {
+            validate_custom_fields(custom_fields)
+                .map_err(UpdateJournalEntryError::InvalidCustomFields)?;
+            for (name, value) in custom_fields {
+                set.insert(
+                    format!("custom_fields.{name}"),
+                    bson::to_bson(value).map_err(UpdateJournalEntryError::Bson)?,
+                );
+            }
+        }
+        if set.is_empty() {
+            return Err(UpdateJournalEntryError::Empty);
+        }
+
+        set.insert(
+            "updated_at",
+            bson::to_bson(&Utc::now()).map_err(UpdateJournalEntryError::Bson)?,
+        );
+        Ok(doc! { "$set": set })
+    }
+}
+
+pub async fn update_journal_entry(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(revisions): Extension<Arc<Collection<EntryRevision>>>,
+    Extension(cipher): Extension<Arc<EntryCipher>>,
+    Path(date): Path<NaiveDate>,
+    Json(update): Json<UpdateJournalEntry>,
+) -> Result<Json<JournalEntry>, UpdateJournalEntryError> {
+    let update = update.update_document()?;
+    let previous = mongo_entries
+        .find_one(date_filter(date), None)
+        .await
+        .map_err(UpdateJournalEntryError::Mongo)?
+        .ok_or(UpdateJournalEntryError::NotFound(date))?;
+    // La modification et l'archivage de la version précédente sont atomiques
+    let revision = EntryRevision::of(previous);
+    let entry = in_transaction(mongo_entries.client(), "the update", |session| {
+        let (mongo_entries, revisions) = (mongo_entries.clone(), revisions.clone());
+        let (update, revision) = (update.clone(), revision.clone());
+        Box::pin(async move {
+            let options = FindOneAndUpdateOptions::builder()
+                .return_document(ReturnDocument::After)
+                .build();
+            let entry = match session {
+                Some(session) => {
+                    let entry = mongo_entries
+                        .find_one_and_update_with_session(
+                            date_filter(date),
+                            update,
+                            options,
+                            session,
+                        )
+                        .await?;
+                    insert_revision(&revisions, Some(session), &revision).await?;
+                    entry
+                }
+                None => {
+                    let entry = mongo_entries
+                        .find_one_and_update(date_filter(date), update, options)
+                        .await?;
+                    insert_revision(&revisions, None, &revision).await?;
+                    entry
+                }
+            };
+            Ok(entry)
+        })
+    })
+    .await
+    .map_err(UpdateJournalEntryError::Mongo)?
+    .ok_or(UpdateJournalEntryError::NotFound(date))?;
+    prune_revisions(&revisions, date).await;
+
+    Ok(Json(
+        entry
+            .decrypted(&cipher)
+            .map_err(UpdateJournalEntryError::Crypto)?,
+    ))
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum JournalEntryExistsError {
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct JournalEntryExists {
+    pub exists: bool,
+}
+
+pub async fn journal_entry_exists(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Path(date): Path<NaiveDate>,
+) -> Result<Json<JournalEntryExists>, JournalEntryExistsError> {
+    let count = mongo_entries
+        .count_documents(date_filter(date), CountOptions::builder().limit(1).build())
+        .await
+        .map_err(JournalEntryExistsError::Mongo)?;
+
+    Ok(Json(JournalEntryExists { exists: count > 0 }))
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum EntryByIdError {
+    #[error("invalid entry id \"{0}\"")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidId(String),
+    #[error("no journal entry with id {0}")]
+    #[status(StatusCode::NOT_FOUND)]
+    NotFound(String),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Crypto(CryptoError),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+/// Filtre Mongo d'une entrée par son identifiant.
+pub fn id_filter(id: &str) -> Result<Document, EntryByIdError> {
+    let id = ObjectId::parse_str(id).map_err(|_| EntryByIdError::InvalidId(id.to_string()))?;
+    Ok(doc! { "_id": id })
+}
+
+pub async fn get_journal_entry_by_id(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(cipher): Extension<Arc<EntryCipher>>,
+    Path(id): Path<String>,
+) -> Result<Json<JournalEntry>, EntryByIdError> {
+    let entry = mongo_entries
+        .find_one(id_filter(&id)?, None)
+        .await
+        .map_err(EntryByIdError::Mongo)?
+        .ok_or(EntryByIdError::NotFound(id))?;
+
+    Ok(Json(
+        entry.decrypted(&cipher).map_err(EntryByIdError::Crypto)?,
+    ))
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum LatestEntryError {
+    #[error("the journal has no entry yet")]
+    #[status(StatusCode::NOT_FOUND)]
+    NotFound,
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Crypto(CryptoError),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+/// Entrée la plus récente, lue seule plutôt qu'en chargeant la liste.
+pub async fn latest_entry(
+    mongo_entries: &Collection<JournalEntry>,
+) -> mongodb::error::Result<Option<JournalEntry>> {
+    mongo_entries
+        .find_one(
+            None,
+            FindOneOptions::builder().sort(doc! { "date": -1 }).build(),
+        )
+        .await
+}
+
+pub async fn get_latest_entry(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(cipher): Extension<Arc<EntryCipher>>,
+) -> Result<Json<JournalEntry>, LatestEntryError> {
+    let entry = latest_entry(&mongo_entries)
+        .await
+        .map_err(LatestEntryError::Mongo)?
+        .ok_or(LatestEntryError::NotFound)?;
+
+    Ok(Json(
+        entry.decrypted(&cipher).map_err(LatestEntryError::Crypto)?,
+    ))
+}
+
+/// Supprime l'entrée par son identifiant, annulable comme la suppression par date.
+pub async fn delete_journal_entry_by_id(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(deleted): Extension<Arc<DeletedEntries>>,
+    Path(id): Path<String>,
+) -> Result<(), EntryByIdError> {
+    let entry = mongo_entries
+        .find_one_and_delete(id_filter(&id)?, None)
+        .await
+        .map_err(EntryByIdError::Mongo)?
+        .ok_or(EntryByIdError::NotFound(id))?;
+
+    deleted.remember(entry);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use axum::{extract::Query, response::IntoResponse, Json};
+    use chrono::{Datelike, NaiveDate, Utc};
+    use futures_util::TryStreamExt;
+    use http_body_util::BodyExt;
+    use mongodb::{
+        bson::{self, doc, oid::ObjectId},
+        options::IndexOptions,
+        IndexModel,
+    };
+    use serde_json::json;
+
+    use crate::{
+        config::AppConfig,
+        crypto::EntryCipher,
+        highlights::Highlight,
+        prompt::ParseEntryError,
+        revisions::EntryRevision,
+        testing::{mock_chat_completion, mock_openai},
+        usage::TokenUsage,
+    };
+
+    use super::{
+        analyze_entry, date_filter, entry_date_projection, group_entries, id_filter, latest_entry,
+        normalize_tags, prepare_journal_entry, process_journal_entry, processing_time_header,
+        readable_entries, upsert_update, CreateJournalEntry, CreateJournalEntryError,
+        CreateJournalEntryQuery, EntriesPage, EntryByIdError, EntryDate, GroupBy, JournalEntry,
+        JournalEntryValidationError, ListJournalEntries, ListJournalEntryError, Page, TagsError,
+        TopicRating, UpdateJournalEntry, UpdateJournalEntryError, DEFAULT_LIST_LIMIT, MAX_TAGS,
+        MAX_TAG_LENGTH,
+    };
+
+    #[test]
+    fn deserialize_tags_as_list_or_string() {
+        let tags = |tags: serde_json::Value| {
+            serde_json::from_value::<JournalEntry>(json!({
+                "date": "2024-01-24",
+                "rate": 6.0,
+                "short_summary": "",
+                "tags": tags,
+            }))
+            .map(|entry| entry.tags)
+        };
+
+        assert_eq!(
+            tags(json!(["travail", "famille"])).unwrap(),
+            ["travail", "famille"]
+        );
+        assert_eq!(
+            tags(json!("travail, famille")).unwrap(),
+            ["travail", "famille"]
+        );
+        assert_eq!(tags(json!(" sport ,, ")).unwrap(), ["sport"]);
+        assert!(tags(json!("")).unwrap().is_empty());
+        assert!(tags(json!(42)).is_err());
+
+        // Les entrées stockées se relisent toujours
+        let stored = bson::to_document(&JournalEntry {
+            tags: vec!["travail".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            bson::from_document::<JournalEntry>(stored).unwrap().tags,
+            ["travail"]
+        );
+    }
+
+    #[test]
+    fn date_filter_matches_stored_entries() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 24).unwrap();
+        let stored = bson::to_document(&JournalEntry {
+            date,
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(date_filter(date).get("date"), stored.get("date"));
+        assert_ne!(
+            date_filter(NaiveDate::from_ymd_opt(2024, 1, 25).unwrap()).get("date"),
+            stored.get("date")
+        );
+    }
+
+    #[test]
+    fn upsert_sets_created_at_only_on_insert() {
+        let now = Utc::now();
+        let update = upsert_update(
+            &JournalEntry {
+                id: ObjectId::new().to_hex(),
+                date: NaiveDate::from_ymd_opt(2024, 1, 24).unwrap(),
+                word_count: Some(3),
+                ..Default::default()
+            },
+            now,
+        )
+        .unwrap();
+
+        let set = update.get_document("$set").unwrap();
+        assert_eq!(set.get_str("date").unwrap(), "2024-01-24");
+        assert!(!set.contains_key("_id"));
+        assert!(!set.contains_key("created_at"));
+        assert_eq!(
+            update
+                .get_document("$setOnInsert")
+                .unwrap()
+                .get("created_at"),
+            Some(&bson::to_bson(&now).unwrap())
+        );
+        let unset = update.get_document("$unset").unwrap();
+        assert!(unset.contains_key("style_hint"));
+        assert!(!unset.contains_key("word_count"));
+    }
+
+    async fn test_database() -> mongodb::Database {
+        dotenvy::dotenv().ok();
+        let options =
+            crate::mongo_client_options(&std::env::var("MONGO").unwrap(), crate::DEFAULT_APP_NAME)
+                .await
+                .unwrap();
+        mongodb::Client::with_options(options)
+            .unwrap()
+            .database("journai_test")
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server in MONGO"]
+    async fn concurrent_creations_of_the_same_day() {
+        let database = test_database().await;
+        let entries = database.collection::<JournalEntry>("concurrent_entries");
+        let revisions = database.collection::<EntryRevision>("concurrent_entry_revisions");
+        entries.drop(None).await.unwrap();
+        entries
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "date": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                None,
+            )
+            .await
+            .unwrap();
+
+        let openai = mock_openai(axum::Router::new()).await;
+        let (config, cipher) = (AppConfig::default(), EntryCipher::default());
+        let create = || {
+            process_journal_entry(
+                &entries,
+                &revisions,
+                &openai,
+                None,
+                &config,
+                &cipher,
+                create_entry(),
+                true,
+            )
+        };
+        let (first, second) = tokio::join!(create(), create());
+        let (first, second) = (first.unwrap(), second.unwrap());
+
+        assert_eq!(
+            entries
+                .count_documents(date_filter(first.date), None)
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(first.created_at.is_some());
+        assert_eq!(first.created_at, second.created_at);
+
+        // L'identifiant renvoyé est celui de l'entrée enregistrée
+        assert!(!first.id.is_empty());
+        assert_eq!(first.id, second.id);
+        let stored = entries
+            .find_one(date_filter(first.date), None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.id, first.id);
+    }
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, 14).unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server in MONGO"]
+    async fn latest_of_several_entries() {
+        let entries = test_database()
+            .await
+            .collection::<JournalEntry>("latest_entries");
+        entries.drop(None).await.unwrap();
+        for day in [12, 24, 3] {
+            entries
+                .insert_one(
+                    JournalEntry {
+                        date: NaiveDate::from_ymd_opt(2024, 1, day).unwrap(),
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+
+        let latest = latest_entry(&entries).await.unwrap().unwrap();
+        assert_eq!(latest.date, NaiveDate::from_ymd_opt(2024, 1, 24).unwrap());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server in MONGO"]
+    async fn no_latest_entry_in_an_empty_journal() {
+        let entries = test_database()
+            .await
+            .collection::<JournalEntry>("latest_empty_entries");
+        entries.drop(None).await.unwrap();
+
+        assert_eq!(latest_entry(&entries).await.unwrap(), None);
+    }
+
+    #[test]
+    fn filter_by_rate_range() {
+        let query = ListJournalEntries {
+            rate_min: Some(6.0),
+            rate_max: Some(9.5),
+            ..Default::default()
+        };
+        assert_eq!(
+            query.filter(today()).unwrap(),
+            doc! { "rate": { "$gte": 6.0, "$lte": 9.5 } }
+        );
+
+        let query = ListJournalEntries {
+            rate_min: Some(7.0),
+            ..Default::default()
+        };
+        assert_eq!(
+            query.filter(today()).unwrap(),
+            doc! { "rate": { "$gte": 7.0 } }
+        );
+
+        assert_eq!(
+            ListJournalEntries::default().filter(today()).unwrap(),
+            doc! {}
+        );
+    }
+
+    #[test]
+    fn filter_by_image() {
+        let query = ListJournalEntries {
+            has_image: Some(true),
+            rate_min: Some(7.0),
+            ..Default::default()
+        };
+        assert_eq!(
+            query.filter(today()).unwrap(),
+            doc! {
+                "rate": { "$gte": 7.0 },
+                "image_url": { "$exists": true, "$ne": null }
+            }
+        );
+
+        let query = ListJournalEntries {
+            has_image: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(query.filter(today()).unwrap(), doc! { "image_url": null });
+    }
+
+    #[test]
+    fn filter_by_positive_highlight() {
+        let query = ListJournalEntries {
+            has_positive_highlight: Some(true),
+            period: Some("this-month".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            query.filter(today()).unwrap(),
+            doc! {
+                "date": { "$gte": "2024-03-01", "$lte": "2024-03-31" },
+                "highlights.sentiment": "positive"
+            }
+        );
+
+        let query = ListJournalEntries {
+            has_positive_highlight: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(
+            query.filter(today()).unwrap(),
+            doc! { "highlights.sentiment": { "$ne": "positive" } }
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server in MONGO"]
+    async fn list_entries_with_positive_highlight() {
+        let entries = test_database()
+            .await
+            .collection::<JournalEntry>("positive_highlight_entries");
+        entries.drop(None).await.unwrap();
+        let highlight = |sentiment: &str| Highlight {
+            text: "Un moment".to_string(),
+            sentiment: sentiment.to_string(),
+            position: None,
+        };
+        for (day, highlights) in [
+            (1, vec![highlight("negative"), highlight("positive")]),
+            (2, vec![highlight("negative")]),
+            (3, vec![]),
+            (20, vec![highlight("positive")]),
+        ] {
+            entries
+                .insert_one(
+                    JournalEntry {
+                        date: NaiveDate::from_ymd_opt(2024, 3, day).unwrap(),
+                        highlights,
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+
+        let dates = |query: ListJournalEntries| {
+            let entries = entries.clone();
+            async move {
+                let mut dates: Vec<u32> = entries
+                    .find(query.filter(today()).unwrap(), None)
+                    .await
+                    .unwrap()
+                    .map_ok(|entry| entry.date.day())
+                    .try_collect()
+                    .await
+                    .unwrap();
+                dates.sort();
+                dates
+            }
+        };
+        let positive = |has_positive_highlight| ListJournalEntries {
+            has_positive_highlight: Some(has_positive_highlight),
+            ..Default::default()
+        };
+        assert_eq!(dates(positive(true)).await, [1, 20]);
+        assert_eq!(dates(positive(false)).await, [2, 3]);
+        assert_eq!(
+            dates(ListJournalEntries {
+                before: NaiveDate::from_ymd_opt(2024, 3, 10),
+                ..positive(true)
+            })
+            .await,
+            [1]
+        );
+    }
+
+    #[test]
+    fn reject_inverted_rate_range() {
+        let query = ListJournalEntries {
+            rate_min: Some(8.0),
+            rate_max: Some(2.0),
+            ..Default::default()
+        };
+        assert!(matches!(
+            query.filter(today()),
+            Err(ListJournalEntryError::InvalidRateRange(..))
+        ));
+    }
+
+    #[test]
+    fn update_custom_fields() {
+        let update = UpdateJournalEntry {
+            custom_fields: Some(
+                [("sleep_hours".to_string(), serde_json::json!(7.5))]
+                    .into_iter()
+                    .collect(),
+            ),
+            ..Default::default()
+        };
+        let set = update.update_document().unwrap();
+        let set = set.get_document("$set").unwrap();
+        assert_eq!(set.get_f64("custom_fields.sleep_hours").unwrap(), 7.5);
+
+        let update = UpdateJournalEntry {
+            custom_fields: Some(
+                [("sleep.hours".to_string(), serde_json::json!(7.5))]
+                    .into_iter()
+                    .collect(),
+            ),
+            ..Default::default()
+        };
+        assert!(matches!(
+            update.update_document(),
+            Err(UpdateJournalEntryError::InvalidCustomFields(_))
+        ));
+    }
+
+    #[test]
+    fn update_single_field() {
+        let update = UpdateJournalEntry {
+            rate: Some(8.0),
+            ..Default::default()
+        }
+        .update_document()
+        .unwrap();
+
+        let set = update.get_document("$set").unwrap();
+        assert_eq!(set.get_f64("rate"), Ok(8.0));
+        assert!(set.contains_key("updated_at"));
+        assert!(!set.contains_key("short_summary"));
+        assert!(!set.contains_key("tags"));
+    }
+
+    #[test]
+    fn reject_empty_update() {
+        let update = serde_json::from_str::<UpdateJournalEntry>("{}").unwrap();
+        assert!(matches!(
+            update.update_document(),
+            Err(UpdateJournalEntryError::Empty)
+        ));
+    }
+
+    #[test]
+    fn normalize_manual_tags() {
+        let tags = ["  Sport ", "lecture", "SPORT", "Lecture", "cinéma"].map(String::from);
+        assert_eq!(
+            normalize_tags(&tags).unwrap(),
+            vec!["sport", "lecture", "cinéma"]
+        );
+
+        assert!(matches!(
+            normalize_tags(&["sport".to_string(), "   ".to_string()]),
+            Err(TagsError::Empty)
+        ));
+        assert!(matches!(
+            normalize_tags(&["a".repeat(MAX_TAG_LENGTH + 1)]),
+            Err(TagsError::TooLong(_))
+        ));
+        assert!(normalize_tags(&["é".repeat(MAX_TAG_LENGTH)]).is_ok());
+
+        let many: Vec<String> = (0..=MAX_TAGS).map(|i| format!("tag {i}")).collect();
+        assert!(matches!(
+            normalize_tags(&many),
+            Err(TagsError::TooMany(count)) if count == MAX_TAGS + 1
+        ));
+        // Les doublons ne comptent qu'une fois dans la limite
+        let duplicates: Vec<String> = (0..=MAX_TAGS).map(|_| "sport".to_string()).collect();
+        assert_eq!(normalize_tags(&duplicates).unwrap(), vec!["sport"]);
+    }
+
+    #[test]
+    fn reject_invalid_tags_in_update() {
+        let update = UpdateJournalEntry {
+            tags: Some(vec!["".to_string()]),
+            ..Default::default()
+        };
+        assert!(matches!(
+            update.update_document(),
+            Err(UpdateJournalEntryError::InvalidTags(TagsError::Empty))
+        ));
+    }
+
+    #[test]
+    fn page_metadata() {
+        let page = Page::new(vec![1, 2, 3], 10, Some(3), 3);
+        assert_eq!(page.total, 10);
+        assert_eq!(page.limit, Some(3));
+        assert_eq!(page.offset, 3);
+        assert!(page.has_more);
+
+        let json = serde_json::to_value(Page::new(vec![1, 2], 8, Some(3), 6)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "data": [1, 2],
+                "total": 8,
+                "limit": 3,
+                "offset": 6,
+                "has_more": false,
+                "count_is_estimate": false
+            })
+        );
+    }
+
+    #[test]
+    fn choose_counting_mode() {
+        let today = today();
+        let query = ListJournalEntries::default();
+        assert!(query.estimates_count(&query.filter(today).unwrap()));
+
+        let query = ListJournalEntries {
+            exact_count: true,
+            ..Default::default()
+        };
+        assert!(!query.estimates_count(&query.filter(today).unwrap()));
+
+        // Une estimation ignorerait le filtre
+        let query = ListJournalEntries {
+            rate_min: Some(7.0),
+            ..Default::default()
+        };
+        assert!(!query.estimates_count(&query.filter(today).unwrap()));
+    }
+
+    #[test]
+    fn average_rate_of_page() {
+        let data = [6.0, 7.5, 9.0]
+            .into_iter()
+            .map(|rate| JournalEntry {
+                rate,
+                ..Default::default()
+            })
+            .collect();
+        let page = EntriesPage::from(Page::new(data, 10, Some(3), 0));
+        assert_eq!(page.page_average_rate, Some(7.5));
+        assert_eq!(page.page.data.len(), 3);
+
+        let json =
+            serde_json::to_value(EntriesPage::from(Page::new(vec![], 10, Some(3), 12))).unwrap();
+        assert_eq!(json["page_average_rate"], serde_json::Value::Null);
+        assert_eq!(json["total"], 10);
+    }
+
+    #[test]
+    fn apply_default_limit() {
+        assert_eq!(
+            ListJournalEntries::default().limit(DEFAULT_LIST_LIMIT),
+            DEFAULT_LIST_LIMIT
+        );
+        let query = ListJournalEntries {
+            limit: Some(20),
+            ..Default::default()
+        };
+        assert_eq!(query.limit(DEFAULT_LIST_LIMIT), 20);
+
+        let default = ListJournalEntries::default();
+        let data = vec![0; default.limit(DEFAULT_LIST_LIMIT) as usize];
+        let page = Page::new(
+            data,
+            50_000,
+            Some(default.limit(DEFAULT_LIST_LIMIT)),
+            default.offset,
+        );
+        assert_eq!(page.data.len(), 1000);
+        assert_eq!(page.limit, Some(1000));
+        assert!(page.has_more);
+    }
+
+    fn create_entry() -> CreateJournalEntry {
+        CreateJournalEntry {
+            name: "Alice".to_string(),
+            summary: "J'ai couru 10km".to_string(),
+            date: NaiveDate::from_ymd_opt(2024, 1, 24).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn analyze_with_gpt() {
+        let openai = mock_chat_completion(
+            r#"{"date":"2024-01-24","rate":3.0,"short_summary":"Course difficile","tags":["sport"]}"#,
+        )
+        .await;
+
+        let entry = analyze_entry(&openai, None, &AppConfig::default(), &create_entry(), false)
+            .await
+            .unwrap();
+        assert_eq!(entry.rate, 3.0);
+        assert_eq!(entry.short_summary, "Course difficile");
+        assert_eq!(
+            entry.tokens,
+            Some(TokenUsage {
+                prompt_tokens: 120,
+                completion_tokens: 30
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn ignore_server_fields_from_gpt() {
+        let openai = mock_chat_completion(
+            r#"{"rate":3.0,"short_summary":"Course difficile","tags":["sport"],"text_compressed":true,"image_url":"https://example.com/a.png"}"#,
+        )
+        .await;
+
+        let result =
+            prepare_journal_entry(&openai, None, &AppConfig::default(), create_entry(), false)
+                .await;
+        assert!(matches!(
+            result,
+            Err(CreateJournalEntryError::Parse(ParseEntryError::Schema(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn skip_gpt_when_analysis_provided() {
+        let openai = mock_chat_completion(
+            r#"{"date":"2024-01-24","rate":3.0,"short_summary":"Course difficile","tags":["sport"]}"#,
+        )
+        .await;
+        let journal_entry = CreateJournalEntry {
+            rate: Some(8.0),
+            short_summary: Some("Belle course".to_string()),
+            ..create_entry()
+        };
+
+        let entry = analyze_entry(&openai, None, &AppConfig::default(), &journal_entry, false)
+            .await
+            .unwrap();
+        assert_eq!(entry.rate, 8.0);
+        assert_eq!(entry.short_summary, "Belle course");
+        assert_eq!(entry.date, journal_entry.date);
+
+        let journal_entry = CreateJournalEntry {
+            rate: Some(12.0),
+            ..journal_entry
+        };
+        assert!(matches!(
+            analyze_entry(&openai, None, &AppConfig::default(), &journal_entry, false).await,
+            Err(CreateJournalEntryError::InvalidEntry(_))
+        ));
+    }
+
+    #[test]
+    fn reject_non_finite_rates() {
+        for rate in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            let entry = JournalEntry {
+                rate,
+                ..Default::default()
+            };
+            assert!(matches!(
+                entry.validate(),
+                Err(JournalEntryValidationError::NonFiniteRate(_))
+            ));
+
+            let entry = JournalEntry {
+                rate: 5.0,
+                topic_ratings: vec![TopicRating {
+                    topic: "sport".to_string(),
+                    rate,
+                }],
+                ..Default::default()
+            };
+            assert!(matches!(
+                entry.validate(),
+                Err(JournalEntryValidationError::NonFiniteTopicRate(_, _))
+            ));
+
+            let import = CreateJournalEntry {
+                rate: Some(rate),
+                short_summary: Some("Belle course".to_string()),
+                ..create_entry()
+            };
+            assert!(matches!(
+                import.provided_analysis(),
+                Some(Err(JournalEntryValidationError::NonFiniteRate(_)))
+            ));
+        }
+        assert_eq!(
+            JournalEntryValidationError::NonFiniteRate(f32::NAN).to_string(),
+            "rate must be a finite number, got NaN"
+        );
+    }
+
+    #[tokio::test]
+    async fn empty_gpt_content_is_no_output() {
+        for content in ["", "   "] {
+            let openai = mock_chat_completion(content).await;
+            assert!(matches!(
+                analyze_entry(&openai, None, &AppConfig::default(), &create_entry(), false).await,
+                Err(CreateJournalEntryError::NoOutput)
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn expose_processing_time() {
+        let start = Instant::now() - Duration::from_millis(15);
+        let entry = JournalEntry {
+            short_summary: "Belle course".to_string(),
+            ..Default::default()
+        };
+
+        let response = (processing_time_header(start), Json(entry.clone())).into_response();
+        let elapsed: u64 = response.headers()["X-Processing-Time-Ms"]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(elapsed >= 15);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(
+            serde_json::from_slice::<JournalEntry>(&body).unwrap(),
+            entry
+        );
+    }
+
+    #[test]
+    fn paginate_towards_the_future() {
+        let after = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+        let query = ListJournalEntries {
+            after: Some(after),
+            limit: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(
+            query.filter(today()).unwrap(),
+            doc! { "date": { "$gt": "2024-03-10" } }
+        );
+        assert_eq!(query.sort(), doc! { "date": 1 });
+
+        let query = ListJournalEntries {
+            before: Some(after),
+            ..Default::default()
+        };
+        assert_eq!(
+            query.filter(today()).unwrap(),
+            doc! { "date": { "$lt": "2024-03-10" } }
+        );
+        assert_eq!(query.sort(), doc! { "date": -1 });
+    }
+
+    #[test]
+    fn filter_by_relative_period() {
+        let query = ListJournalEntries {
+            period: Some("last-week".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            query.filter(today()).unwrap(),
+            doc! { "date": { "$gte": "2024-03-04", "$lte": "2024-03-10" } }
+        );
+
+        let query = ListJournalEntries {
+            period: Some("this-month".to_string()),
+            before: Some(NaiveDate::from_ymd_opt(2024, 3, 10).unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(
+            query.filter(today()).unwrap(),
+            doc! { "date": { "$lt": "2024-03-10", "$gte": "2024-03-01", "$lte": "2024-03-31" } }
+        );
+
+        let query = ListJournalEntries {
+            period: Some("next-week".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            query.filter(today()),
+            Err(ListJournalEntryError::UnknownPeriod(period)) if period == "next-week"
+        ));
+    }
+
+    #[test]
+    fn reject_both_cursors() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+        let query = ListJournalEntries {
+            before: Some(date),
+            after: Some(date),
+            ..Default::default()
+        };
+        assert!(matches!(
+            query.filter(today()),
+            Err(ListJournalEntryError::ConflictingCursors)
+        ));
+    }
+
+    #[tokio::test]
+    async fn conflict_when_replace_is_disabled() {
+        let Query(query) =
+            Query::<CreateJournalEntryQuery>::try_from_uri(&"/?replace=false".parse().unwrap())
+                .unwrap();
+        assert!(query.rejects_existing());
+        assert!(!CreateJournalEntryQuery::default().rejects_existing());
+
+        let response =
+            CreateJournalEntryError::AlreadyExists(NaiveDate::from_ymd_opt(2024, 3, 10).unwrap())
+                .into_response();
+        assert_eq!(response.status(), 409);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert!(String::from_utf8_lossy(&body)
+            .contains("an entry already exists for 2024-03-10, use PATCH /entry/2024-03-10"));
+    }
+
+    #[test]
+    fn dates_without_summary() {
+        assert!(!entry_date_projection().contains_key("short_summary"));
+
+        let json = serde_json::to_value(EntryDate {
+            date: NaiveDate::from_ymd_opt(2024, 1, 24).unwrap(),
+            rate: 7.5,
+        })
+        .unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "date": "2024-01-24", "rate": 7.5 })
+        );
+    }
+
+    #[test]
+    fn skip_unreadable_documents() {
+        let valid = |day: u32| {
+            bson::to_document(&JournalEntry {
+                date: NaiveDate::from_ymd_opt(2024, 1, day).unwrap(),
+                rate: 6.0,
+                ..Default::default()
+            })
+            .unwrap()
+        };
+        let documents = vec![
+            valid(24),
+            doc! { "date": "2024-01-25", "rate": "huit", "short_summary": "", "tags": [] },
+            valid(26),
+        ];
+
+        let entries = readable_entries(documents);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0].date,
+            NaiveDate::from_ymd_opt(2024, 1, 24).unwrap()
+        );
+        assert_eq!(
+            entries[1].date,
+            NaiveDate::from_ymd_opt(2024, 1, 26).unwrap()
+        );
+    }
+
+    #[test]
+    fn read_entry_by_id() {
+        let id = ObjectId::new();
+        let document =
+            doc! { "_id": id, "date": "2024-01-24", "rate": 7.0, "short_summary": "", "tags": [] };
+        let entry: JournalEntry = bson::from_document(document.clone()).unwrap();
+        assert_eq!(entry.id, id.to_hex());
+
+        assert_eq!(id_filter(&entry.id).unwrap(), doc! { "_id": id });
+        assert!(matches!(
+            id_filter("2024-01-24"),
+            Err(EntryByIdError::InvalidId(_))
+        ));
+        assert_eq!(serde_json::to_value(&entry).unwrap()["_id"], id.to_hex());
+        let json: JournalEntry =
+            serde_json::from_value(serde_json::to_value(&entry).unwrap()).unwrap();
+        assert_eq!(json.id, entry.id);
+
+        let stored = bson::to_raw_document_buf(&entry).unwrap();
+        assert_eq!(stored.get_object_id("_id").unwrap(), id);
+        let unsaved = bson::to_document(&JournalEntry::default()).unwrap();
+        assert!(!unsaved.contains_key("_id"));
+    }
+
+    #[tokio::test]
+    async fn store_entry_timezone() {
+        let openai = mock_openai(axum::Router::new()).await;
+        let config = AppConfig::default();
+        let travelling = CreateJournalEntry {
+            timezone: Some("America/New_York".to_string()),
+            ..create_entry()
+        };
+
+        let entry = prepare_journal_entry(&openai, None, &config, travelling, true)
+            .await
+            .unwrap();
+        assert_eq!(entry.timezone, "America/New_York");
+        let stored: JournalEntry = bson::from_document(bson::to_document(&entry).unwrap()).unwrap();
+        assert_eq!(stored.timezone, "America/New_York");
+        let update = upsert_update(&entry, Utc::now()).unwrap();
+        assert_eq!(
+            update
+                .get_document("$set")
+                .unwrap()
+                .get_str("timezone")
+                .unwrap(),
+            "America/New_York"
+        );
+
+        let entry = prepare_journal_entry(&openai, None, &config, create_entry(), true)
+            .await
+            .unwrap();
+        assert_eq!(entry.timezone, "Europe/Paris");
+
+        let older: JournalEntry = bson::from_document(
+            doc! { "date": "2024-01-24", "rate": 7.0, "short_summary": "", "tags": [] },
+        )
+        .unwrap();
+        assert_eq!(older.timezone, "Europe/Paris");
+
+        let invalid = CreateJournalEntry {
+            timezone: Some("Mars/Olympus".to_string()),
+            ..create_entry()
+        };
+        assert!(matches!(
+            prepare_journal_entry(&openai, None, &config, invalid, true).await,
+            Err(CreateJournalEntryError::InvalidTimezone(timezone)) if timezone == "Mars/Olympus"
+        ));
+    }
+
+    #[test]
+    fn group_entries_by_month() {
+        let entries: Vec<JournalEntry> = [(3, 10), (3, 2), (2, 28), (2, 5)]
+            .into_iter()
+            .map(|(month, day)| JournalEntry {
+                date: NaiveDate::from_ymd_opt(2024, month, day).unwrap(),
+                ..Default::default()
+            })
+            .collect();
+
+        let groups = group_entries(entries.clone(), GroupBy::Month);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].month, "2024-03");
+        assert_eq!(groups[0].entries, entries[..2]);
+        assert_eq!(groups[1].month, "2024-02");
+        assert_eq!(groups[1].entries, entries[2..]);
+
+        let groups = group_entries(entries, GroupBy::Year);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].month, "2024");
+        assert_eq!(groups[0].entries.len(), 4);
+    }
+}
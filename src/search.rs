@@ -0,0 +1,186 @@
+use std::sync::Arc;
+
+use axum::{extract::Query, http::StatusCode, Extension, Json};
+use axum_thiserror::ErrorStatus;
+use futures_util::TryStreamExt;
+use mongodb::Collection;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::crypto::{CryptoError, EntryCipher};
+use crate::routes::JournalEntry;
+
+/// Nombre maximal de fautes tolérées par mot recherché.
+pub const MAX_DISTANCE: usize = 2;
+
+#[derive(Deserialize, Debug, Default)]
+pub struct SearchQuery {
+    pub q: String,
+    #[serde(default)]
+    pub fuzzy: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub entry: JournalEntry,
+    /// Somme des fautes pour les mots recherchés, 0 pour une correspondance exacte.
+    pub distance: usize,
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum SearchError {
+    #[error("the search query must not be empty")]
+    #[status(StatusCode::BAD_REQUEST)]
+    EmptyQuery,
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Crypto(CryptoError),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+/// Distance de Levenshtein entre deux mots, en caractères.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Fautes tolérées pour un mot : aucune pour les mots très courts, qui
+/// correspondraient sinon à presque tout.
+fn tolerated_distance(word: &str) -> usize {
+    (word.chars().count() / 4).min(MAX_DISTANCE)
+}
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// Distance de l'entrée à la recherche, `None` si un des mots n'y figure pas,
+/// même approximativement.
+pub fn match_distance(entry: &JournalEntry, query: &str, fuzzy: bool) -> Option<usize> {
+    let text = [
+        entry.short_summary.as_str(),
+        entry.raw_text.as_deref().unwrap_or_default(),
+        &entry.tags.join(" "),
+    ]
+    .join(" ");
+    let candidates: Vec<String> = words(&text).collect();
+
+    words(query).try_fold(0, |total, word| {
+        let best = if fuzzy {
+            candidates
+                .iter()
+                .map(|candidate| levenshtein(&word, candidate))
+                .min()
+                .filter(|distance| *distance <= tolerated_distance(&word))
+        } else {
+            candidates.contains(&word).then_some(0)
+        };
+        best.map(|distance| total + distance)
+    })
+}
+
+/// Entrées correspondant à la recherche, les plus proches d'abord puis les plus récentes.
+pub fn search_entries(entries: Vec<JournalEntry>, query: &str, fuzzy: bool) -> Vec<SearchResult> {
+    let mut results: Vec<SearchResult> = entries
+        .into_iter()
+        .filter_map(|entry| {
+            match_distance(&entry, query, fuzzy).map(|distance| SearchResult { entry, distance })
+        })
+        .collect();
+    results.sort_by(|a, b| {
+        a.distance
+            .cmp(&b.distance)
+            .then_with(|| b.entry.date.cmp(&a.entry.date))
+    });
+    results
+}
+
+/// Recherche par mots-clés dans les résumés et les textes. Les textes étant
+/// chiffrés au repos, la comparaison se fait après déchiffrement, hors de Mongo.
+pub async fn search(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(cipher): Extension<Arc<EntryCipher>>,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<Vec<SearchResult>>, SearchError> {
+    if words(&query.q).next().is_none() {
+        return Err(SearchError::EmptyQuery);
+    }
+
+    let entries = mongo_entries
+        .find(None, None)
+        .await
+        .map_err(SearchError::Mongo)?
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(SearchError::Mongo)?
+        .into_iter()
+        .map(|entry| entry.decrypted(&cipher))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(SearchError::Crypto)?;
+    Ok(Json(search_entries(entries, &query.q, query.fuzzy)))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Datelike, NaiveDate};
+
+    use crate::routes::JournalEntry;
+
+    use super::{levenshtein, search_entries};
+
+    fn entry(day: u32, summary: &str) -> JournalEntry {
+        JournalEntry {
+            date: NaiveDate::from_ymd_opt(2024, 3, day).unwrap(),
+            short_summary: summary.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn levenshtein_distance() {
+        assert_eq!(levenshtein("anniversaire", "anniversaire"), 0);
+        assert_eq!(levenshtein("anniverssaire", "anniversaire"), 1);
+        assert_eq!(levenshtein("chat", "chien"), 3);
+        assert_eq!(levenshtein("", "été"), 3);
+    }
+
+    #[test]
+    fn fuzzy_search_with_typo() {
+        let entries = vec![
+            entry(1, "Anniversaire de Léa, super soirée"),
+            entry(2, "Journée de révisions"),
+            entry(3, "Cadeau pour l'anniversaire de maman"),
+        ];
+
+        assert!(search_entries(entries.clone(), "anniverssaire", false).is_empty());
+
+        let results = search_entries(entries, "anniverssaire", true);
+        let dates: Vec<u32> = results
+            .iter()
+            .map(|result| result.entry.date.day())
+            .collect();
+        assert_eq!(dates, vec![3, 1]);
+        assert!(results.iter().all(|result| result.distance == 1));
+    }
+
+    #[test]
+    fn limit_fuzzy_distance() {
+        let entries = vec![entry(1, "Journée au parc"), entry(2, "Soirée au port")];
+        let results = search_entries(entries, "parc", true);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].entry.short_summary, "Journée au parc");
+    }
+}
@@ -0,0 +1,271 @@
+use std::sync::Arc;
+
+use async_openai::{
+    config::OpenAIConfig, error::OpenAIError, types::CreateEmbeddingRequestArgs, Client,
+};
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    Extension, Json,
+};
+use axum_thiserror::ErrorStatus;
+use chrono::NaiveDate;
+use futures_util::TryStreamExt;
+use mongodb::{bson::doc, options::FindOptions, Collection};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::crypto::{CryptoError, EntryCipher};
+use crate::routes::{date_filter, JournalEntry};
+use crate::usage::TokenUsage;
+
+pub const EMBEDDING_MODEL: &str = "text-embedding-3-small";
+
+/// Nombre d'entrées récentes comparées à une nouvelle entrée.
+pub const RECENT_ENTRIES_COMPARED: i64 = 30;
+/// Seuil de similarité par défaut au-delà duquel une entrée est signalée comme proche.
+pub const DEFAULT_SIMILARITY_THRESHOLD: f32 = 0.95;
+
+/// Embedding du texte d'une entrée, pour la recherche par similarité, avec les
+/// tokens consommés pour le calculer.
+pub async fn embed_text(
+    openai: &Client<OpenAIConfig>,
+    model: &str,
+    text: &str,
+) -> Result<Option<(Vec<f32>, TokenUsage)>, OpenAIError> {
+    if text.trim().is_empty() {
+        return Ok(None);
+    }
+
+    let response = openai
+        .embeddings()
+        .create(
+            CreateEmbeddingRequestArgs::default()
+                .model(model)
+                .input(text)
+                .build()?,
+        )
+        .await?;
+    let tokens = TokenUsage::from(&response.usage);
+    Ok(response
+        .data
+        .into_iter()
+        .next()
+        .map(|data| (data.embedding, tokens)))
+}
+
+/// Similarité cosinus entre deux vecteurs, `None` s'ils ne sont pas comparables.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> Option<f32> {
+    if a.len() != b.len() || a.is_empty() {
+        return None;
+    }
+
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return None;
+    }
+    Some(dot / (norm_a * norm_b))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SimilarEntry {
+    pub entry: JournalEntry,
+    pub similarity: f32,
+}
+
+/// Les `k` entrées les plus proches de `embedding`, les entrées sans embedding étant ignorées.
+pub fn most_similar(embedding: &[f32], entries: Vec<JournalEntry>, k: usize) -> Vec<SimilarEntry> {
+    let mut similar: Vec<SimilarEntry> = entries
+        .into_iter()
+        .filter_map(|mut entry| {
+            let similarity = cosine_similarity(embedding, entry.embedding.as_deref()?)?;
+            entry.embedding = None;
+            Some(SimilarEntry { entry, similarity })
+        })
+        .collect();
+    similar.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    similar.truncate(k);
+    similar
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct EmbeddedEntry {
+    pub date: NaiveDate,
+    pub embedding: Vec<f32>,
+}
+
+/// Dates des entrées dont la similarité avec `embedding` atteint `threshold`.
+pub fn similar_dates(
+    embedding: &[f32],
+    entries: &[EmbeddedEntry],
+    threshold: f32,
+) -> Vec<NaiveDate> {
+    entries
+        .iter()
+        .filter(|entry| {
+            cosine_similarity(embedding, &entry.embedding)
+                .is_some_and(|similarity| similarity >= threshold)
+        })
+        .map(|entry| entry.date)
+        .collect()
+}
+
+/// Entrées récentes très proches de `entry`, pour signaler une routine à la création.
+pub async fn recent_similar_dates(
+    mongo_entries: &Collection<JournalEntry>,
+    entry: &JournalEntry,
+    threshold: f32,
+) -> Result<Vec<NaiveDate>, mongodb::error::Error> {
+    let Some(embedding) = &entry.embedding else {
+        return Ok(vec![]);
+    };
+
+    let recent: Vec<EmbeddedEntry> = mongo_entries
+        .clone_with_type::<EmbeddedEntry>()
+        .find(
+            doc! { "embedding": { "$exists": true }, "date": { "$ne": entry.date.to_string() } },
+            FindOptions::builder()
+                .projection(doc! { "_id": 0, "date": 1, "embedding": 1 })
+                .sort(doc! { "date": -1 })
+                .limit(RECENT_ENTRIES_COMPARED)
+                .build(),
+        )
+        .await?
+        .try_collect()
+        .await?;
+    Ok(similar_dates(embedding, &recent, threshold))
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum SimilarEntriesError {
+    #[error("no journal entry for {0}")]
+    #[status(StatusCode::NOT_FOUND)]
+    NotFound(NaiveDate),
+    #[error("the entry of {0} has no embedding yet")]
+    #[status(StatusCode::NOT_FOUND)]
+    NoEmbedding(NaiveDate),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Crypto(CryptoError),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+fn default_k() -> usize {
+    5
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SimilarEntriesQuery {
+    #[serde(default = "default_k")]
+    pub k: usize,
+}
+
+pub async fn get_similar_entries(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(cipher): Extension<Arc<EntryCipher>>,
+    Path(date): Path<NaiveDate>,
+    Query(query): Query<SimilarEntriesQuery>,
+) -> Result<Json<Vec<SimilarEntry>>, SimilarEntriesError> {
+    let entry = mongo_entries
+        .find_one(date_filter(date), None)
+        .await
+        .map_err(SimilarEntriesError::Mongo)?
+        .ok_or(SimilarEntriesError::NotFound(date))?;
+    let embedding = entry
+        .embedding
+        .ok_or(SimilarEntriesError::NoEmbedding(date))?;
+
+    let others: Vec<JournalEntry> = mongo_entries
+        .find(
+            doc! { "embedding": { "$exists": true }, "date": { "$ne": date.to_string() } },
+            None,
+        )
+        .await
+        .map_err(SimilarEntriesError::Mongo)?
+        .try_collect()
+        .await
+        .map_err(SimilarEntriesError::Mongo)?;
+
+    most_similar(&embedding, others, query.k)
+        .into_iter()
+        .map(|similar| {
+            Ok(SimilarEntry {
+                entry: similar.entry.decrypted(&cipher)?,
+                ..similar
+            })
+        })
+        .collect::<Result<_, _>>()
+        .map(Json)
+        .map_err(SimilarEntriesError::Crypto)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::routes::JournalEntry;
+
+    use super::{cosine_similarity, most_similar, similar_dates, EmbeddedEntry};
+
+    #[test]
+    fn compute_cosine_similarity() {
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]), Some(1.0));
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]), Some(0.0));
+        assert!((cosine_similarity(&[1.0, 1.0], &[-1.0, -1.0]).unwrap() + 1.0).abs() < 1e-6);
+        assert!(
+            (cosine_similarity(&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]).unwrap() - 0.974_631_8).abs()
+                < 1e-6
+        );
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[1.0]), None);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), None);
+    }
+
+    #[test]
+    fn keep_closest_entries() {
+        let entries = [
+            (1, Some(vec![0.0, 1.0])),
+            (2, Some(vec![1.0, 0.1])),
+            (3, None),
+            (4, Some(vec![1.0, 0.5])),
+        ]
+        .into_iter()
+        .map(|(day, embedding)| JournalEntry {
+            date: NaiveDate::from_ymd_opt(2024, 3, day).unwrap(),
+            embedding,
+            ..Default::default()
+        })
+        .collect();
+
+        let similar = most_similar(&[1.0, 0.0], entries, 2);
+        let days: Vec<_> = similar.iter().map(|similar| similar.entry.date).collect();
+        assert_eq!(
+            days,
+            [2, 4].map(|day| NaiveDate::from_ymd_opt(2024, 3, day).unwrap())
+        );
+        assert!(similar
+            .iter()
+            .all(|similar| similar.entry.embedding.is_none()));
+    }
+
+    #[test]
+    fn flag_nearly_identical_entries() {
+        let date = |day| NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+        let recent = [
+            EmbeddedEntry {
+                date: date(1),
+                embedding: vec![0.8, 0.6, 0.0],
+            },
+            EmbeddedEntry {
+                date: date(2),
+                embedding: vec![0.0, 0.6, 0.8],
+            },
+        ];
+
+        assert_eq!(similar_dates(&[0.79, 0.61, 0.01], &recent, 0.95), [date(1)]);
+        assert!(similar_dates(&[0.79, 0.61, 0.01], &recent, 1.0).is_empty());
+    }
+}
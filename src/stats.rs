@@ -0,0 +1,1157 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+
+use axum::{
+    extract::Query,
+    http::{HeaderMap, StatusCode},
+    Extension, Json,
+};
+use axum_thiserror::ErrorStatus;
+use chrono::{Datelike, Days, NaiveDate};
+use chrono_tz::Tz;
+use futures_util::TryStreamExt;
+use mongodb::{
+    bson::{self, doc, Bson, Document},
+    Collection,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config::AppConfig;
+use crate::locale::Locale;
+use crate::routes::JournalEntry;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TopicRatingStats {
+    pub topic: String,
+    pub average_rate: f32,
+    pub count: u32,
+}
+
+/// Calcule la note moyenne de chaque topic sur l'ensemble des entrées.
+/// Les topics sont regroupés sans tenir compte de la casse.
+pub fn aggregate_topic_ratings(entries: &[JournalEntry]) -> Vec<TopicRatingStats> {
+    let mut totals: BTreeMap<String, (f32, u32)> = BTreeMap::new();
+    for rating in entries.iter().flat_map(|entry| &entry.topic_ratings) {
+        let total = totals
+            .entry(rating.topic.trim().to_lowercase())
+            .or_default();
+        total.0 += rating.rate;
+        total.1 += 1;
+    }
+
+    let mut stats: Vec<TopicRatingStats> = totals
+        .into_iter()
+        .map(|(topic, (sum, count))| TopicRatingStats {
+            topic,
+            average_rate: sum / count as f32,
+            count,
+        })
+        .collect();
+    stats.sort_by_key(|stats| Reverse(stats.count));
+    stats
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TopicCorrelation {
+    pub topic: String,
+    pub average_rate: f32,
+    pub global_average: f32,
+    pub delta: f32,
+    pub count: u32,
+}
+
+#[derive(Deserialize, Debug)]
+struct TopicAverage {
+    #[serde(rename = "_id")]
+    topic: String,
+    average_rate: f64,
+    count: u32,
+}
+
+#[derive(Deserialize, Debug)]
+struct GlobalAverage {
+    average_rate: f64,
+}
+
+#[derive(Deserialize, Debug)]
+struct TopicCorrelationFacets {
+    global: Vec<GlobalAverage>,
+    topics: Vec<TopicAverage>,
+}
+
+/// Agrégation calculant en une passe la note moyenne globale et celle des
+/// entrées contenant chaque topic (sans tenir compte de la casse).
+pub fn topic_correlation_pipeline() -> Vec<Document> {
+    vec![
+        doc! { "$match": rated_filter() },
+        doc! {
+            "$facet": {
+                "global": [
+                    { "$group": { "_id": null, "average_rate": { "$avg": "$rate" } } }
+                ],
+                "topics": [
+                    { "$unwind": "$tags" },
+                    { "$group": {
+                        "_id": { "$toLower": "$tags" },
+                        "average_rate": { "$avg": "$rate" },
+                        "count": { "$sum": 1 }
+                    } }
+                ]
+            }
+        },
+    ]
+}
+
+/// Nombre d'entrées dont la note est dans `range` (`6-7` : de 6 inclus à 7 exclu,
+/// la dernière tranche incluant 10).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RateRange {
+    pub range: String,
+    pub count: u32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RateBucket {
+    #[serde(rename = "_id")]
+    pub lower_bound: Bson,
+    pub count: u32,
+}
+
+/// Agrégation comptant les entrées par tranche d'un point de note. Les notes hors
+/// de 0-10 (anciennes entrées) tombent dans une tranche à part, ignorée.
+pub fn rate_distribution_pipeline() -> Vec<Document> {
+    vec![
+        doc! { "$match": rated_filter() },
+        doc! {
+            "$bucket": {
+                "groupBy": "$rate",
+                "boundaries": [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10.000001],
+                "default": "out_of_range",
+                "output": { "count": { "$sum": 1 } }
+            }
+        },
+    ]
+}
+
+/// Les dix tranches dans l'ordre, les tranches absentes de l'agrégation à 0.
+pub fn rate_distribution(buckets: &[RateBucket]) -> Vec<RateRange> {
+    (0..10)
+        .map(|lower| RateRange {
+            range: format!("{lower}-{}", lower + 1),
+            count: buckets
+                .iter()
+                .find(|bucket| bucket.lower_bound.as_i32() == Some(lower))
+                .map_or(0, |bucket| bucket.count),
+        })
+        .collect()
+}
+
+/// Écart de chaque topic à la moyenne globale, les topics qui tirent la note
+/// vers le bas en premier.
+pub fn topic_correlations(facets: Document) -> Result<Vec<TopicCorrelation>, StatsError> {
+    let facets: TopicCorrelationFacets = bson::from_document(facets).map_err(StatsError::Bson)?;
+    let Some(global) = facets.global.first() else {
+        return Ok(Vec::new());
+    };
+    let global_average = global.average_rate as f32;
+
+    let mut correlations: Vec<TopicCorrelation> = facets
+        .topics
+        .into_iter()
+        .map(|topic| TopicCorrelation {
+            topic: topic.topic,
+            average_rate: topic.average_rate as f32,
+            global_average,
+            delta: topic.average_rate as f32 - global_average,
+            count: topic.count,
+        })
+        .collect();
+    correlations.sort_by(|a, b| a.delta.total_cmp(&b.delta));
+    Ok(correlations)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RollingAverage {
+    pub date: NaiveDate,
+    /// Jour de la semaine dans la locale demandée.
+    pub weekday: String,
+    pub rolling_avg: f32,
+}
+
+/// Moyenne glissante des notes sur les `window` jours précédant chaque entrée
+/// (jour inclus). Les jours sans entrée sont exclus de la moyenne.
+/// Les entrées doivent être triées par date croissante.
+pub fn rolling_averages(
+    entries: &[JournalEntry],
+    window: u32,
+    locale: Locale,
+) -> Vec<RollingAverage> {
+    let mut start = 0;
+    entries
+        .iter()
+        .enumerate()
+        .map(|(end, entry)| {
+            let window_start = entry
+                .date
+                .checked_sub_days(Days::new(u64::from(window.saturating_sub(1))))
+                .unwrap_or(NaiveDate::MIN);
+            while entries[start].date < window_start {
+                start += 1;
+            }
+            let rates = &entries[start..=end];
+            RollingAverage {
+                date: entry.date,
+                weekday: locale.weekday_name(entry.date.weekday()).to_string(),
+                rolling_avg: rates.iter().map(|entry| entry.rate).sum::<f32>() / rates.len() as f32,
+            }
+        })
+        .collect()
+}
+
+/// Nombre de mots du texte : la ponctuation isolée (tirets, points de
+/// suspension…) n'est pas comptée comme un mot.
+pub fn count_words(text: &str) -> u32 {
+    text.split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .count() as u32
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MonthlyWriting {
+    pub month: String,
+    /// Mois et année dans la locale demandée.
+    pub label: String,
+    pub total_words: u32,
+    pub entries: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WritingStats {
+    pub total_words: u32,
+    pub average_words_per_entry: f32,
+    pub monthly: Vec<MonthlyWriting>,
+}
+
+/// Statistiques d'écriture, sur les seules entrées dont la longueur est connue.
+pub fn writing_stats(entries: &[JournalEntry], locale: Locale) -> WritingStats {
+    let mut monthly: BTreeMap<String, MonthlyWriting> = BTreeMap::new();
+    for (date, word_count) in entries
+        .iter()
+        .filter_map(|entry| Some((entry.date, entry.word_count?)))
+    {
+        let month = date.format("%Y-%m").to_string();
+        let stats = monthly
+            .entry(month.clone())
+            .or_insert_with(|| MonthlyWriting {
+                month,
+                label: locale.month_label(date),
+                total_words: 0,
+                entries: 0,
+            });
+        stats.total_words += word_count;
+        stats.entries += 1;
+    }
+
+    let total_words = monthly.values().map(|month| month.total_words).sum();
+    let counted_entries: u32 = monthly.values().map(|month| month.entries).sum();
+    WritingStats {
+        total_words,
+        average_words_per_entry: if counted_entries == 0 {
+            0.0
+        } else {
+            total_words as f32 / counted_entries as f32
+        },
+        monthly: monthly.into_values().collect(),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Consistency {
+    /// Pourcentage des jours de la plage ayant une entrée.
+    pub fill_rate: f32,
+    /// Jours consécutifs avec une entrée jusqu'à la fin de la plage.
+    pub current_streak: u32,
+    pub longest_streak: u32,
+}
+
+/// Régularité d'écriture entre `from` et `to` inclus.
+pub fn consistency(entries: &[JournalEntry], from: NaiveDate, to: NaiveDate) -> Consistency {
+    let written: BTreeSet<NaiveDate> = entries.iter().map(|entry| entry.date).collect();
+
+    let mut days = 0;
+    let mut filled = 0;
+    let mut streak = 0;
+    let mut longest_streak = 0;
+    for day in from.iter_days().take_while(|day| *day <= to) {
+        days += 1;
+        if written.contains(&day) {
+            filled += 1;
+            streak += 1;
+            longest_streak = longest_streak.max(streak);
+        } else {
+            streak = 0;
+        }
+    }
+
+    Consistency {
+        fill_rate: if days == 0 {
+            0.0
+        } else {
+            filled as f32 * 100.0 / days as f32
+        },
+        current_streak: streak,
+        longest_streak,
+    }
+}
+
+/// Écart à la moyenne, en écarts-types, au-delà duquel une note est anormale.
+pub const ANOMALY_THRESHOLD: f32 = 2.0;
+/// En dessous de ce nombre d'entrées, l'écart-type n'est pas significatif.
+pub const MIN_ENTRIES_FOR_ANOMALIES: usize = 10;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RateAnomaly {
+    pub date: NaiveDate,
+    pub rate: f32,
+    /// Écart à la moyenne en écarts-types, négatif pour un jour anormalement bas.
+    pub deviation: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RateAnomalies {
+    pub mean: Option<f32>,
+    /// Absent tant qu'il y a moins de `MIN_ENTRIES_FOR_ANOMALIES` entrées.
+    pub std_dev: Option<f32>,
+    pub anomalies: Vec<RateAnomaly>,
+}
+
+/// Entrées dont la note s'écarte de la moyenne de plus de `ANOMALY_THRESHOLD` écarts-types.
+pub fn rate_anomalies(entries: &[JournalEntry]) -> RateAnomalies {
+    let count = entries.len() as f32;
+    let mean =
+        (!entries.is_empty()).then(|| entries.iter().map(|entry| entry.rate).sum::<f32>() / count);
+    let std_dev = mean
+        .filter(|_| entries.len() >= MIN_ENTRIES_FOR_ANOMALIES)
+        .map(|mean| {
+            (entries
+                .iter()
+                .map(|entry| (entry.rate - mean).powi(2))
+                .sum::<f32>()
+                / count)
+                .sqrt()
+        });
+
+    let anomalies = match (mean, std_dev) {
+        // Des notes toutes identiques n'ont pas d'écart anormal
+        (Some(mean), Some(std_dev)) if std_dev > 0.0 => entries
+            .iter()
+            .map(|entry| RateAnomaly {
+                date: entry.date,
+                rate: entry.rate,
+                deviation: (entry.rate - mean) / std_dev,
+            })
+            .filter(|anomaly| anomaly.deviation.abs() > ANOMALY_THRESHOLD)
+            .collect(),
+        _ => vec![],
+    };
+
+    RateAnomalies {
+        mean,
+        std_dev,
+        anomalies,
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Hemisphere {
+    #[default]
+    North,
+    South,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Season {
+    Winter,
+    Spring,
+    Summer,
+    Autumn,
+}
+
+impl Season {
+    /// Saison météorologique du mois : l'hiver va de décembre à février au nord,
+    /// de juin à août au sud.
+    pub fn of(month: u32, hemisphere: Hemisphere) -> Self {
+        let north = match month {
+            12 | 1 | 2 => Season::Winter,
+            3..=5 => Season::Spring,
+            6..=8 => Season::Summer,
+            _ => Season::Autumn,
+        };
+        match (hemisphere, north) {
+            (Hemisphere::North, season) => season,
+            (Hemisphere::South, Season::Winter) => Season::Summer,
+            (Hemisphere::South, Season::Spring) => Season::Autumn,
+            (Hemisphere::South, Season::Summer) => Season::Winter,
+            (Hemisphere::South, Season::Autumn) => Season::Spring,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SeasonalStats {
+    pub season: Season,
+    pub average_rate: f32,
+    pub count: u32,
+}
+
+/// Note moyenne par saison, toutes années confondues, de l'hiver à l'automne.
+/// Les saisons sans entrée sont omises.
+pub fn seasonal_stats(entries: &[JournalEntry], hemisphere: Hemisphere) -> Vec<SeasonalStats> {
+    let mut seasons: BTreeMap<Season, (f32, u32)> = BTreeMap::new();
+    for entry in entries {
+        let (total, count) = seasons
+            .entry(Season::of(entry.date.month(), hemisphere))
+            .or_default();
+        *total += entry.rate;
+        *count += 1;
+    }
+
+    seasons
+        .into_iter()
+        .map(|(season, (total, count))| SeasonalStats {
+            season,
+            average_rate: total / count as f32,
+            count,
+        })
+        .collect()
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum StatsError {
+    #[error("from ({0}) must be before or equal to to ({1})")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidRange(NaiveDate, NaiveDate),
+    #[error("window must be between 1 and {MAX_ROLLING_WINDOW} days")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidWindow,
+    #[error("unknown timezone \"{0}\"")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidTimezone(String),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Bson(bson::de::Error),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+/// Entrées ayant une note exploitable. Les brouillons non analysés n'en ont pas
+/// et fausseraient les moyennes (ou ne se désérialiseraient pas).
+pub fn rated_filter() -> Document {
+    doc! { "rate": { "$exists": true, "$type": "number" } }
+}
+
+/// Complément de `rated_filter` : note absente ou d'un autre type.
+pub fn unrated_filter() -> Document {
+    doc! { "rate": { "$not": { "$type": "number" } } }
+}
+
+/// Statistiques accompagnées du nombre d'entrées écartées faute de note valide.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RatedStats<T> {
+    pub stats: T,
+    pub excluded_count: u64,
+}
+
+impl<T> RatedStats<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> RatedStats<U> {
+        RatedStats {
+            stats: f(self.stats),
+            excluded_count: self.excluded_count,
+        }
+    }
+}
+
+/// Nombre d'entrées sans note valide parmi celles que retiennent `stages` : les
+/// statistiques calculées sur les mêmes étapes les ont écartées.
+pub async fn count_unrated(
+    mongo_entries: &Collection<JournalEntry>,
+    mut stages: Vec<Document>,
+) -> Result<u64, mongodb::error::Error> {
+    stages.extend([
+        doc! { "$match": unrated_filter() },
+        doc! { "$count": "count" },
+    ]);
+    let count = mongo_entries
+        .aggregate(stages, None)
+        .await?
+        .try_next()
+        .await?;
+    Ok(count
+        .and_then(|count| match count.get("count") {
+            Some(Bson::Int32(count)) => u64::try_from(*count).ok(),
+            Some(Bson::Int64(count)) => u64::try_from(*count).ok(),
+            _ => None,
+        })
+        .unwrap_or_default())
+}
+
+/// Fuseau demandé, sinon celui de la configuration.
+pub fn resolve_timezone(timezone: Option<&str>, default: Tz) -> Result<Tz, StatsError> {
+    match timezone {
+        Some(timezone) => timezone
+            .parse()
+            .map_err(|_| StatsError::InvalidTimezone(timezone.to_string())),
+        None => Ok(default),
+    }
+}
+
+/// Étape d'agrégation ramenant chaque `date` au jour local du fuseau. Les dates
+/// écrites en `DateTime` (minuit Paris stocké en UTC) tomberaient sinon la veille ;
+/// les dates déjà stockées comme jour calendaire sont laissées telles quelles.
+pub fn local_date_stage(timezone: Tz) -> Document {
+    doc! {
+        "$addFields": {
+            "date": {
+                "$cond": {
+                    "if": { "$eq": [{ "$type": "$date" }, "date"] },
+                    "then": {
+                        "$dateToString": {
+                            "format": "%Y-%m-%d",
+                            "date": "$date",
+                            "timezone": timezone.name()
+                        }
+                    },
+                    "else": "$date"
+                }
+            }
+        }
+    }
+}
+
+/// Étapes retenant les entrées de `filter`, appliqué à leur jour local dans `timezone`.
+pub fn entry_stages(timezone: Tz, filter: Document) -> Vec<Document> {
+    vec![local_date_stage(timezone), doc! { "$match": filter }]
+}
+
+/// Entrées entre `from` et `to` inclus, à filtrer après `local_date_stage`.
+pub fn period_filter(from: NaiveDate, to: NaiveDate) -> Document {
+    doc! { "date": { "$gte": from.to_string(), "$lte": to.to_string() } }
+}
+
+/// Charge les entrées notées de `filter` triées par date, avec leur jour local dans `timezone`.
+pub async fn load_entries(
+    mongo_entries: &Collection<JournalEntry>,
+    timezone: Tz,
+    filter: Document,
+) -> Result<Vec<JournalEntry>, StatsError> {
+    let mut pipeline = entry_stages(timezone, filter);
+    pipeline.extend([
+        doc! { "$match": rated_filter() },
+        doc! { "$sort": { "date": 1 } },
+    ]);
+    mongo_entries
+        .aggregate(pipeline, None)
+        .await
+        .map_err(StatsError::Mongo)?
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(StatsError::Mongo)?
+        .into_iter()
+        .map(bson::from_document)
+        .collect::<Result<_, _>>()
+        .map_err(StatsError::Bson)
+}
+
+/// Entrées notées de `filter`, avec le nombre d'entrées du même filtre écartées.
+pub async fn load_rated_entries(
+    mongo_entries: &Collection<JournalEntry>,
+    timezone: Tz,
+    filter: Document,
+) -> Result<RatedStats<Vec<JournalEntry>>, StatsError> {
+    Ok(RatedStats {
+        excluded_count: count_unrated(mongo_entries, entry_stages(timezone, filter.clone()))
+            .await
+            .map_err(StatsError::Mongo)?,
+        stats: load_entries(mongo_entries, timezone, filter).await?,
+    })
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct TimezoneQuery {
+    pub timezone: Option<String>,
+    pub locale: Option<Locale>,
+}
+
+pub async fn get_topic_ratings(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<Json<RatedStats<Vec<TopicRatingStats>>>, StatsError> {
+    let rated = load_rated_entries(&mongo_entries, config.timezone, doc! {}).await?;
+    Ok(Json(rated.map(|entries| aggregate_topic_ratings(&entries))))
+}
+
+pub async fn get_topic_correlation(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+) -> Result<Json<RatedStats<Vec<TopicCorrelation>>>, StatsError> {
+    let facets = mongo_entries
+        .aggregate(topic_correlation_pipeline(), None)
+        .await
+        .map_err(StatsError::Mongo)?
+        .try_next()
+        .await
+        .map_err(StatsError::Mongo)?
+        .unwrap_or_default();
+    Ok(Json(RatedStats {
+        stats: topic_correlations(facets)?,
+        excluded_count: count_unrated(&mongo_entries, vec![])
+            .await
+            .map_err(StatsError::Mongo)?,
+    }))
+}
+
+pub async fn get_rate_distribution(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+) -> Result<Json<RatedStats<Vec<RateRange>>>, StatsError> {
+    let buckets: Vec<RateBucket> = mongo_entries
+        .aggregate(rate_distribution_pipeline(), None)
+        .await
+        .map_err(StatsError::Mongo)?
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(StatsError::Mongo)?
+        .into_iter()
+        .map(bson::from_document)
+        .collect::<Result<_, _>>()
+        .map_err(StatsError::Bson)?;
+    Ok(Json(RatedStats {
+        stats: rate_distribution(&buckets),
+        excluded_count: count_unrated(&mongo_entries, vec![])
+            .await
+            .map_err(StatsError::Mongo)?,
+    }))
+}
+
+pub async fn get_writing_stats(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Query(query): Query<TimezoneQuery>,
+    headers: HeaderMap,
+) -> Result<Json<RatedStats<WritingStats>>, StatsError> {
+    let timezone = resolve_timezone(query.timezone.as_deref(), config.timezone)?;
+    let rated = load_rated_entries(&mongo_entries, timezone, doc! {}).await?;
+    Ok(Json(rated.map(|entries| {
+        writing_stats(&entries, Locale::resolve(query.locale, &headers))
+    })))
+}
+
+pub async fn get_rate_anomalies(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<Json<RatedStats<RateAnomalies>>, StatsError> {
+    let rated = load_rated_entries(&mongo_entries, config.timezone, doc! {}).await?;
+    Ok(Json(rated.map(|entries| rate_anomalies(&entries))))
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct SeasonalQuery {
+    #[serde(default)]
+    pub hemisphere: Hemisphere,
+    pub timezone: Option<String>,
+}
+
+pub async fn get_seasonal_stats(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Query(query): Query<SeasonalQuery>,
+) -> Result<Json<RatedStats<Vec<SeasonalStats>>>, StatsError> {
+    let timezone = resolve_timezone(query.timezone.as_deref(), config.timezone)?;
+    let rated = load_rated_entries(&mongo_entries, timezone, doc! {}).await?;
+    Ok(Json(
+        rated.map(|entries| seasonal_stats(&entries, query.hemisphere)),
+    ))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ConsistencyQuery {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub timezone: Option<String>,
+}
+
+pub async fn get_consistency(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Query(query): Query<ConsistencyQuery>,
+) -> Result<Json<RatedStats<Consistency>>, StatsError> {
+    if query.from > query.to {
+        return Err(StatsError::InvalidRange(query.from, query.to));
+    }
+
+    let timezone = resolve_timezone(query.timezone.as_deref(), config.timezone)?;
+    let rated = load_rated_entries(
+        &mongo_entries,
+        timezone,
+        period_filter(query.from, query.to),
+    )
+    .await?;
+    Ok(Json(rated.map(|entries| {
+        consistency(&entries, query.from, query.to)
+    })))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RollingQuery {
+    #[serde(default = "default_window")]
+    pub window: u32,
+    pub timezone: Option<String>,
+    pub locale: Option<Locale>,
+}
+
+/// Fenêtre maximale de la moyenne glissante, une dizaine d'années.
+pub const MAX_ROLLING_WINDOW: u32 = 3660;
+
+fn default_window() -> u32 {
+    7
+}
+
+pub async fn get_rolling_average(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Query(query): Query<RollingQuery>,
+    headers: HeaderMap,
+) -> Result<Json<RatedStats<Vec<RollingAverage>>>, StatsError> {
+    if !(1..=MAX_ROLLING_WINDOW).contains(&query.window) {
+        return Err(StatsError::InvalidWindow);
+    }
+
+    let timezone = resolve_timezone(query.timezone.as_deref(), config.timezone)?;
+    let rated = load_rated_entries(&mongo_entries, timezone, doc! {}).await?;
+    Ok(Json(rated.map(|entries| {
+        rolling_averages(
+            &entries,
+            query.window,
+            Locale::resolve(query.locale, &headers),
+        )
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::{locale::Locale, routes::JournalEntry};
+
+    use chrono_tz::{America::New_York, Europe::Paris};
+    use futures_util::TryStreamExt;
+    use mongodb::bson::doc;
+
+    use super::{
+        aggregate_topic_ratings, consistency, count_words, local_date_stage, rate_anomalies,
+        rate_distribution, resolve_timezone, rolling_averages, seasonal_stats, topic_correlations,
+        writing_stats, Hemisphere, RateBucket, RatedStats, Season, SeasonalStats,
+    };
+
+    fn entry(day: u32, rate: f32) -> JournalEntry {
+        JournalEntry {
+            date: NaiveDate::from_ymd_opt(2024, 3, day).unwrap(),
+            rate,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn deserialize_topic_ratings() {
+        let entry = serde_json::from_str::<JournalEntry>(
+            r#"{"date":"2024-01-24","rate":6.5,"short_summary":"Journée chargée","tags":["travail"],"topic_ratings":[{"topic":"travail","rate":4.0},{"topic":"sport","rate":9.0}]}"#,
+        )
+        .unwrap();
+        assert_eq!(entry.topic_ratings.len(), 2);
+        assert_eq!(entry.topic_ratings[1].topic, "sport");
+        assert!(entry.validate().is_ok());
+
+        let old_entry = serde_json::from_str::<JournalEntry>(
+            r#"{"date":"2024-01-24","rate":6.5,"short_summary":"Journée chargée","tags":[]}"#,
+        )
+        .unwrap();
+        assert!(old_entry.topic_ratings.is_empty());
+    }
+
+    #[test]
+    fn reject_out_of_range_topic_rate() {
+        let entry = serde_json::from_str::<JournalEntry>(
+            r#"{"date":"2024-01-24","rate":6.5,"short_summary":"","tags":[],"topic_ratings":[{"topic":"travail","rate":12.0}]}"#,
+        )
+        .unwrap();
+        assert!(entry.validate().is_err());
+    }
+
+    #[test]
+    fn aggregate_ratings_by_topic() {
+        let entries: Vec<JournalEntry> = [
+            r#"{"date":"2024-01-24","rate":5.0,"short_summary":"","tags":[],"topic_ratings":[{"topic":"Travail","rate":4.0},{"topic":"sport","rate":8.0}]}"#,
+            r#"{"date":"2024-01-25","rate":5.0,"short_summary":"","tags":[],"topic_ratings":[{"topic":"travail","rate":6.0}]}"#,
+        ]
+        .iter()
+        .map(|json| serde_json::from_str(json).unwrap())
+        .collect();
+
+        let stats = aggregate_topic_ratings(&entries);
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].topic, "travail");
+        assert_eq!(stats[0].count, 2);
+        assert_eq!(stats[0].average_rate, 5.0);
+        assert_eq!(stats[1].topic, "sport");
+        assert_eq!(stats[1].average_rate, 8.0);
+    }
+
+    #[test]
+    fn rolling_average_skips_missing_days() {
+        // Le 4 mars manque : la fenêtre du 5 ne contient que le 3 et le 5
+        let entries = vec![entry(1, 2.0), entry(2, 4.0), entry(3, 6.0), entry(5, 8.0)];
+
+        let averages: Vec<f32> = rolling_averages(&entries, 3, Locale::Fr)
+            .into_iter()
+            .map(|average| average.rolling_avg)
+            .collect();
+        assert_eq!(averages, vec![2.0, 3.0, 4.0, 7.0]);
+
+        let averages = rolling_averages(&entries, 1, Locale::Fr);
+        assert_eq!(
+            averages[3].date,
+            NaiveDate::from_ymd_opt(2024, 3, 5).unwrap()
+        );
+        assert_eq!(averages[3].rolling_avg, 8.0);
+
+        // Une fenêtre plus longue que le calendrier couvre toutes les entrées
+        let averages = rolling_averages(&entries, u32::MAX, Locale::Fr);
+        assert_eq!(averages[3].rolling_avg, 5.0);
+    }
+
+    #[test]
+    fn count_words_ignoring_punctuation() {
+        assert_eq!(count_words("J'ai couru 10km — enfin !"), 4);
+        assert_eq!(count_words("  Bonne\njournée...  ?! "), 2);
+        assert_eq!(count_words(""), 0);
+    }
+
+    #[test]
+    fn writing_stats_by_month() {
+        let mut entries = vec![entry(1, 5.0), entry(2, 5.0), entry(3, 5.0)];
+        entries[0].word_count = Some(100);
+        entries[1].word_count = Some(50);
+        entries[2].date = NaiveDate::from_ymd_opt(2024, 4, 1).unwrap();
+        entries[2].word_count = Some(30);
+        // Entrée antérieure au comptage, ignorée
+        entries.push(entry(4, 5.0));
+
+        let stats = writing_stats(&entries, Locale::Fr);
+        assert_eq!(stats.total_words, 180);
+        assert_eq!(stats.average_words_per_entry, 60.0);
+        assert_eq!(stats.monthly.len(), 2);
+        assert_eq!(stats.monthly[0].month, "2024-03");
+        assert_eq!(stats.monthly[0].total_words, 150);
+        assert_eq!(stats.monthly[1].entries, 1);
+    }
+
+    #[test]
+    fn distribute_rates_in_ranges() {
+        let buckets: Vec<RateBucket> = [
+            doc! { "_id": 2, "count": 1 },
+            doc! { "_id": 6, "count": 4 },
+            doc! { "_id": 9, "count": 2 },
+            doc! { "_id": "out_of_range", "count": 3 },
+        ]
+        .into_iter()
+        .map(|bucket| mongodb::bson::from_document(bucket).unwrap())
+        .collect();
+
+        let distribution = rate_distribution(&buckets);
+        assert_eq!(distribution.len(), 10);
+        assert_eq!(distribution[0].range, "0-1");
+        assert_eq!(distribution[0].count, 0);
+        assert_eq!(distribution[2].count, 1);
+        assert_eq!(distribution[6].range, "6-7");
+        assert_eq!(distribution[6].count, 4);
+        assert_eq!(distribution[9].range, "9-10");
+        assert_eq!(distribution[9].count, 2);
+        assert_eq!(distribution.iter().map(|range| range.count).sum::<u32>(), 7);
+    }
+
+    #[test]
+    fn resolve_requested_timezone() {
+        assert_eq!(
+            resolve_timezone(Some("America/New_York"), Paris).unwrap(),
+            New_York
+        );
+        assert_eq!(resolve_timezone(None, Paris).unwrap(), Paris);
+        assert!(resolve_timezone(Some("Mars/Olympus"), Paris).is_err());
+    }
+
+    #[test]
+    fn local_day_of_entry_near_midnight() {
+        // Une entrée du 24 à minuit Paris est stockée le 23 à 23h UTC :
+        // le jour doit être calculé dans le fuseau et non en UTC.
+        let stage = local_date_stage(resolve_timezone(Some("Europe/Paris"), New_York).unwrap());
+        let date = stage
+            .get_document("$addFields")
+            .and_then(|fields| fields.get_document("date"))
+            .and_then(|date| date.get_document("$cond"))
+            .unwrap();
+
+        assert_eq!(
+            date.get_document("then").unwrap(),
+            &doc! {
+                "$dateToString": { "format": "%Y-%m-%d", "date": "$date", "timezone": "Europe/Paris" }
+            }
+        );
+        assert_eq!(date.get_str("else"), Ok("$date"));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server in MONGO"]
+    async fn load_entry_near_midnight_on_its_local_day() {
+        dotenvy::dotenv().ok();
+        let options =
+            crate::mongo_client_options(&std::env::var("MONGO").unwrap(), crate::DEFAULT_APP_NAME)
+                .await
+                .unwrap();
+        let documents = mongodb::Client::with_options(options)
+            .unwrap()
+            .database("journai_test")
+            .collection::<mongodb::bson::Document>("local_day_entries");
+        documents.drop(None).await.unwrap();
+
+        // Minuit à Paris le 24, écrit en `DateTime` par les anciens upserts
+        let mut legacy = mongodb::bson::to_document(&entry(1, 6.0)).unwrap();
+        legacy.insert(
+            "date",
+            mongodb::bson::DateTime::parse_rfc3339_str("2024-01-23T23:00:00Z").unwrap(),
+        );
+        documents.insert_one(legacy, None).await.unwrap();
+
+        let entries = documents.clone_with_type::<JournalEntry>();
+        let day = NaiveDate::from_ymd_opt(2024, 1, 24).unwrap();
+        let loaded = super::load_entries(&entries, Paris, super::period_filter(day, day))
+            .await
+            .unwrap();
+        assert_eq!(
+            loaded.iter().map(|entry| entry.date).collect::<Vec<_>>(),
+            [day]
+        );
+    }
+
+    #[test]
+    fn correlate_topics_with_rate() {
+        // "travail" apparaît dans les journées à 2 et 3, "sport" dans celles à 8 et 9
+        let facets = doc! {
+            "global": [{ "_id": null, "average_rate": 5.5 }],
+            "topics": [
+                { "_id": "sport", "average_rate": 8.5, "count": 2 },
+                { "_id": "travail", "average_rate": 2.5, "count": 2 },
+                { "_id": "famille", "average_rate": 5.5, "count": 4 }
+            ]
+        };
+
+        let correlations = topic_correlations(facets).unwrap();
+        assert_eq!(correlations[0].topic, "travail");
+        assert_eq!(correlations[0].delta, -3.0);
+        assert_eq!(correlations[0].global_average, 5.5);
+        assert_eq!(correlations[1].topic, "famille");
+        assert_eq!(correlations[2].topic, "sport");
+        assert_eq!(correlations[2].delta, 3.0);
+
+        assert!(topic_correlations(doc! { "global": [], "topics": [] })
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn consistency_of_partially_filled_range() {
+        // Du 1er au 10 mars : 1-2, 4-6 et 9-10 écrits
+        let entries: Vec<JournalEntry> = [1, 2, 4, 5, 6, 9, 10]
+            .into_iter()
+            .map(|day| entry(day, 5.0))
+            .collect();
+        let date = |day| NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+
+        let stats = consistency(&entries, date(1), date(10));
+        assert_eq!(stats.fill_rate, 70.0);
+        assert_eq!(stats.current_streak, 2);
+        assert_eq!(stats.longest_streak, 3);
+
+        let stats = consistency(&entries, date(3), date(8));
+        assert_eq!(stats.fill_rate, 50.0);
+        assert_eq!(stats.current_streak, 0);
+        assert_eq!(stats.longest_streak, 3);
+    }
+
+    #[test]
+    fn consistency_of_single_day() {
+        let entries = vec![entry(4, 5.0)];
+        let date = |day| NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+
+        let stats = consistency(&entries, date(4), date(4));
+        assert_eq!(stats.fill_rate, 100.0);
+        assert_eq!(stats.current_streak, 1);
+        assert_eq!(stats.longest_streak, 1);
+
+        assert_eq!(consistency(&entries, date(5), date(5)).fill_rate, 0.0);
+    }
+
+    #[test]
+    fn detect_outlier_rates() {
+        let mut entries: Vec<JournalEntry> = (1..=10)
+            .map(|day| entry(day, if day % 2 == 0 { 7.0 } else { 6.0 }))
+            .collect();
+        entries.push(entry(11, 1.0));
+
+        let result = rate_anomalies(&entries);
+        assert_eq!(result.mean, Some(6.0));
+        assert_eq!(result.anomalies.len(), 1);
+        let anomaly = &result.anomalies[0];
+        assert_eq!(anomaly.date, NaiveDate::from_ymd_opt(2024, 3, 11).unwrap());
+        assert_eq!(anomaly.rate, 1.0);
+        assert!(anomaly.deviation < -3.0);
+    }
+
+    #[test]
+    fn no_anomalies_without_enough_entries() {
+        let entries = vec![entry(1, 7.0), entry(2, 7.0), entry(3, 0.0)];
+
+        let result = rate_anomalies(&entries);
+        assert_eq!(result.std_dev, None);
+        assert!(result.anomalies.is_empty());
+
+        let flat: Vec<JournalEntry> = (1..=12).map(|day| entry(day, 5.0)).collect();
+        assert_eq!(rate_anomalies(&flat).std_dev, Some(0.0));
+        assert!(rate_anomalies(&flat).anomalies.is_empty());
+    }
+
+    #[test]
+    fn group_entries_by_season() {
+        let entries: Vec<JournalEntry> = [
+            (2023, 12, 4.0),
+            (2024, 1, 3.0),
+            (2024, 2, 5.0),
+            (2024, 4, 7.0),
+            (2024, 7, 9.0),
+            (2024, 8, 8.0),
+        ]
+        .into_iter()
+        .map(|(year, month, rate)| JournalEntry {
+            date: NaiveDate::from_ymd_opt(year, month, 15).unwrap(),
+            rate,
+            ..Default::default()
+        })
+        .collect();
+
+        assert_eq!(
+            seasonal_stats(&entries, Hemisphere::North),
+            [
+                SeasonalStats {
+                    season: Season::Winter,
+                    average_rate: 4.0,
+                    count: 3
+                },
+                SeasonalStats {
+                    season: Season::Spring,
+                    average_rate: 7.0,
+                    count: 1
+                },
+                SeasonalStats {
+                    season: Season::Summer,
+                    average_rate: 8.5,
+                    count: 2
+                },
+            ]
+        );
+
+        // Saisons inversées au sud ; l'automne n'a toujours pas d'entrée
+        let south = seasonal_stats(&entries, Hemisphere::South);
+        assert_eq!(
+            south.iter().map(|stats| stats.season).collect::<Vec<_>>(),
+            [Season::Winter, Season::Summer, Season::Autumn]
+        );
+        assert_eq!(south[0].average_rate, 8.5);
+        assert_eq!(Season::of(10, Hemisphere::North), Season::Autumn);
+        assert_eq!(serde_json::to_value(Season::Autumn).unwrap(), "autumn");
+    }
+
+    #[test]
+    fn report_excluded_entries() {
+        let stats = RatedStats {
+            stats: rate_anomalies(&[entry(1, 6.0), entry(2, 8.0)]),
+            excluded_count: 3,
+        };
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["excluded_count"], 3);
+        assert_eq!(json["stats"]["mean"], 7.0);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server in MONGO"]
+    async fn exclude_unrated_entries() {
+        dotenvy::dotenv().ok();
+        let options =
+            crate::mongo_client_options(&std::env::var("MONGO").unwrap(), crate::DEFAULT_APP_NAME)
+                .await
+                .unwrap();
+        let documents = mongodb::Client::with_options(options)
+            .unwrap()
+            .database("journai_test")
+            .collection::<mongodb::bson::Document>("unrated_stats_entries");
+        documents.drop(None).await.unwrap();
+
+        let rated = |day, rate| mongodb::bson::to_document(&entry(day, rate)).unwrap();
+        let mut draft = rated(3, 0.0);
+        draft.remove("rate");
+        let mut invalid = rated(4, 0.0);
+        invalid.insert("rate", "n/a");
+        documents
+            .insert_many([rated(1, 4.0), rated(2, 8.0), draft, invalid], None)
+            .await
+            .unwrap();
+
+        let entries = documents.clone_with_type::<JournalEntry>();
+        let rated = super::load_rated_entries(&entries, Paris, doc! {})
+            .await
+            .unwrap();
+        assert_eq!(rated.stats.len(), 2);
+        assert_eq!(rated.excluded_count, 2);
+        assert_eq!(rate_anomalies(&rated.stats).mean, Some(6.0));
+
+        // Seules les entrées écartées de la période sont comptées
+        let period = super::period_filter(
+            NaiveDate::from_ymd_opt(2024, 3, 2).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 3, 3).unwrap(),
+        );
+        let rated = super::load_rated_entries(&entries, Paris, period)
+            .await
+            .unwrap();
+        assert_eq!(rated.stats.len(), 1);
+        assert_eq!(rated.excluded_count, 1);
+
+        let buckets: Vec<RateBucket> = entries
+            .aggregate(super::rate_distribution_pipeline(), None)
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|bucket| mongodb::bson::from_document(bucket).unwrap())
+            .collect();
+        let counted: u32 = rate_distribution(&buckets)
+            .iter()
+            .map(|range| range.count)
+            .sum();
+        assert_eq!(counted, 2);
+    }
+}
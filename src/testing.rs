@@ -0,0 +1,43 @@
+use async_openai::{config::OpenAIConfig, Client};
+use axum::{routing::post, Json, Router};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+
+/// Client OpenAI branché sur un faux serveur local servant `routes`.
+pub async fn mock_openai(routes: Router) -> Client<OpenAIConfig> {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, routes).await.unwrap() });
+
+    Client::with_config(
+        OpenAIConfig::new()
+            .with_api_key("test")
+            .with_api_base(format!("http://{address}")),
+    )
+}
+
+/// Réponse de complétion renvoyant `content`.
+pub fn chat_completion_response(content: &str) -> Value {
+    json!({
+        "id": "chatcmpl-test",
+        "object": "chat.completion",
+        "created": 1706054400,
+        "model": "gpt-3.5-turbo",
+        "choices": [{
+            "index": 0,
+            "message": { "role": "assistant", "content": content },
+            "finish_reason": "stop"
+        }],
+        "usage": { "prompt_tokens": 120, "completion_tokens": 30, "total_tokens": 150 }
+    })
+}
+
+/// Client OpenAI dont les complétions renvoient toujours `content`.
+pub async fn mock_chat_completion(content: &str) -> Client<OpenAIConfig> {
+    let response = chat_completion_response(content);
+    mock_openai(Router::new().route(
+        "/chat/completions",
+        post(move || async move { Json(response) }),
+    ))
+    .await
+}
@@ -0,0 +1,127 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::http::StatusCode;
+use axum_thiserror::ErrorStatus;
+use thiserror::Error;
+use tokio::sync::Semaphore;
+
+/// Requêtes OpenAI par minute acceptées par défaut.
+pub const DEFAULT_OPENAI_REQUESTS_PER_MINUTE: u32 = 60;
+/// Attente maximale d'un jeton avant de répondre 429.
+pub const DEFAULT_OPENAI_QUEUE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum ThrottleError {
+    #[error("too many OpenAI requests, no slot freed up within {0:?}")]
+    #[status(StatusCode::TOO_MANY_REQUESTS)]
+    Saturated(Duration),
+}
+
+/// File d'attente des appels OpenAI : un seau de jetons (les permis du
+/// sémaphore) rechargé à intervalle régulier. Le sémaphore servant les
+/// demandes dans l'ordre, les requêtes passent une à une sous la limite.
+#[derive(Debug)]
+pub struct OpenAiThrottle {
+    tokens: Arc<Semaphore>,
+    max_wait: Duration,
+}
+
+impl OpenAiThrottle {
+    pub fn new(requests_per_minute: u32, max_wait: Duration) -> Arc<Self> {
+        let requests_per_minute = requests_per_minute.max(1);
+        Self::with_refill(
+            requests_per_minute as usize,
+            Duration::from_secs(60) / requests_per_minute,
+            max_wait,
+        )
+    }
+
+    /// Seau de `capacity` jetons, un jeton étant rendu tous les `refill_every`.
+    /// La recharge s'arrête avec le dernier `Arc`.
+    pub fn with_refill(capacity: usize, refill_every: Duration, max_wait: Duration) -> Arc<Self> {
+        let tokens = Arc::new(Semaphore::new(capacity));
+        let bucket = Arc::downgrade(&tokens);
+        tokio::spawn(async move {
+            let mut refill = tokio::time::interval(refill_every);
+            refill.tick().await;
+            loop {
+                refill.tick().await;
+                let Some(tokens) = bucket.upgrade() else {
+                    break;
+                };
+                if tokens.available_permits() < capacity {
+                    tokens.add_permits(1);
+                }
+            }
+        });
+
+        Arc::new(OpenAiThrottle { tokens, max_wait })
+    }
+
+    /// Attend son tour, au plus `max_wait`. Le jeton consommé n'est rendu que par la recharge.
+    pub async fn acquire(&self) -> Result<(), ThrottleError> {
+        match tokio::time::timeout(self.max_wait, self.tokens.acquire()).await {
+            Ok(Ok(permit)) => {
+                permit.forget();
+                Ok(())
+            }
+            // Le sémaphore n'est jamais fermé
+            Ok(Err(_)) | Err(_) => Err(ThrottleError::Saturated(self.max_wait)),
+        }
+    }
+}
+
+/// Attend un tour dans la file avant un appel OpenAI. Sans file configurée,
+/// l'appel passe directement.
+pub async fn take_turn(throttle: Option<&OpenAiThrottle>) -> Result<(), ThrottleError> {
+    match throttle {
+        Some(throttle) => throttle.acquire().await,
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{OpenAiThrottle, ThrottleError};
+
+    #[tokio::test]
+    async fn serialize_concurrent_requests() {
+        let refill = Duration::from_millis(50);
+        let throttle = OpenAiThrottle::with_refill(1, refill, Duration::from_secs(5));
+
+        let start = Instant::now();
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let throttle = throttle.clone();
+                tokio::spawn(async move {
+                    throttle.acquire().await.unwrap();
+                    start.elapsed()
+                })
+            })
+            .collect();
+        let mut granted = Vec::new();
+        for handle in handles {
+            granted.push(handle.await.unwrap());
+        }
+        granted.sort();
+
+        // Un seul jeton à la fois : chaque requête attend la recharge suivante
+        for (turn, elapsed) in granted.iter().enumerate() {
+            assert!(*elapsed + Duration::from_millis(10) >= refill * turn as u32);
+        }
+    }
+
+    #[tokio::test]
+    async fn reject_when_the_wait_is_too_long() {
+        let throttle =
+            OpenAiThrottle::with_refill(1, Duration::from_secs(60), Duration::from_millis(20));
+
+        throttle.acquire().await.unwrap();
+        assert!(matches!(
+            throttle.acquire().await,
+            Err(ThrottleError::Saturated(_))
+        ));
+    }
+}
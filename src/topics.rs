@@ -0,0 +1,316 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{extract::Query, http::StatusCode, Extension, Json};
+use axum_thiserror::ErrorStatus;
+use chrono::{Days, NaiveDate, Utc};
+use chrono_tz::Tz;
+use futures_util::TryStreamExt;
+use mongodb::{
+    bson::{self, doc, Document},
+    Collection,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    config::AppConfig,
+    routes::JournalEntry,
+    stats::{count_unrated, local_date_stage, rated_filter, RatedStats},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecurringTopic {
+    #[serde(rename(deserialize = "_id"))]
+    pub topic: String,
+    pub occurrences: u32,
+    pub first_occurrence: NaiveDate,
+    pub last_occurrence: NaiveDate,
+    pub average_rate: f32,
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum TopicsError {
+    #[error("min_occurrences must be at least 1")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidMinOccurrences,
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Bson(bson::de::Error),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+/// Topics apparus au moins `min_occurrences` fois (sans tenir compte de la casse)
+/// dans les entrées notées, les plus fréquents en premier.
+pub fn recurring_topics_pipeline(timezone: Tz, min_occurrences: u32) -> Vec<Document> {
+    vec![
+        local_date_stage(timezone),
+        doc! { "$match": rated_filter() },
+        doc! { "$unwind": "$tags" },
+        doc! {
+            "$group": {
+                "_id": { "$toLower": "$tags" },
+                "occurrences": { "$sum": 1 },
+                "first_occurrence": { "$min": "$date" },
+                "last_occurrence": { "$max": "$date" },
+                "average_rate": { "$avg": "$rate" }
+            }
+        },
+        doc! { "$match": { "occurrences": { "$gte": min_occurrences } } },
+        doc! { "$sort": { "occurrences": -1, "_id": 1 } },
+    ]
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RecurringTopicsQuery {
+    #[serde(default = "default_min_occurrences")]
+    pub min_occurrences: u32,
+}
+
+fn default_min_occurrences() -> u32 {
+    5
+}
+
+pub async fn get_recurring_topics(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Query(query): Query<RecurringTopicsQuery>,
+) -> Result<Json<RatedStats<Vec<RecurringTopic>>>, TopicsError> {
+    if query.min_occurrences == 0 {
+        return Err(TopicsError::InvalidMinOccurrences);
+    }
+
+    let timezone = config.timezone;
+    let topics = mongo_entries
+        .aggregate(
+            recurring_topics_pipeline(timezone, query.min_occurrences),
+            None,
+        )
+        .await
+        .map_err(TopicsError::Mongo)?
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(TopicsError::Mongo)?
+        .into_iter()
+        .map(bson::from_document)
+        .collect::<Result<_, _>>()
+        .map_err(TopicsError::Bson)?;
+
+    Ok(Json(RatedStats {
+        stats: topics,
+        excluded_count: count_unrated(&mongo_entries, vec![])
+            .await
+            .map_err(TopicsError::Mongo)?,
+    }))
+}
+
+/// Durée de chacune des deux fenêtres comparées par `GET /topics/momentum`.
+pub const MOMENTUM_WINDOW_DAYS: u64 = 30;
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct WindowKey {
+    pub topic: String,
+    pub recent: bool,
+}
+
+/// Nombre d'entrées d'un topic dans l'une des deux fenêtres.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct WindowCount {
+    #[serde(rename = "_id")]
+    pub key: WindowKey,
+    pub count: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TopicTrend {
+    pub topic: String,
+    pub recent: u32,
+    pub previous: u32,
+    /// Variation en pourcentage, `null` pour un topic absent de la fenêtre précédente.
+    pub change_percent: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct TopicMomentum {
+    pub rising: Vec<TopicTrend>,
+    pub declining: Vec<TopicTrend>,
+}
+
+/// Compte les topics (sans tenir compte de la casse) depuis `previous_start`, en
+/// distinguant les entrées à partir de `recent_start`.
+pub fn topic_windows_pipeline(
+    timezone: Tz,
+    previous_start: NaiveDate,
+    recent_start: NaiveDate,
+) -> Vec<Document> {
+    vec![
+        local_date_stage(timezone),
+        doc! { "$match": { "date": { "$gte": previous_start.to_string() } } },
+        doc! { "$unwind": "$tags" },
+        doc! {
+            "$group": {
+                "_id": {
+                    "topic": { "$toLower": "$tags" },
+                    "recent": { "$gte": ["$date", recent_start.to_string()] }
+                },
+                "count": { "$sum": 1 }
+            }
+        },
+    ]
+}
+
+/// Topics en hausse et en baisse entre les deux fenêtres, les plus fortes variations en premier.
+/// Les nouveaux topics sont en tête des hausses, ceux qui ont disparu baissent de 100 %.
+pub fn topic_momentum(counts: Vec<WindowCount>) -> TopicMomentum {
+    let mut topics: BTreeMap<String, (u32, u32)> = BTreeMap::new();
+    for count in counts {
+        let (recent, previous) = topics.entry(count.key.topic).or_default();
+        if count.key.recent {
+            *recent += count.count;
+        } else {
+            *previous += count.count;
+        }
+    }
+
+    let mut momentum = TopicMomentum::default();
+    for (topic, (recent, previous)) in topics {
+        let trend = TopicTrend {
+            topic,
+            recent,
+            previous,
+            change_percent: (previous > 0)
+                .then(|| (recent as f32 - previous as f32) / previous as f32 * 100.0),
+        };
+        match recent.cmp(&previous) {
+            std::cmp::Ordering::Greater => momentum.rising.push(trend),
+            std::cmp::Ordering::Less => momentum.declining.push(trend),
+            std::cmp::Ordering::Equal => {}
+        }
+    }
+
+    momentum.rising.sort_by(|a, b| {
+        let change = |trend: &TopicTrend| trend.change_percent.unwrap_or(f32::INFINITY);
+        change(b).total_cmp(&change(a))
+    });
+    momentum.declining.sort_by(|a, b| {
+        a.change_percent
+            .unwrap_or(0.0)
+            .total_cmp(&b.change_percent.unwrap_or(0.0))
+    });
+    momentum
+}
+
+pub async fn get_topic_momentum(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<Json<TopicMomentum>, TopicsError> {
+    let timezone = config.timezone;
+    let today = Utc::now().with_timezone(&timezone).date_naive();
+    let recent_start = today - Days::new(MOMENTUM_WINDOW_DAYS - 1);
+    let previous_start = recent_start - Days::new(MOMENTUM_WINDOW_DAYS);
+
+    let counts = mongo_entries
+        .aggregate(
+            topic_windows_pipeline(timezone, previous_start, recent_start),
+            None,
+        )
+        .await
+        .map_err(TopicsError::Mongo)?
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(TopicsError::Mongo)?
+        .into_iter()
+        .map(bson::from_document)
+        .collect::<Result<_, _>>()
+        .map_err(TopicsError::Bson)?;
+
+    Ok(Json(topic_momentum(counts)))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use chrono_tz::Europe::Paris;
+    use mongodb::bson::{self, doc};
+
+    use crate::stats::rated_filter;
+
+    use super::{
+        recurring_topics_pipeline, topic_momentum, RecurringTopic, RecurringTopicsQuery,
+        WindowCount,
+    };
+
+    #[test]
+    fn filter_by_min_occurrences() {
+        let query = serde_json::from_str::<RecurringTopicsQuery>("{}").unwrap();
+        assert_eq!(query.min_occurrences, 5);
+
+        let pipeline = recurring_topics_pipeline(Paris, 3);
+        assert_eq!(pipeline[1], doc! { "$match": rated_filter() });
+        assert_eq!(
+            pipeline[4],
+            doc! { "$match": { "occurrences": { "$gte": 3 } } }
+        );
+    }
+
+    #[test]
+    fn read_grouped_topic() {
+        let topic: RecurringTopic = bson::from_document(doc! {
+            "_id": "sport",
+            "occurrences": 6,
+            "first_occurrence": "2024-01-03",
+            "last_occurrence": "2024-03-28",
+            "average_rate": 7.25
+        })
+        .unwrap();
+        assert_eq!(topic.topic, "sport");
+        assert_eq!(
+            topic.first_occurrence,
+            NaiveDate::from_ymd_opt(2024, 1, 3).unwrap()
+        );
+
+        let json = serde_json::to_value(&topic).unwrap();
+        assert_eq!(json["topic"], "sport");
+        assert_eq!(json["occurrences"], 6);
+    }
+
+    #[test]
+    fn detect_emerging_topic() {
+        let counts: Vec<WindowCount> = [
+            ("sport", true, 12),
+            ("sport", false, 2),
+            ("cours", true, 3),
+            ("cours", false, 6),
+            ("famille", true, 4),
+            ("famille", false, 4),
+            ("piano", true, 5),
+            ("voyage", false, 3),
+        ]
+        .into_iter()
+        .map(|(topic, recent, count)| {
+            bson::from_document(
+                doc! { "_id": { "topic": topic, "recent": recent }, "count": count },
+            )
+            .unwrap()
+        })
+        .collect();
+
+        let momentum = topic_momentum(counts);
+        let rising: Vec<_> = momentum
+            .rising
+            .iter()
+            .map(|trend| (trend.topic.as_str(), trend.change_percent))
+            .collect();
+        assert_eq!(rising, [("piano", None), ("sport", Some(500.0))]);
+        let declining: Vec<_> = momentum
+            .declining
+            .iter()
+            .map(|trend| (trend.topic.as_str(), trend.change_percent))
+            .collect();
+        assert_eq!(
+            declining,
+            [("voyage", Some(-100.0)), ("cours", Some(-50.0))]
+        );
+    }
+}
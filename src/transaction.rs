@@ -0,0 +1,103 @@
+use futures_util::future::BoxFuture;
+use mongodb::{
+    error::{Error, ErrorKind, Result},
+    Client, ClientSession,
+};
+
+/// Code renvoyé par un serveur Mongo autonome, qui ne gère pas les transactions.
+const ILLEGAL_OPERATION: i32 = 20;
+
+/// Le serveur n'est pas un replica set : les transactions y sont refusées.
+pub fn transactions_unsupported(error: &Error) -> bool {
+    matches!(*error.kind, ErrorKind::Command(ref command) if command.code == ILLEGAL_OPERATION)
+}
+
+/// Exécute `operation` dans une transaction, annulée si elle échoue. Sans
+/// replica set, l'opération est rejouée sans session, avec un avertissement :
+/// ses écritures ne sont alors plus atomiques.
+pub async fn in_transaction<T, F>(client: &Client, name: &str, mut operation: F) -> Result<T>
+where
+    F: for<'a> FnMut(Option<&'a mut ClientSession>) -> BoxFuture<'a, Result<T>>,
+{
+    let mut session = client.start_session(None).await?;
+    session.start_transaction(None).await?;
+
+    match operation(Some(&mut session)).await {
+        Ok(value) => {
+            session.commit_transaction().await?;
+            Ok(value)
+        }
+        Err(error) if transactions_unsupported(&error) => {
+            tracing::warn!("Transactions unavailable, running {name} without one: {error}");
+            session.abort_transaction().await.ok();
+            operation(None).await
+        }
+        Err(error) => {
+            session.abort_transaction().await.ok();
+            Err(error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mongodb::{
+        bson::{doc, Document},
+        error::{Error, ErrorKind},
+    };
+
+    use super::{in_transaction, transactions_unsupported};
+
+    #[test]
+    fn detect_unsupported_transactions() {
+        let command: mongodb::error::CommandError = mongodb::bson::from_document(doc! {
+            "code": 20,
+            "codeName": "IllegalOperation",
+            "errmsg": "Transaction numbers are only allowed on a replica set member or mongos",
+        })
+        .unwrap();
+        assert!(transactions_unsupported(&Error::from(ErrorKind::Command(
+            command
+        ))));
+        assert!(!transactions_unsupported(&Error::custom("network down")));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB replica set in MONGO"]
+    async fn rollback_on_failure() {
+        dotenvy::dotenv().ok();
+        let options =
+            crate::mongo_client_options(&std::env::var("MONGO").unwrap(), crate::DEFAULT_APP_NAME)
+                .await
+                .unwrap();
+        let client = mongodb::Client::with_options(options).unwrap();
+        let collection = client
+            .database("journai_test")
+            .collection::<Document>("transaction_rollback");
+        collection.drop(None).await.unwrap();
+        collection
+            .insert_one(doc! { "step": "setup" }, None)
+            .await
+            .unwrap();
+
+        let result = in_transaction(&client, "rollback test", |session| {
+            let collection = collection.clone();
+            Box::pin(async move {
+                let session = session.expect("a replica set supports transactions");
+                collection
+                    .insert_one_with_session(doc! { "step": "first" }, None, session)
+                    .await?;
+                Err::<(), _>(Error::custom("simulated failure"))
+            })
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(collection.count_documents(None, None).await.unwrap(), 1);
+        assert!(collection
+            .find_one(doc! { "step": "first" }, None)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,141 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{http::StatusCode, Extension, Json};
+use axum_thiserror::ErrorStatus;
+use mongodb::{options::ReplaceOptions, Collection};
+use thiserror::Error;
+
+use crate::crypto::{CryptoError, EntryCipher};
+use crate::routes::{date_filter, JournalEntry};
+
+/// Délai pendant lequel la dernière suppression peut être annulée.
+pub const UNDO_TTL: Duration = Duration::from_secs(30);
+
+/// Dernière entrée supprimée, gardée en mémoire le temps de pouvoir l'annuler.
+#[derive(Debug)]
+pub struct DeletedEntries {
+    ttl: Duration,
+    last: Mutex<Option<(Instant, JournalEntry)>>,
+}
+
+impl Default for DeletedEntries {
+    fn default() -> Self {
+        DeletedEntries::with_ttl(UNDO_TTL)
+    }
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum UndoDeleteError {
+    #[error("no deletion to undo")]
+    #[status(StatusCode::NOT_FOUND)]
+    NothingToUndo,
+    #[error("the last deletion can no longer be undone")]
+    #[status(StatusCode::GONE)]
+    Expired,
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Crypto(CryptoError),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+impl DeletedEntries {
+    pub fn with_ttl(ttl: Duration) -> Self {
+        DeletedEntries {
+            ttl,
+            last: Mutex::new(None),
+        }
+    }
+
+    pub fn remember(&self, entry: JournalEntry) {
+        *self.last.lock().unwrap() = Some((Instant::now(), entry));
+    }
+
+    /// Reprend la dernière entrée supprimée, si le délai n'est pas dépassé.
+    pub fn take(&self) -> Result<JournalEntry, UndoDeleteError> {
+        let (deleted_at, entry) = self
+            .last
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or(UndoDeleteError::NothingToUndo)?;
+        if deleted_at.elapsed() > self.ttl {
+            return Err(UndoDeleteError::Expired);
+        }
+        Ok(entry)
+    }
+}
+
+/// Réinsère la dernière entrée supprimée, telle qu'elle était stockée, et la
+/// renvoie déchiffrée.
+pub async fn undo_delete(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(deleted): Extension<Arc<DeletedEntries>>,
+    Extension(cipher): Extension<Arc<EntryCipher>>,
+) -> Result<Json<JournalEntry>, UndoDeleteError> {
+    let entry = deleted.take()?;
+    if let Err(error) = mongo_entries
+        .replace_one(
+            date_filter(entry.date),
+            &entry,
+            ReplaceOptions::builder().upsert(true).build(),
+        )
+        .await
+    {
+        // L'entrée n'est pas perdue : l'annulation pourra être retentée
+        deleted.remember(entry);
+        return Err(UndoDeleteError::Mongo(error));
+    }
+
+    entry
+        .decrypted(&cipher)
+        .map(Json)
+        .map_err(UndoDeleteError::Crypto)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+
+    use crate::routes::JournalEntry;
+
+    use super::{DeletedEntries, UndoDeleteError};
+
+    fn entry() -> JournalEntry {
+        JournalEntry {
+            date: NaiveDate::from_ymd_opt(2024, 1, 24).unwrap(),
+            short_summary: "Supprimée par erreur".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn undo_within_window() {
+        let deleted = DeletedEntries::default();
+        assert!(matches!(
+            deleted.take(),
+            Err(UndoDeleteError::NothingToUndo)
+        ));
+
+        deleted.remember(entry());
+        assert_eq!(deleted.take().unwrap(), entry());
+        assert!(matches!(
+            deleted.take(),
+            Err(UndoDeleteError::NothingToUndo)
+        ));
+    }
+
+    #[test]
+    fn expire_after_ttl() {
+        let deleted = DeletedEntries::with_ttl(Duration::ZERO);
+        deleted.remember(entry());
+        std::thread::sleep(Duration::from_millis(2));
+        assert!(matches!(deleted.take(), Err(UndoDeleteError::Expired)));
+    }
+}
@@ -0,0 +1,266 @@
+use axum::{
+    async_trait,
+    extract::{
+        multipart::{MultipartError, MultipartRejection},
+        rejection::JsonRejection,
+        FromRequest, Multipart, Request,
+    },
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use axum_thiserror::ErrorStatus;
+use chrono::NaiveDate;
+use thiserror::Error;
+
+use crate::routes::CreateJournalEntry;
+
+/// Taille maximale d'un fichier texte importé.
+pub const MAX_UPLOAD_BYTES: usize = 512 * 1024;
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum UploadError {
+    #[error(transparent)]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidForm(MultipartRejection),
+    #[error(transparent)]
+    #[status(StatusCode::BAD_REQUEST)]
+    Multipart(MultipartError),
+    #[error("missing \"{0}\" field")]
+    #[status(StatusCode::BAD_REQUEST)]
+    MissingField(&'static str),
+    #[error("invalid date \"{0}\", expected YYYY-MM-DD")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidDate(String),
+    #[error("only .txt files are accepted")]
+    #[status(StatusCode::UNSUPPORTED_MEDIA_TYPE)]
+    UnsupportedFile,
+    #[error("the file must not be larger than {MAX_UPLOAD_BYTES} bytes")]
+    #[status(StatusCode::PAYLOAD_TOO_LARGE)]
+    FileTooLarge,
+    #[error("the file is not valid UTF-8 text")]
+    #[status(StatusCode::BAD_REQUEST)]
+    NotUtf8,
+}
+
+pub enum EntryPayloadRejection {
+    UnsupportedMediaType(Option<String>),
+    Json(JsonRejection),
+    Upload(UploadError),
+}
+
+impl IntoResponse for EntryPayloadRejection {
+    fn into_response(self) -> Response {
+        match self {
+            EntryPayloadRejection::UnsupportedMediaType(content_type) => (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                format!(
+                    "unsupported Content-Type {}, only application/json is accepted \
+                     (or multipart/form-data to upload a .txt file)",
+                    content_type.map_or("(none)".to_string(), |value| format!("\"{value}\""))
+                ),
+            )
+                .into_response(),
+            EntryPayloadRejection::Json(rejection) => rejection.into_response(),
+            EntryPayloadRejection::Upload(error) => error.into_response(),
+        }
+    }
+}
+
+/// Entrée envoyée en JSON, ou en `multipart/form-data` avec un fichier `.txt`
+/// (champ `file`, utilisé comme `summary`), un champ `date` et un champ `name` optionnel.
+pub struct EntryPayload(pub CreateJournalEntry);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequest<S> for EntryPayload {
+    type Rejection = EntryPayloadRejection;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let content_type = request
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let is_multipart = content_type
+            .as_deref()
+            .is_some_and(|value| value.starts_with("multipart/form-data"));
+        if !is_multipart {
+            if !content_type.as_deref().is_some_and(is_json_content_type) {
+                return Err(EntryPayloadRejection::UnsupportedMediaType(content_type));
+            }
+            let Json(entry) = Json::from_request(request, state)
+                .await
+                .map_err(EntryPayloadRejection::Json)?;
+            return Ok(EntryPayload(entry));
+        }
+
+        let multipart = Multipart::from_request(request, state)
+            .await
+            .map_err(|rejection| {
+                EntryPayloadRejection::Upload(UploadError::InvalidForm(rejection))
+            })?;
+        read_multipart_entry(multipart)
+            .await
+            .map(EntryPayload)
+            .map_err(EntryPayloadRejection::Upload)
+    }
+}
+
+/// `application/json`, ou un sous-type `+json`, comme l'accepte `Json`.
+fn is_json_content_type(content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    essence
+        .strip_prefix("application/")
+        .is_some_and(|subtype| subtype == "json" || subtype.ends_with("+json"))
+}
+
+async fn read_multipart_entry(mut multipart: Multipart) -> Result<CreateJournalEntry, UploadError> {
+    let (mut summary, mut date, mut name) = (None, None, None);
+    while let Some(mut field) = multipart
+        .next_field()
+        .await
+        .map_err(UploadError::Multipart)?
+    {
+        match field.name() {
+            Some("file") => {
+                let is_text = field.file_name().is_some_and(|file| file.ends_with(".txt"))
+                    && field
+                        .content_type()
+                        .is_none_or(|content_type| content_type.starts_with("text/plain"));
+                if !is_text {
+                    return Err(UploadError::UnsupportedFile);
+                }
+
+                let mut content = Vec::new();
+                while let Some(chunk) = field.chunk().await.map_err(UploadError::Multipart)? {
+                    if content.len() + chunk.len() > MAX_UPLOAD_BYTES {
+                        return Err(UploadError::FileTooLarge);
+                    }
+                    content.extend_from_slice(&chunk);
+                }
+                summary = Some(String::from_utf8(content).map_err(|_| UploadError::NotUtf8)?);
+            }
+            Some("date") => {
+                let value = field.text().await.map_err(UploadError::Multipart)?;
+                date = Some(
+                    value
+                        .trim()
+                        .parse::<NaiveDate>()
+                        .map_err(|_| UploadError::InvalidDate(value))?,
+                );
+            }
+            Some("name") => name = Some(field.text().await.map_err(UploadError::Multipart)?),
+            _ => {}
+        }
+    }
+
+    Ok(CreateJournalEntry {
+        name: name.unwrap_or_default(),
+        summary: summary.ok_or(UploadError::MissingField("file"))?,
+        date: date.ok_or(UploadError::MissingField("date"))?,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+        routing::post,
+        Router,
+    };
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    use super::{EntryPayload, MAX_UPLOAD_BYTES};
+
+    const BOUNDARY: &str = "journai-boundary";
+
+    fn multipart_body(file_name: &str, content: &str) -> String {
+        format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"date\"\r\n\r\n2024-01-24\r\n\
+             --{BOUNDARY}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{file_name}\"\r\n\
+             Content-Type: text/plain\r\n\r\n{content}\r\n--{BOUNDARY}--\r\n"
+        )
+    }
+
+    async fn upload(body: String) -> (StatusCode, String) {
+        send(&format!("multipart/form-data; boundary={BOUNDARY}"), body).await
+    }
+
+    async fn send(content_type: &str, body: String) -> (StatusCode, String) {
+        let app = Router::new().route(
+            "/",
+            post(|EntryPayload(entry): EntryPayload| async move {
+                format!("{} {}", entry.date, entry.summary)
+            }),
+        );
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/")
+                    .header("Content-Type", content_type)
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, String::from_utf8_lossy(&body).to_string())
+    }
+
+    #[tokio::test]
+    async fn upload_text_file() {
+        assert_eq!(
+            upload(multipart_body("journal.txt", "J'ai couru 10km")).await,
+            (StatusCode::OK, "2024-01-24 J'ai couru 10km".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn reject_invalid_uploads() {
+        assert_eq!(
+            upload(multipart_body("photo.png", "...")).await.0,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+        assert_eq!(
+            upload(multipart_body(
+                "journal.txt",
+                &"a".repeat(MAX_UPLOAD_BYTES + 1)
+            ))
+            .await
+            .0,
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+        let without_file = format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"date\"\r\n\r\n2024-01-24\r\n--{BOUNDARY}--\r\n"
+        );
+        assert_eq!(upload(without_file).await.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn reject_unsupported_content_type() {
+        let (status, body) = send("text/plain", "J'ai couru 10km".to_string()).await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(
+            body,
+            "unsupported Content-Type \"text/plain\", only application/json is accepted \
+             (or multipart/form-data to upload a .txt file)"
+        );
+
+        let (status, _) = send(
+            "application/json; charset=utf-8",
+            r#"{"name":"Alice","summary":"Bien","date":"2024-01-24"}"#.to_string(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
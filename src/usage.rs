@@ -0,0 +1,139 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use axum::{Extension, Json};
+use serde::{Deserialize, Serialize};
+
+/// Tokens consommés par un appel à OpenAI.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct TokenUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+}
+
+impl From<&async_openai::types::CompletionUsage> for TokenUsage {
+    fn from(usage: &async_openai::types::CompletionUsage) -> Self {
+        TokenUsage {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+        }
+    }
+}
+
+/// Un embedding ne consomme que des tokens de prompt.
+impl From<&async_openai::types::EmbeddingUsage> for TokenUsage {
+    fn from(usage: &async_openai::types::EmbeddingUsage) -> Self {
+        TokenUsage {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: 0,
+        }
+    }
+}
+
+impl std::ops::Add for TokenUsage {
+    type Output = TokenUsage;
+
+    fn add(self, other: TokenUsage) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens: self.prompt_tokens + other.prompt_tokens,
+            completion_tokens: self.completion_tokens + other.completion_tokens,
+        }
+    }
+}
+
+/// Tarif en dollars pour 1000 tokens, par défaut celui de gpt-3.5-turbo.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TokenPricing {
+    pub prompt_per_1k: f64,
+    pub completion_per_1k: f64,
+}
+
+impl Default for TokenPricing {
+    fn default() -> Self {
+        TokenPricing {
+            prompt_per_1k: 0.0005,
+            completion_per_1k: 0.0015,
+        }
+    }
+}
+
+/// Compteur des tokens consommés depuis le démarrage du serveur.
+#[derive(Debug, Default)]
+pub struct UsageCounter {
+    pricing: TokenPricing,
+    prompt_tokens: AtomicU64,
+    completion_tokens: AtomicU64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct UsageStats {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+    pub estimated_cost: f64,
+}
+
+impl UsageCounter {
+    pub fn new(pricing: TokenPricing) -> Self {
+        UsageCounter {
+            pricing,
+            ..Default::default()
+        }
+    }
+
+    pub fn record(&self, usage: Option<TokenUsage>) {
+        if let Some(usage) = usage {
+            self.prompt_tokens
+                .fetch_add(u64::from(usage.prompt_tokens), Ordering::Relaxed);
+            self.completion_tokens
+                .fetch_add(u64::from(usage.completion_tokens), Ordering::Relaxed);
+        }
+    }
+
+    pub fn stats(&self) -> UsageStats {
+        let prompt_tokens = self.prompt_tokens.load(Ordering::Relaxed);
+        let completion_tokens = self.completion_tokens.load(Ordering::Relaxed);
+        UsageStats {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+            estimated_cost: (prompt_tokens as f64 * self.pricing.prompt_per_1k
+                + completion_tokens as f64 * self.pricing.completion_per_1k)
+                / 1000.0,
+        }
+    }
+}
+
+pub async fn get_usage(Extension(usage): Extension<Arc<UsageCounter>>) -> Json<UsageStats> {
+    Json(usage.stats())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TokenPricing, TokenUsage, UsageCounter};
+
+    #[test]
+    fn accumulate_usage() {
+        let counter = UsageCounter::new(TokenPricing {
+            prompt_per_1k: 0.5,
+            completion_per_1k: 1.5,
+        });
+        counter.record(Some(TokenUsage {
+            prompt_tokens: 1200,
+            completion_tokens: 300,
+        }));
+        counter.record(None);
+        counter.record(Some(TokenUsage {
+            prompt_tokens: 800,
+            completion_tokens: 700,
+        }));
+
+        let stats = counter.stats();
+        assert_eq!(stats.prompt_tokens, 2000);
+        assert_eq!(stats.completion_tokens, 1000);
+        assert_eq!(stats.total_tokens, 3000);
+        assert_eq!(stats.estimated_cost, 2.5);
+    }
+}
@@ -0,0 +1,288 @@
+use std::sync::Arc;
+
+use async_openai::{config::OpenAIConfig, Client};
+use axum::{
+    extract::Query,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{NaiveDate, Utc};
+use mongodb::Collection;
+use serde::Deserialize;
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+
+use crate::config::AppConfig;
+use crate::crypto::EntryCipher;
+use crate::revisions::EntryRevision;
+use crate::routes::{
+    process_journal_entry, CreateJournalEntry, CreateJournalEntryError, JournalEntry,
+};
+use crate::throttle::OpenAiThrottle;
+use crate::usage::UsageCounter;
+
+/// Route authentifiée par son propre secret plutôt que par `X-API-Key`, que
+/// les services tiers ne savent pas toujours envoyer.
+pub const WEBHOOK_ROUTE: &str = "/webhook/entry";
+pub const WEBHOOK_SECRET_HEADER: &str = "X-Webhook-Secret";
+/// Longueur maximale du nom de la source.
+pub const MAX_SOURCE_LENGTH: usize = 32;
+
+/// Format générique accepté depuis IFTTT, Zapier, un bot Telegram…
+#[derive(Deserialize, Debug, Clone)]
+pub struct WebhookEntry {
+    pub text: String,
+    /// Aujourd'hui, dans le fuseau configuré, par défaut.
+    pub date: Option<NaiveDate>,
+    pub source: String,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct WebhookQuery {
+    /// Secret passé dans l'URL, pour les services sans en-têtes personnalisés.
+    pub secret: Option<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum WebhookError {
+    #[error("missing or invalid webhook secret")]
+    Unauthorized,
+    #[error("text cannot be empty")]
+    EmptyText,
+    #[error("source must be between 1 and {MAX_SOURCE_LENGTH} characters")]
+    InvalidSource,
+    #[error(transparent)]
+    Create(CreateJournalEntryError),
+}
+
+impl IntoResponse for WebhookError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            WebhookError::Unauthorized => StatusCode::UNAUTHORIZED,
+            WebhookError::EmptyText | WebhookError::InvalidSource => StatusCode::BAD_REQUEST,
+            // Une saisie invalide reste une erreur de l'intégrateur, pas du serveur
+            WebhookError::Create(error) => return error.into_response(),
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// Secret de l'en-tête, sinon de l'URL, comparé en temps constant. Sans secret
+/// configuré, le webhook est fermé.
+pub fn accepts_webhook_secret(
+    expected: Option<&str>,
+    headers: &HeaderMap,
+    query: &WebhookQuery,
+) -> bool {
+    let provided = headers
+        .get(WEBHOOK_SECRET_HEADER)
+        .map(|secret| secret.as_bytes())
+        .or(query.secret.as_deref().map(str::as_bytes));
+    match (expected, provided) {
+        (Some(expected), Some(secret)) if !expected.is_empty() => {
+            bool::from(expected.as_bytes().ct_eq(secret))
+        }
+        _ => false,
+    }
+}
+
+impl WebhookEntry {
+    // Erreur partagée avec le handler, dont la création d'entrée fait toute la taille
+    #[allow(clippy::result_large_err)]
+    pub fn into_entry(self, today: NaiveDate) -> Result<CreateJournalEntry, WebhookError> {
+        if self.text.trim().is_empty() {
+            return Err(WebhookError::EmptyText);
+        }
+        let source = self.source.trim().to_lowercase();
+        if source.is_empty() || source.chars().count() > MAX_SOURCE_LENGTH {
+            return Err(WebhookError::InvalidSource);
+        }
+
+        Ok(CreateJournalEntry {
+            summary: self.text,
+            date: self.date.unwrap_or(today),
+            source: Some(source),
+            ..Default::default()
+        })
+    }
+}
+
+/// Crée l'entrée par le même pipeline que `POST /`, en retenant sa source.
+#[allow(clippy::too_many_arguments)]
+pub async fn create_entry_from_webhook(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(revisions): Extension<Arc<Collection<EntryRevision>>>,
+    Extension(openai): Extension<Arc<Client<OpenAIConfig>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(cipher): Extension<Arc<EntryCipher>>,
+    Extension(usage): Extension<Arc<UsageCounter>>,
+    throttle: Option<Extension<Arc<OpenAiThrottle>>>,
+    Query(query): Query<WebhookQuery>,
+    headers: HeaderMap,
+    Json(payload): Json<WebhookEntry>,
+) -> Result<Json<JournalEntry>, WebhookError> {
+    // Le secret est vérifié avant que l'analyse ne prenne un tour dans la file OpenAI
+    if !accepts_webhook_secret(config.webhook_secret.as_deref(), &headers, &query) {
+        return Err(WebhookError::Unauthorized);
+    }
+    let journal_entry =
+        payload.into_entry(Utc::now().with_timezone(&config.timezone).date_naive())?;
+
+    let entry = process_journal_entry(
+        &mongo_entries,
+        &revisions,
+        &openai,
+        throttle.as_deref().map(Arc::as_ref),
+        &config,
+        &cipher,
+        journal_entry,
+        false,
+    )
+    .await
+    .map_err(WebhookError::Create)?;
+    usage.record(entry.tokens);
+    Ok(Json(entry))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{
+        extract::Query,
+        http::{HeaderMap, StatusCode},
+        response::IntoResponse,
+        Extension, Json,
+    };
+    use chrono::NaiveDate;
+
+    use crate::{
+        config::AppConfig,
+        crypto::EntryCipher,
+        revisions::EntryRevision,
+        routes::{prepare_journal_entry, JournalEntry},
+        testing::{mock_chat_completion, mock_openai},
+        usage::UsageCounter,
+    };
+
+    use super::{
+        accepts_webhook_secret, create_entry_from_webhook, WebhookEntry, WebhookError, WebhookQuery,
+    };
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, 14).unwrap()
+    }
+
+    fn payload(source: &str) -> WebhookEntry {
+        serde_json::from_value(serde_json::json!({
+            "text": "Rendu du projet, puis apéro avec l'équipe.",
+            "source": source,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn create_entry_from_valid_payload() {
+        let journal_entry = payload(" Telegram ").into_entry(today()).unwrap();
+        assert_eq!(journal_entry.date, today());
+        assert_eq!(journal_entry.source.as_deref(), Some("telegram"));
+
+        let openai = mock_chat_completion(
+            r#"{"date":"2024-03-14","rate":8.0,"short_summary":"Projet rendu","tags":["travail"]}"#,
+        )
+        .await;
+        let entry =
+            prepare_journal_entry(&openai, None, &AppConfig::default(), journal_entry, false)
+                .await
+                .unwrap();
+        assert_eq!(entry.short_summary, "Projet rendu");
+        assert_eq!(entry.source.as_deref(), Some("telegram"));
+
+        let dated = WebhookEntry {
+            date: NaiveDate::from_ymd_opt(2024, 3, 1),
+            ..payload("ifttt")
+        };
+        assert_eq!(
+            dated.into_entry(today()).unwrap().date,
+            NaiveDate::from_ymd_opt(2024, 3, 1).unwrap()
+        );
+        assert!(matches!(
+            payload("").into_entry(today()),
+            Err(WebhookError::InvalidSource)
+        ));
+    }
+
+    #[test]
+    fn check_webhook_secret() {
+        let mut headers = HeaderMap::new();
+        let in_url = WebhookQuery {
+            secret: Some("s3cret".to_string()),
+        };
+        assert!(accepts_webhook_secret(Some("s3cret"), &headers, &in_url));
+        assert!(!accepts_webhook_secret(None, &headers, &in_url));
+
+        headers.insert("X-Webhook-Secret", "s3cret".parse().unwrap());
+        assert!(accepts_webhook_secret(
+            Some("s3cret"),
+            &headers,
+            &WebhookQuery::default()
+        ));
+        assert!(!accepts_webhook_secret(
+            Some("other"),
+            &headers,
+            &WebhookQuery::default()
+        ));
+    }
+
+    /// Webhook appelé avec `secret` sur une base et un OpenAI jamais contactés :
+    /// la requête doit échouer avant tout accès externe.
+    async fn offline_webhook(
+        config: AppConfig,
+        secret: &str,
+        payload: WebhookEntry,
+    ) -> Result<Json<JournalEntry>, WebhookError> {
+        let database = mongodb::Client::with_uri_str("mongodb://localhost:27017")
+            .await
+            .unwrap()
+            .database("journai_test");
+        create_entry_from_webhook(
+            Extension(Arc::new(database.collection::<JournalEntry>("entries"))),
+            Extension(Arc::new(
+                database.collection::<EntryRevision>("entry_revisions"),
+            )),
+            Extension(Arc::new(mock_openai(axum::Router::new()).await)),
+            Extension(Arc::new(AppConfig {
+                webhook_secret: Some("s3cret".to_string()),
+                ..config
+            })),
+            Extension(Arc::new(EntryCipher::default())),
+            Extension(Arc::new(UsageCounter::new(Default::default()))),
+            None,
+            Query(WebhookQuery {
+                secret: Some(secret.to_string()),
+            }),
+            HeaderMap::new(),
+            Json(payload),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn reject_invalid_secret() {
+        let result = offline_webhook(AppConfig::default(), "guess", payload("zapier")).await;
+        assert!(matches!(result, Err(WebhookError::Unauthorized)));
+    }
+
+    #[tokio::test]
+    async fn reject_too_long_text_as_bad_request() {
+        let config = AppConfig {
+            max_summary_length: 10,
+            ..Default::default()
+        };
+        let Err(error) = offline_webhook(config, "s3cret", payload("zapier")).await else {
+            panic!("expected the text to be rejected");
+        };
+        assert_eq!(error.into_response().status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,531 @@
+use std::{cmp::Reverse, collections::BTreeMap, sync::Arc};
+
+use async_openai::{
+    config::OpenAIConfig,
+    error::OpenAIError,
+    types::{
+        ChatCompletionRequestMessage, ChatCompletionRequestSystemMessageArgs,
+        ChatCompletionRequestUserMessageArgs, CreateChatCompletionRequestArgs,
+    },
+    Client,
+};
+use axum::{extract::Path, http::StatusCode, Extension, Json};
+use axum_thiserror::ErrorStatus;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures_util::TryStreamExt;
+use mongodb::{
+    bson::{self, doc, Document},
+    options::ReplaceOptions,
+    Collection,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config::AppConfig;
+use crate::recap::{rated_period_entries, stored_period_filter, MonthlyRecap};
+use crate::routes::JournalEntry;
+use crate::stats::{rated_filter, unrated_filter, RatedStats};
+use crate::throttle::{OpenAiThrottle, ThrottleError};
+use crate::usage::{TokenUsage, UsageCounter};
+
+/// Nombre de topics dominants retenus pour l'année.
+pub const YEARLY_TOP_TOPICS: usize = 5;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MonthRate {
+    /// Mois au format `2024-03`.
+    pub month: String,
+    pub average_rate: f32,
+    pub count: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TopicCount {
+    pub topic: String,
+    pub count: u32,
+}
+
+/// Agrégations de l'année, calculées sans GPT.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct YearlyStats {
+    pub entries_count: u32,
+    pub average_rate: Option<f32>,
+    pub best_month: Option<MonthRate>,
+    pub worst_month: Option<MonthRate>,
+    pub top_topics: Vec<TopicCount>,
+    /// Mois ayant au moins une entrée, dans l'ordre.
+    pub months: Vec<MonthRate>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct YearlyRecap {
+    pub year: i32,
+    #[serde(flatten)]
+    pub stats: YearlyStats,
+    pub narrative: String,
+    /// Entrées de l'année ignorées faute de note.
+    #[serde(default)]
+    pub excluded_count: u64,
+    /// Dernière modification des entrées de l'année lors de la génération.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entries_updated_at: Option<DateTime<Utc>>,
+    pub generated_at: DateTime<Utc>,
+}
+
+/// Empreinte des entrées notées de l'année : toute création, suppression ou
+/// modification change leur nombre ou leur dernier `updated_at`.
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct YearFingerprint {
+    pub count: u32,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl YearlyRecap {
+    /// Le récapitulatif en cache reste valable tant que les entrées n'ont pas changé.
+    pub fn is_up_to_date(&self, fingerprint: &YearFingerprint) -> bool {
+        self.stats.entries_count == fingerprint.count
+            && self.entries_updated_at == fingerprint.updated_at
+    }
+}
+
+pub fn year_fingerprint_pipeline(rated: Document) -> Vec<Document> {
+    vec![
+        doc! { "$match": rated },
+        doc! { "$group": {
+            "_id": null,
+            "count": { "$sum": 1 },
+            "updated_at": { "$max": "$updated_at" }
+        } },
+    ]
+}
+
+pub fn yearly_stats(entries: &[JournalEntry]) -> YearlyStats {
+    let mut months: BTreeMap<String, (f32, u32)> = BTreeMap::new();
+    let mut topics: BTreeMap<String, u32> = BTreeMap::new();
+    for entry in entries {
+        let (total, count) = months
+            .entry(entry.date.format("%Y-%m").to_string())
+            .or_default();
+        *total += entry.rate;
+        *count += 1;
+        for tag in &entry.tags {
+            *topics.entry(tag.to_lowercase()).or_default() += 1;
+        }
+    }
+
+    let months: Vec<MonthRate> = months
+        .into_iter()
+        .map(|(month, (total, count))| MonthRate {
+            month,
+            average_rate: total / count as f32,
+            count,
+        })
+        .collect();
+    let mut top_topics: Vec<TopicCount> = topics
+        .into_iter()
+        .map(|(topic, count)| TopicCount { topic, count })
+        .collect();
+    // Tri stable : à nombre égal, l'ordre alphabétique est conservé
+    top_topics.sort_by_key(|topic| Reverse(topic.count));
+    top_topics.truncate(YEARLY_TOP_TOPICS);
+
+    YearlyStats {
+        entries_count: entries.len() as u32,
+        average_rate: (!entries.is_empty())
+            .then(|| entries.iter().map(|entry| entry.rate).sum::<f32>() / entries.len() as f32),
+        best_month: months
+            .iter()
+            .max_by(|a, b| a.average_rate.total_cmp(&b.average_rate))
+            .cloned(),
+        worst_month: months
+            .iter()
+            .min_by(|a, b| a.average_rate.total_cmp(&b.average_rate))
+            .cloned(),
+        top_topics,
+        months,
+    }
+}
+
+/// Message utilisateur : un mois par ligne avec son récapitulatif s'il a été
+/// généré, puis les topics dominants.
+pub fn yearly_message(stats: &YearlyStats, monthly_recaps: &[MonthlyRecap]) -> String {
+    let mut lines: Vec<String> = stats
+        .months
+        .iter()
+        .map(|month| {
+            let recap = monthly_recaps
+                .iter()
+                .find(|recap| recap.month == month.month)
+                .map_or("no recap", |recap| recap.summary.as_str());
+            format!(
+                "{} ({:.1}/10 over {} entries): {recap}",
+                month.month, month.average_rate, month.count
+            )
+        })
+        .collect();
+    let topics: Vec<&str> = stats
+        .top_topics
+        .iter()
+        .map(|topic| topic.topic.as_str())
+        .collect();
+    lines.push(format!("Topics: {}", topics.join(", ")));
+    lines.join("\n")
+}
+
+#[derive(Deserialize, Debug)]
+struct YearlyRecapResponse {
+    narrative: String,
+}
+
+#[derive(Error, Debug, ErrorStatus)]
+pub enum YearlyRecapError {
+    #[error("invalid year {0}")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidYear(i32),
+    #[error(transparent)]
+    #[status(StatusCode::TOO_MANY_REQUESTS)]
+    Throttle(ThrottleError),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    OpenAI(OpenAIError),
+    #[error("no output from GPT-4")]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    NoOutput,
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Parse(serde_json::Error),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Bson(bson::de::Error),
+    #[error(transparent)]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    Mongo(mongodb::error::Error),
+}
+
+/// Demande à GPT la rétrospective de l'année. Une année sans entrée ne donne
+/// lieu à aucun appel.
+pub async fn generate_yearly_recap(
+    openai: &Client<OpenAIConfig>,
+    usage: &UsageCounter,
+    model: &str,
+    year: i32,
+    entries: &[JournalEntry],
+    monthly_recaps: &[MonthlyRecap],
+) -> Result<YearlyRecap, YearlyRecapError> {
+    let stats = yearly_stats(entries);
+    if entries.is_empty() {
+        return Ok(YearlyRecap {
+            year,
+            stats,
+            narrative: "Aucune entrée n'a été écrite cette année.".to_string(),
+            excluded_count: 0,
+            entries_updated_at: None,
+            generated_at: Utc::now(),
+        });
+    }
+
+    let completion_request = CreateChatCompletionRequestArgs::default()
+        .model(model)
+        .messages(vec![
+            ChatCompletionRequestMessage::System(
+                ChatCompletionRequestSystemMessageArgs::default()
+                    .content(include_str!("./yearly_recap_message.txt"))
+                    .build()
+                    .map_err(YearlyRecapError::OpenAI)?,
+            ),
+            ChatCompletionRequestMessage::User(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content(yearly_message(&stats, monthly_recaps))
+                    .build()
+                    .map_err(YearlyRecapError::OpenAI)?,
+            ),
+        ])
+        .n(1)
+        .build()
+        .map_err(YearlyRecapError::OpenAI)?;
+
+    let response = openai
+        .chat()
+        .create(completion_request)
+        .await
+        .map_err(YearlyRecapError::OpenAI)?;
+    usage.record(response.usage.as_ref().map(TokenUsage::from));
+    let content = response
+        .choices
+        .first()
+        .and_then(|o| o.message.clone().content)
+        .filter(|content| !content.trim().is_empty())
+        .ok_or(YearlyRecapError::NoOutput)?;
+    let response =
+        serde_json::from_str::<YearlyRecapResponse>(&content).map_err(YearlyRecapError::Parse)?;
+
+    Ok(YearlyRecap {
+        year,
+        stats,
+        narrative: response.narrative.trim().to_string(),
+        excluded_count: 0,
+        entries_updated_at: None,
+        generated_at: Utc::now(),
+    })
+}
+
+/// Rétrospective de l'année, relue depuis `yearly_recaps` tant qu'aucune entrée
+/// de l'année n'a été ajoutée, supprimée ou modifiée, régénérée sinon. Seule la
+/// régénération attend son tour dans la file OpenAI.
+#[allow(clippy::too_many_arguments)]
+pub async fn get_yearly_recap(
+    Extension(mongo_entries): Extension<Arc<Collection<JournalEntry>>>,
+    Extension(mongo_monthly_recaps): Extension<Arc<Collection<MonthlyRecap>>>,
+    Extension(mongo_yearly_recaps): Extension<Arc<Collection<YearlyRecap>>>,
+    Extension(openai): Extension<Arc<Client<OpenAIConfig>>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(usage): Extension<Arc<UsageCounter>>,
+    throttle: Option<Extension<Arc<OpenAiThrottle>>>,
+    Path(year): Path<i32>,
+) -> Result<Json<YearlyRecap>, YearlyRecapError> {
+    if !(1..=9999).contains(&year) {
+        return Err(YearlyRecapError::InvalidYear(year));
+    }
+    let first = NaiveDate::from_ymd_opt(year, 1, 1).unwrap();
+    let last = NaiveDate::from_ymd_opt(year, 12, 31).unwrap();
+    let mut rated = stored_period_filter(first, last);
+    rated.extend(rated_filter());
+
+    let fingerprint: YearFingerprint = match mongo_entries
+        .aggregate(year_fingerprint_pipeline(rated), None)
+        .await
+        .map_err(YearlyRecapError::Mongo)?
+        .try_next()
+        .await
+        .map_err(YearlyRecapError::Mongo)?
+    {
+        Some(fingerprint) => bson::from_document(fingerprint).map_err(YearlyRecapError::Bson)?,
+        None => YearFingerprint::default(),
+    };
+    if let Some(cached) = mongo_yearly_recaps
+        .find_one(doc! { "year": year }, None)
+        .await
+        .map_err(YearlyRecapError::Mongo)?
+        .filter(|cached| cached.is_up_to_date(&fingerprint))
+    {
+        // Les brouillons n'entrent pas dans l'empreinte : leur nombre est relu
+        let mut unrated = stored_period_filter(first, last);
+        unrated.extend(unrated_filter());
+        let excluded_count = mongo_entries
+            .count_documents(unrated, None)
+            .await
+            .map_err(YearlyRecapError::Mongo)?;
+        return Ok(Json(YearlyRecap {
+            excluded_count,
+            ..cached
+        }));
+    }
+
+    let RatedStats {
+        stats: entries,
+        excluded_count,
+    } = rated_period_entries(&mongo_entries, first, last)
+        .await
+        .map_err(YearlyRecapError::Mongo)?;
+    let monthly_recaps: Vec<MonthlyRecap> = mongo_monthly_recaps
+        .find(doc! { "month": { "$regex": format!("^{year}-") } }, None)
+        .await
+        .map_err(YearlyRecapError::Mongo)?
+        .try_collect()
+        .await
+        .map_err(YearlyRecapError::Mongo)?;
+
+    if let (false, Some(Extension(throttle))) = (entries.is_empty(), throttle) {
+        throttle
+            .acquire()
+            .await
+            .map_err(YearlyRecapError::Throttle)?;
+    }
+    let recap = YearlyRecap {
+        excluded_count,
+        entries_updated_at: fingerprint.updated_at,
+        ..generate_yearly_recap(
+            &openai,
+            &usage,
+            &config.chat_model,
+            year,
+            &entries,
+            &monthly_recaps,
+        )
+        .await?
+    };
+    mongo_yearly_recaps
+        .replace_one(
+            doc! { "year": year },
+            &recap,
+            ReplaceOptions::builder().upsert(true).build(),
+        )
+        .await
+        .map_err(YearlyRecapError::Mongo)?;
+
+    Ok(Json(recap))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::Router;
+    use chrono::{NaiveDate, Utc};
+    use mongodb::bson;
+
+    use crate::{
+        config::CHAT_MODEL,
+        recap::MonthlyRecap,
+        routes::JournalEntry,
+        testing::{mock_chat_completion, mock_openai},
+        usage::UsageCounter,
+    };
+
+    use super::{generate_yearly_recap, yearly_message, MonthRate, YearFingerprint, YearlyRecap};
+
+    /// Une entrée le 5 et le 20 de chaque mois, l'été étant le meilleur moment
+    /// et novembre le plus dur.
+    fn simulated_year() -> Vec<JournalEntry> {
+        (1..=12)
+            .flat_map(|month| {
+                let rate = match month {
+                    7 => 9.0,
+                    11 => 3.0,
+                    _ => 6.0,
+                };
+                [5, 20].map(|day| JournalEntry {
+                    date: NaiveDate::from_ymd_opt(2024, month, day).unwrap(),
+                    rate,
+                    short_summary: format!("Jour {day}"),
+                    tags: match month {
+                        6..=8 => vec!["Vacances".to_string(), "sport".to_string()],
+                        _ => vec!["cours".to_string()],
+                    },
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn recap_a_simulated_year() {
+        let openai =
+            mock_chat_completion(r#"{"narrative":" Une année rythmée par les cours. "}"#).await;
+        let monthly_recaps = [MonthlyRecap {
+            month: "2024-07".to_string(),
+            summary: "Un été au soleil.".to_string(),
+            felt_rate: Some(8.5),
+            entries_count: 2,
+            excluded_count: 0,
+            generated_at: Utc::now(),
+        }];
+
+        let recap = generate_yearly_recap(
+            &openai,
+            &UsageCounter::default(),
+            CHAT_MODEL,
+            2024,
+            &simulated_year(),
+            &monthly_recaps,
+        )
+        .await
+        .unwrap();
+        assert_eq!(recap.narrative, "Une année rythmée par les cours.");
+        assert_eq!(recap.stats.entries_count, 24);
+        assert_eq!(recap.stats.average_rate, Some(6.0));
+        assert_eq!(
+            recap.stats.best_month,
+            Some(MonthRate {
+                month: "2024-07".to_string(),
+                average_rate: 9.0,
+                count: 2
+            })
+        );
+        assert_eq!(
+            recap
+                .stats
+                .worst_month
+                .as_ref()
+                .map(|month| month.month.as_str()),
+            Some("2024-11")
+        );
+        let topics: Vec<(&str, u32)> = recap
+            .stats
+            .top_topics
+            .iter()
+            .map(|topic| (topic.topic.as_str(), topic.count))
+            .collect();
+        assert_eq!(topics, [("cours", 18), ("sport", 6), ("vacances", 6)]);
+
+        let message = yearly_message(&recap.stats, &monthly_recaps);
+        assert!(message.contains("2024-07 (9.0/10 over 2 entries): Un été au soleil."));
+        assert!(message.contains("2024-01 (6.0/10 over 2 entries): no recap"));
+        assert!(message.ends_with("Topics: cours, sport, vacances"));
+
+        // Mise en cache dans `yearly_recaps` telle quelle
+        let stored = bson::to_document(&recap).unwrap();
+        assert!(stored.contains_key("entries_count") && !stored.contains_key("stats"));
+        assert_eq!(bson::from_document::<YearlyRecap>(stored).unwrap(), recap);
+    }
+
+    #[tokio::test]
+    async fn invalidate_cache_when_an_entry_changes() {
+        let openai = mock_openai(Router::new()).await;
+        let edited_at = Utc::now();
+        let recap = YearlyRecap {
+            entries_updated_at: Some(edited_at),
+            ..generate_yearly_recap(
+                &openai,
+                &UsageCounter::default(),
+                CHAT_MODEL,
+                2024,
+                &[],
+                &[],
+            )
+            .await
+            .unwrap()
+        };
+
+        let fingerprint = YearFingerprint {
+            count: 0,
+            updated_at: Some(edited_at),
+        };
+        assert!(recap.is_up_to_date(&fingerprint));
+        // Même nombre d'entrées, mais une note modifiée depuis
+        assert!(!recap.is_up_to_date(&YearFingerprint {
+            updated_at: Some(edited_at + chrono::Duration::seconds(1)),
+            ..fingerprint.clone()
+        }));
+        assert!(!recap.is_up_to_date(&YearFingerprint {
+            count: 1,
+            ..fingerprint
+        }));
+
+        let fingerprint: YearFingerprint = bson::from_document(bson::doc! {
+            "_id": null,
+            "count": 3,
+            "updated_at": bson::to_bson(&edited_at).unwrap(),
+        })
+        .unwrap();
+        assert_eq!(fingerprint.updated_at, Some(edited_at));
+    }
+
+    #[tokio::test]
+    async fn empty_year_skips_gpt() {
+        let openai = mock_openai(Router::new()).await;
+
+        let recap = generate_yearly_recap(
+            &openai,
+            &UsageCounter::default(),
+            CHAT_MODEL,
+            2019,
+            &[],
+            &[],
+        )
+        .await
+        .unwrap();
+        assert_eq!(recap.stats.entries_count, 0);
+        assert_eq!(recap.stats.average_rate, None);
+        assert_eq!(recap.stats.best_month, None);
+        assert!(recap.stats.top_topics.is_empty());
+    }
+}